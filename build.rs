@@ -0,0 +1,18 @@
+// Expose the target triple to the `--version` banner, and the compiler
+// and optimization settings to the run identity
+fn main() {
+    println!("cargo:rustc-env=TARGET_TRIPLE={}", std::env::var("TARGET").unwrap_or_default());
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=RUSTC_VERSION={}", version);
+    println!("cargo:rustc-env=BUILD_OPT_LEVEL={}", std::env::var("OPT_LEVEL").unwrap_or_default());
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,581 @@
+use crate::high_resolution_timer::{aux_cpu, read_tsc_aux};
+
+// ============================================================
+// CPU affinity
+// ============================================================
+
+/// Pin the calling thread to `cpu`.
+///
+/// Pin before the first `tick_hz()` so calibration and measurement run
+/// on the same core; a per-core TSC offset/rate would otherwise skew
+/// every conversion.
+pub fn pin_to_cpu(cpu: usize) -> Result<(), String> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        // CPU_SETSIZE is a c_int on Linux but already usize on Android
+        #[allow(clippy::unnecessary_cast)]
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(format!("cpu {} out of range", cpu));
+        }
+        unsafe {
+            let mut set: libc::cpu_set_t = core::mem::zeroed();
+            libc::CPU_SET(cpu, &mut set);
+            if libc::sched_setaffinity(0, core::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(format!("sched_setaffinity failed: {}", std::io::Error::last_os_error()));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadAffinityMask};
+
+        if cpu >= usize::BITS as usize {
+            return Err(format!("cpu {} out of range", cpu));
+        }
+        let previous = unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << cpu) };
+        if previous == 0 {
+            return Err(format!("SetThreadAffinityMask failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    {
+        Err(format!("pinning to cpu {} is not supported on {}", cpu, std::env::consts::OS))
+    }
+}
+
+/// Logical CPUs this process may use (`available_parallelism`); 1, with
+/// a one-time warning, where that can't be determined (some minimal
+/// containers and sandboxes)
+pub fn logical_cpus() -> usize {
+    logical_cpus_or_one(std::thread::available_parallelism())
+}
+
+/// `logical_cpus` given the result of the underlying query
+pub fn logical_cpus_or_one(detected: std::io::Result<std::num::NonZeroUsize>) -> usize {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    match detected {
+        Ok(n) => n.get(),
+        Err(err) => {
+            WARNED.call_once(|| eprintln!("Warning: CPU count unavailable ({}); assuming 1", err));
+            1
+        }
+    }
+}
+
+/// Ids of the online CPUs: /sys/devices/system/cpu/online on Linux,
+/// else 0..logical_cpus()
+pub fn online_cpus() -> Vec<usize> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Ok(list) = std::fs::read_to_string("/sys/devices/system/cpu/online") {
+        let cpus = parse_cpu_list(&list);
+        if !cpus.is_empty() {
+            return cpus;
+        }
+    }
+    (0..logical_cpus()).collect()
+}
+
+/// Core id of the calling thread, as cheaply as this machine allows:
+/// the TSC_AUX cpu field via rdtscp, else `current_cpu()`
+pub fn current_core_id() -> Option<u32> {
+    match read_tsc_aux() {
+        Some(reading) => Some(aux_cpu(reading.aux)),
+        None => current_cpu().map(|cpu| cpu as u32),
+    }
+}
+
+/// CPU the calling thread is currently running on, if the OS says
+pub fn current_cpu() -> Option<usize> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let cpu = unsafe { libc::sched_getcpu() };
+        if cpu < 0 { None } else { Some(cpu as usize) }
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::GetCurrentProcessorNumber;
+
+        Some(unsafe { GetCurrentProcessorNumber() } as usize)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    {
+        None
+    }
+}
+
+// ============================================================
+// Scheduling policy
+// ============================================================
+
+/// Scheduling policy of the calling thread, e.g. "SCHED_OTHER"; on
+/// Windows the process priority class and thread priority, e.g.
+/// "HIGH_PRIORITY_CLASS, thread HIGHEST".
+///
+/// "unknown" where the OS has neither to report.
+pub fn scheduling_policy() -> String {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        match unsafe { libc::sched_getscheduler(0) } {
+            -1 => format!("unknown ({})", std::io::Error::last_os_error()),
+            policy => policy_name(policy),
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentProcess, GetCurrentThread, GetPriorityClass, GetThreadPriority, ABOVE_NORMAL_PRIORITY_CLASS,
+            BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS, IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+            REALTIME_PRIORITY_CLASS,
+        };
+
+        let (class, thread) = unsafe { (GetPriorityClass(GetCurrentProcess()), GetThreadPriority(GetCurrentThread())) };
+        let class = match class {
+            IDLE_PRIORITY_CLASS => "IDLE_PRIORITY_CLASS".to_string(),
+            BELOW_NORMAL_PRIORITY_CLASS => "BELOW_NORMAL_PRIORITY_CLASS".to_string(),
+            NORMAL_PRIORITY_CLASS => "NORMAL_PRIORITY_CLASS".to_string(),
+            ABOVE_NORMAL_PRIORITY_CLASS => "ABOVE_NORMAL_PRIORITY_CLASS".to_string(),
+            HIGH_PRIORITY_CLASS => "HIGH_PRIORITY_CLASS".to_string(),
+            REALTIME_PRIORITY_CLASS => "REALTIME_PRIORITY_CLASS".to_string(),
+            0 => format!("unknown ({})", std::io::Error::last_os_error()),
+            other => format!("class {:#x}", other),
+        };
+        let thread = match thread {
+            -15 => "IDLE".to_string(),
+            -2 => "LOWEST".to_string(),
+            -1 => "BELOW_NORMAL".to_string(),
+            0 => "NORMAL".to_string(),
+            1 => "ABOVE_NORMAL".to_string(),
+            2 => "HIGHEST".to_string(),
+            15 => "TIME_CRITICAL".to_string(),
+            THREAD_PRIORITY_ERROR_RETURN => "unknown".to_string(),
+            other => other.to_string(),
+        };
+        format!("{}, thread {}", class, thread)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    {
+        "unknown".to_string()
+    }
+}
+
+/// Name of a `sched_getscheduler` policy value
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn policy_name(policy: libc::c_int) -> String {
+    match policy {
+        // SCHED_OTHER (Android's libc only names it SCHED_NORMAL)
+        0 => "SCHED_OTHER".to_string(),
+        libc::SCHED_FIFO => "SCHED_FIFO".to_string(),
+        libc::SCHED_RR => "SCHED_RR".to_string(),
+        libc::SCHED_BATCH => "SCHED_BATCH".to_string(),
+        libc::SCHED_IDLE => "SCHED_IDLE".to_string(),
+        other => format!("policy {}", other),
+    }
+}
+
+/// Move the calling thread to SCHED_FIFO at the lowest realtime
+/// priority, enough to stop ordinary threads preempting it.
+///
+/// Needs CAP_SYS_NICE (or root) on Linux; unsupported elsewhere.
+pub fn set_realtime_priority() -> Result<(), String> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        unsafe {
+            // zeroed(): musl's sched_param has extra reserved fields
+            let mut param: libc::sched_param = core::mem::zeroed();
+            param.sched_priority = libc::sched_get_priority_min(libc::SCHED_FIFO);
+            if libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) != 0 {
+                let err = std::io::Error::last_os_error();
+                if err.raw_os_error() == Some(libc::EPERM) {
+                    return Err("SCHED_FIFO needs CAP_SYS_NICE or root".to_string());
+                }
+                return Err(format!("sched_setscheduler failed: {}", err));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        Err(format!("realtime scheduling is not supported on {}", std::env::consts::OS))
+    }
+}
+
+/// What GetThreadPriority returns on failure
+#[cfg(windows)]
+const THREAD_PRIORITY_ERROR_RETURN: i32 = 0x7fff_ffff;
+
+/// Level for `raise_priority` (`--priority`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// HIGH_PRIORITY_CLASS, THREAD_PRIORITY_HIGHEST
+    High,
+    /// REALTIME_PRIORITY_CLASS, THREAD_PRIORITY_TIME_CRITICAL: above
+    /// the threads that service input, disk and network
+    Realtime,
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "high" => Ok(Priority::High),
+            "realtime" => Ok(Priority::Realtime),
+            other => Err(format!("unknown priority: {} (expected high or realtime)", other)),
+        }
+    }
+}
+
+/// The process priority class and thread priority from before
+/// `raise_priority`; dropping it puts them back
+#[derive(Debug)]
+pub struct PriorityGuard {
+    #[cfg(windows)]
+    class: u32,
+    #[cfg(windows)]
+    thread: i32,
+}
+
+/// Raise the process priority class and the calling thread's priority
+/// (SetPriorityClass/SetThreadPriority), the Windows counterpart of
+/// `set_realtime_priority`.
+///
+/// Without the "increase scheduling priority" privilege Windows
+/// quietly grants HIGH for REALTIME; check `scheduling_policy()`.
+/// Unsupported elsewhere.
+pub fn raise_priority(priority: Priority) -> Result<PriorityGuard, String> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentProcess, GetCurrentThread, GetPriorityClass, GetThreadPriority, SetPriorityClass,
+            SetThreadPriority, HIGH_PRIORITY_CLASS, REALTIME_PRIORITY_CLASS, THREAD_PRIORITY_HIGHEST,
+            THREAD_PRIORITY_TIME_CRITICAL,
+        };
+
+        let (class, thread) = match priority {
+            Priority::High => (HIGH_PRIORITY_CLASS, THREAD_PRIORITY_HIGHEST),
+            Priority::Realtime => (REALTIME_PRIORITY_CLASS, THREAD_PRIORITY_TIME_CRITICAL),
+        };
+        unsafe {
+            let guard = PriorityGuard {
+                class: GetPriorityClass(GetCurrentProcess()),
+                thread: GetThreadPriority(GetCurrentThread()),
+            };
+            if guard.class == 0 || guard.thread == THREAD_PRIORITY_ERROR_RETURN {
+                return Err(format!("reading the current priority failed: {}", std::io::Error::last_os_error()));
+            }
+            if SetPriorityClass(GetCurrentProcess(), class) == 0 {
+                return Err(format!("SetPriorityClass failed: {}", std::io::Error::last_os_error()));
+            }
+            // from here on, dropping the guard restores the class too
+            if SetThreadPriority(GetCurrentThread(), thread) == 0 {
+                return Err(format!("SetThreadPriority failed: {}", std::io::Error::last_os_error()));
+            }
+            Ok(guard)
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = priority;
+        Err(format!("priority classes are not supported on {}", std::env::consts::OS))
+    }
+}
+
+impl Drop for PriorityGuard {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetCurrentThread, SetPriorityClass, SetThreadPriority};
+
+            SetThreadPriority(GetCurrentThread(), self.thread);
+            SetPriorityClass(GetCurrentProcess(), self.class);
+        }
+    }
+}
+
+/// Period `raise_timer_resolution` requests, ms
+pub const HIGH_TIMER_RESOLUTION_MS: u32 = 1;
+
+/// Keeps the Windows timer at `HIGH_TIMER_RESOLUTION_MS` while alive;
+/// dropping it calls timeEndPeriod
+#[derive(Debug)]
+pub struct TimerResolutionGuard {
+    _private: (),
+}
+
+/// timeBeginPeriod(1): Windows timer interrupts, sleeps and waits get
+/// 1 ms granularity instead of the default ~15.6 ms.
+///
+/// The setting is system-wide (it raises timer interrupt rate and
+/// power use for every process) until the guard is dropped or the
+/// process exits. Unsupported elsewhere.
+pub fn raise_timer_resolution() -> Result<TimerResolutionGuard, String> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Media::{timeBeginPeriod, TIMERR_NOERROR};
+
+        let rc = unsafe { timeBeginPeriod(HIGH_TIMER_RESOLUTION_MS) };
+        if rc != TIMERR_NOERROR {
+            return Err(format!("timeBeginPeriod({}) failed: {}", HIGH_TIMER_RESOLUTION_MS, rc));
+        }
+        Ok(TimerResolutionGuard { _private: () })
+    }
+
+    #[cfg(not(windows))]
+    {
+        Err(format!("timer resolution control is not supported on {}", std::env::consts::OS))
+    }
+}
+
+impl Drop for TimerResolutionGuard {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            windows_sys::Win32::Media::timeEndPeriod(HIGH_TIMER_RESOLUTION_MS);
+        }
+    }
+}
+
+// ============================================================
+// NUMA
+// ============================================================
+
+/// NUMA node of the CPU the calling thread is running on.
+///
+/// None on single-node (non-NUMA) machines and off Linux.
+pub fn current_numa_node() -> Option<u32> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        if numa_node_count() < 2 {
+            return None;
+        }
+        let mut cpu: libc::c_uint = 0;
+        let mut node: libc::c_uint = 0;
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_getcpu,
+                &mut cpu as *mut libc::c_uint,
+                &mut node as *mut libc::c_uint,
+                core::ptr::null_mut::<libc::c_void>(),
+            )
+        };
+        if rc == 0 { Some(node) } else { None }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        None
+    }
+}
+
+/// Number of NUMA nodes listed under /sys/devices/system/node
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn numa_node_count() -> usize {
+    let Ok(entries) = std::fs::read_dir("/sys/devices/system/node") else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.strip_prefix("node")
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .count()
+}
+
+// ============================================================
+// CPU isolation
+// ============================================================
+
+/// CPUs kept free of ordinary tasks (`isolcpus=`) or of the scheduler
+/// tick (`nohz_full=`), sorted and deduplicated.
+///
+/// Empty when nothing is isolated; None off Linux or when neither sysfs
+/// list can be read.
+pub fn isolated_cpus() -> Option<Vec<usize>> {
+    let lists: Vec<String> = ["isolated", "nohz_full"]
+        .iter()
+        .filter_map(|name| std::fs::read_to_string(format!("/sys/devices/system/cpu/{}", name)).ok())
+        .collect();
+    if lists.is_empty() {
+        return None;
+    }
+    Some(merge_cpu_lists(&lists))
+}
+
+/// CPUs of all of `lists`, sorted and deduplicated
+fn merge_cpu_lists(lists: &[String]) -> Vec<usize> {
+    let mut cpus: Vec<usize> = lists.iter().flat_map(|list| parse_cpu_list(list)).collect();
+    cpus.sort_unstable();
+    cpus.dedup();
+    cpus
+}
+
+/// Whether the CPU the calling thread runs on is in `isolated_cpus()`
+pub fn is_current_cpu_isolated() -> Option<bool> {
+    let cpu = current_cpu()?;
+    Some(isolated_cpus()?.contains(&cpu))
+}
+
+/// CPUs of a kernel cpu list such as "0-3,8,10-11"; malformed parts
+/// (and "(null)", as nohz_full reads when unset) are skipped
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.trim()
+        .split(',')
+        .filter_map(|part| match part.split_once('-') {
+            Some((first, last)) => Some(first.trim().parse().ok()?..=last.trim().parse().ok()?),
+            None => part.trim().parse().ok().map(|cpu| cpu..=cpu),
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn pinned_calibration_matches_measurement() {
+        use super::{current_cpu, pin_to_cpu};
+        use crate::high_resolution_timer::{test_lock, tick_hz, HighResolutionTimer};
+        use std::time::{Duration, Instant};
+
+        let _lock = test_lock();
+        // own thread, so the pin doesn't outlive the test
+        std::thread::spawn(|| {
+            let cpu = current_cpu().expect("sched_getcpu");
+            pin_to_cpu(cpu).unwrap();
+            assert_eq!(current_cpu(), Some(cpu));
+
+            let hz = tick_hz() as f64;
+            let (ticks, start) = (HighResolutionTimer::ticks(), Instant::now());
+            std::thread::sleep(Duration::from_millis(5));
+            let measured_hz = HighResolutionTimer::ticks().wrapping_sub(ticks) as f64 / start.elapsed().as_secs_f64();
+            assert!((measured_hz - hz).abs() / hz < 0.05, "{} vs {} Hz", measured_hz, hz);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn policies_have_posix_names() {
+        use super::policy_name;
+
+        assert_eq!(policy_name(0), "SCHED_OTHER");
+        assert_eq!(policy_name(libc::SCHED_FIFO), "SCHED_FIFO");
+        assert_eq!(policy_name(libc::SCHED_RR), "SCHED_RR");
+        assert_eq!(policy_name(libc::SCHED_BATCH), "SCHED_BATCH");
+        assert_eq!(policy_name(libc::SCHED_IDLE), "SCHED_IDLE");
+        assert_eq!(policy_name(42), "policy 42");
+    }
+
+    #[test]
+    fn numa_node_query_does_not_panic() {
+        let node = super::current_numa_node();
+        // a node id comes from a real multi-node machine only
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if super::numa_node_count() < 2 {
+            assert_eq!(node, None);
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        assert_eq!(node, None);
+    }
+
+    #[test]
+    fn isolated_list_parsing_and_membership() {
+        use super::{merge_cpu_lists, parse_cpu_list};
+
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), [0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("(null)\n"), Vec::<usize>::new());
+        assert_eq!(parse_cpu_list("\n"), Vec::<usize>::new());
+        assert_eq!(parse_cpu_list("2,x-4,5"), [2, 5]);
+
+        // isolcpus=2-3 nohz_full=3-5
+        let isolated = merge_cpu_lists(&["2-3\n".to_string(), "3-5\n".to_string()]);
+        assert_eq!(isolated, [2, 3, 4, 5]);
+        assert!(isolated.contains(&4));
+        assert!(!isolated.contains(&0));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn timer_resolution_begins_and_ends() {
+        use windows_sys::Win32::Media::{timeEndPeriod, TIMERR_NOERROR};
+
+        let guard = super::raise_timer_resolution().expect("timeBeginPeriod");
+        drop(guard);
+        // the guard's timeEndPeriod balanced the begin, so another
+        // begin/end pair still succeeds
+        let guard = super::raise_timer_resolution().expect("timeBeginPeriod");
+        std::mem::forget(guard);
+        assert_eq!(unsafe { timeEndPeriod(super::HIGH_TIMER_RESOLUTION_MS) }, TIMERR_NOERROR);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn timer_resolution_is_windows_only() {
+        let err = super::raise_timer_resolution().unwrap_err();
+        assert!(err.contains(std::env::consts::OS), "{}", err);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn priority_is_raised_and_restored() {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentProcess, GetCurrentThread, GetPriorityClass, GetThreadPriority, SetPriorityClass,
+            HIGH_PRIORITY_CLASS, THREAD_PRIORITY_HIGHEST,
+        };
+
+        // the thread priority is per thread, so raise it on a thread of
+        // our own; the class is process-wide, so every test runs under
+        // HIGH until it is put back below
+        let class = unsafe { GetPriorityClass(GetCurrentProcess()) };
+        let checked = std::thread::spawn(|| unsafe {
+            let thread = GetThreadPriority(GetCurrentThread());
+            let guard = super::raise_priority(super::Priority::High).expect("raise_priority");
+            assert_eq!(GetPriorityClass(GetCurrentProcess()), HIGH_PRIORITY_CLASS);
+            assert_eq!(GetThreadPriority(GetCurrentThread()), THREAD_PRIORITY_HIGHEST);
+            drop(guard);
+            assert_eq!(GetThreadPriority(GetCurrentThread()), thread);
+        })
+        .join();
+        let restored = unsafe { GetPriorityClass(GetCurrentProcess()) };
+        // restore even if the thread failed before its guard did
+        unsafe { SetPriorityClass(GetCurrentProcess(), class) };
+        checked.unwrap();
+        assert_eq!(restored, class, "the guard restores the class");
+    }
+
+    #[test]
+    fn priority_names_parse() {
+        use super::Priority;
+
+        assert_eq!("high".parse(), Ok(Priority::High));
+        assert_eq!("realtime".parse(), Ok(Priority::Realtime));
+        assert!("idle".parse::<Priority>().unwrap_err().contains("idle"));
+        #[cfg(not(windows))]
+        assert!(super::raise_priority(Priority::High).is_err());
+    }
+
+    #[test]
+    fn cpu_count_falls_back_to_one() {
+        use super::{logical_cpus, logical_cpus_or_one};
+        use std::num::NonZeroUsize;
+
+        assert!(logical_cpus() >= 1);
+        assert_eq!(logical_cpus_or_one(Ok(NonZeroUsize::new(6).unwrap())), 6);
+        let unsupported = std::io::Error::new(std::io::ErrorKind::Unsupported, "no cgroup");
+        assert_eq!(logical_cpus_or_one(Err(unsupported)), 1);
+    }
+}
@@ -0,0 +1,1032 @@
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Barrier;
+use std::time::{Instant, SystemTime};
+
+use crate::affinity::current_core_id;
+use crate::catalog::catalog;
+use crate::date_time_tool::{current_timestamp, now_anchored_ns};
+use crate::error::AssertionFailure;
+use crate::high_resolution_timer::{active_backend, HighResolutionTimer, TimerBackend};
+use crate::signal::stop_requested;
+use crate::stats::{linear_fit, precision_reached, trimmed_mean, LinearFit, RunningMoments, Stats};
+use crate::warning::Warning;
+
+// ============================================================
+// Benchmark registry
+// ============================================================
+
+/// Most calls timed together per sample
+pub const SAMPLE_BATCH: u64 = 10_000;
+/// Samples a measurement aims for when loops allow
+pub const TARGET_SAMPLES: u64 = 1_000;
+/// Samples `precision_batch_size` allows before the loops run out, so
+/// `--target-precision` can keep going well past TARGET_SAMPLES
+pub const PRECISION_SAMPLES: u64 = 100_000;
+/// Fewer samples than this and p99 is flagged as unreliable
+pub const DEFAULT_MIN_SAMPLES: usize = 1_000;
+/// Iterations per run unless a benchmark or the config says otherwise
+pub const DEFAULT_LOOPS: u64 = 10_000_000;
+
+/// A named operation that can be run a given number of times
+pub struct Benchmark {
+    /// Short id used for selection and machine-readable output
+    pub name: &'static str,
+    /// Human-readable description of what is measured
+    pub label: &'static str,
+    /// Iterations per run when the config doesn't set loops
+    pub default_loops: u64,
+    /// Calls per timed batch regardless of loops (see `fixed_batch`)
+    pub batch: Option<u64>,
+    body: Box<dyn FnMut(u64)>,
+    /// Untimed setup for the next `run(iterations)` (see `with_prepare`
+    /// and `with_input`)
+    prepare: Option<Box<dyn FnMut(u64)>>,
+}
+
+impl Benchmark {
+    /// Wrap `f` so each iteration calls it once and black-boxes the output
+    pub fn new<T>(
+        name: &'static str,
+        label: &'static str,
+        mut f: impl FnMut() -> T + 'static,
+    ) -> Self {
+        Self {
+            name,
+            label,
+            default_loops: DEFAULT_LOOPS,
+            batch: None,
+            body: Box::new(move |iterations| {
+                for _ in 0..iterations {
+                    black_box(f());
+                }
+            }),
+            prepare: None,
+        }
+    }
+
+    /// Benchmark `run` on a fresh input from `generate` per iteration,
+    /// so the optimizer can't fold a constant argument through it.
+    ///
+    /// `measure` and `measure_first_call` generate each batch's inputs
+    /// before starting the timer, so only `run` is timed; a bare `run()`
+    /// (warmup) generates them inline. Inputs and outputs are black-boxed.
+    pub fn with_input<I: 'static, O>(
+        name: &'static str,
+        label: &'static str,
+        generate: impl FnMut() -> I + 'static,
+        mut run: impl FnMut(I) -> O + 'static,
+    ) -> Self {
+        let state = Rc::new(RefCell::new(InputState { generate, inputs: Vec::new() }));
+        let prepared = Rc::clone(&state);
+
+        Self {
+            name,
+            label,
+            default_loops: DEFAULT_LOOPS,
+            batch: None,
+            body: Box::new(move |iterations| {
+                let InputState { generate, inputs } = &mut *state.borrow_mut();
+                let mut pending = inputs.drain(..);
+                for _ in 0..iterations {
+                    let input = pending.next().unwrap_or_else(&mut *generate);
+                    black_box(run(black_box(input)));
+                }
+            }),
+            prepare: Some(Box::new(move |iterations| {
+                let InputState { generate, inputs } = &mut *prepared.borrow_mut();
+                inputs.clear();
+                inputs.extend((0..iterations).map(|_| generate()));
+            })),
+        }
+    }
+
+    /// Run `prepare(iterations)` untimed before each timed batch (see
+    /// `prepare`), for per-batch state the body relies on
+    pub fn with_prepare(mut self, prepare: impl FnMut(u64) + 'static) -> Self {
+        self.prepare = Some(Box::new(prepare));
+        self
+    }
+
+    /// Override the default iteration count (for slow operations)
+    pub fn loops(mut self, loops: u64) -> Self {
+        self.default_loops = loops;
+        self
+    }
+
+    /// Time `calls` calls per sample whatever the loops, e.g. 1 for an
+    /// operation slow enough to time on its own
+    pub fn fixed_batch(mut self, calls: u64) -> Self {
+        self.batch = Some(calls.max(1));
+        self
+    }
+
+    /// Call the operation `iterations` times
+    pub fn run(&mut self, iterations: u64) {
+        (self.body)(iterations)
+    }
+
+    /// Untimed setup for a following `run(iterations)`; a no-op unless
+    /// the benchmark has a `with_prepare` step or takes inputs
+    pub fn prepare(&mut self, iterations: u64) {
+        if let Some(prepare) = self.prepare.as_mut() {
+            prepare(iterations);
+        }
+    }
+}
+
+/// Generator and pre-generated inputs shared by a `with_input`
+/// benchmark's prepare and body
+struct InputState<G, I> {
+    generate: G,
+    inputs: Vec<I>,
+}
+
+/// Outcome of measuring one benchmark
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub name: String,
+    pub label: String,
+    pub loops: u64,
+    pub total_ns: u128,
+    /// Per-call ns, one sample per batch
+    pub samples: Vec<f64>,
+    /// Wall-clock epoch ns at the start of each sample's batch (see
+    /// `now_anchored_ns`), parallel to `samples`
+    pub sample_times: Vec<u64>,
+    /// (wall-clock epoch ns, per-call ns) of the samples of runs
+    /// dropped as cold (`--discard-runs`): in `timeline()`, not in
+    /// `samples` or the stats
+    pub cold_samples: Vec<(u64, f64)>,
+    pub stats: Stats,
+    /// Latency of the very first call, before warmup (cold path)
+    pub first_call_ns: Option<u64>,
+    /// Batches after which the thread was on a different core than
+    /// before (see `count_migrations`)
+    pub migrations: u64,
+    /// The timer and the wall clock disagreed about how long the run
+    /// took (see `clocks_diverge`): a suspend or clock anomaly. Such
+    /// runs are kept; reports flag them
+    pub clock_anomaly: bool,
+    /// Non-timer interrupts on the measuring CPU during the timed runs
+    /// (see `noise::cpu_interrupts`); filled in by `runner::run_into`,
+    /// None where they can't be read or the thread changed CPU
+    pub interrupts: Option<u64>,
+    /// Why to trust this result less; filled in by `runner::run_into`
+    pub warnings: Vec<Warning>,
+}
+
+/// Calls per second for a per-call cost in ns; infinite at 0 ns
+pub fn ops_per_sec(ns_per_call: f64) -> f64 {
+    if ns_per_call > 0.0 { 1e9 / ns_per_call } else { f64::INFINITY }
+}
+
+impl BenchmarkResult {
+    /// A result over caller-measured latencies, one per call, as if
+    /// each were a batch of one (no timeline, no first call)
+    pub fn from_samples(name: &str, samples: &[u64]) -> Self {
+        let samples: Vec<f64> = samples.iter().map(|&ns| ns as f64).collect();
+
+        BenchmarkResult {
+            name: name.to_string(),
+            label: name.to_string(),
+            loops: samples.len() as u64,
+            total_ns: samples.iter().map(|&ns| ns as u128).sum(),
+            stats: Stats::from_samples(&samples),
+            samples,
+            sample_times: Vec::new(),
+            cold_samples: Vec::new(),
+            first_call_ns: None,
+            migrations: 0,
+            clock_anomaly: false,
+            interrupts: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Mean cost per call in nanoseconds
+    pub fn ns_per_call(&self) -> f64 {
+        if self.loops == 0 {
+            return 0.0;
+        }
+        self.total_ns as f64 / self.loops as f64
+    }
+
+    /// Calls per second at the mean cost, `1e9 / ns_per_call()`;
+    /// infinite when the cost rounds to 0 ns (or nothing ran)
+    pub fn ops_per_sec(&self) -> f64 {
+        ops_per_sec(self.ns_per_call())
+    }
+
+    /// Mean calls per timed sample (1 for external samples)
+    pub fn batch_size(&self) -> u64 {
+        if self.samples.is_empty() { 1 } else { (self.loops / self.samples.len() as u64).max(1) }
+    }
+
+    /// Whether there are at least `min_samples` samples: below that a
+    /// handful of batches decide p90/p99 (run cut short by a deadline,
+    /// Ctrl-C or few loops)
+    pub fn percentiles_reliable(&self, min_samples: usize) -> bool {
+        self.samples.len() >= min_samples
+    }
+
+    /// Mean per-call ns with the slowest `trim_percent`% of samples dropped
+    pub fn trimmed_mean(&self, trim_percent: f64) -> f64 {
+        trimmed_mean(&self.samples, trim_percent)
+    }
+
+    /// (wall-clock epoch ns, per-call ns) per sample, in time order,
+    /// to line latency spikes up with external events; includes the
+    /// discarded cold runs' samples
+    pub fn timeline(&self) -> Vec<(u64, f64)> {
+        let warm = self.sample_times.iter().copied().zip(self.samples.iter().copied());
+        self.cold_samples.iter().copied().chain(warm).collect()
+    }
+}
+
+/// Run `loops` iterations in batches, timing each batch.
+///
+/// Batches aim for TARGET_SAMPLES samples, capped at SAMPLE_BATCH calls,
+/// unless the benchmark has a `fixed_batch`.
+pub fn measure(bench: &mut Benchmark, loops: u64) -> BenchmarkResult {
+    measure_with(bench, loops, || {})
+}
+
+/// Calls per timed batch when neither the config's `batch_size` nor the
+/// benchmark's `fixed_batch` sets one
+pub fn default_batch_size(loops: u64) -> u64 {
+    (loops / TARGET_SAMPLES).clamp(1, SAMPLE_BATCH)
+}
+
+/// `default_batch_size` for a `--target-precision` run: smaller
+/// batches, so the loops cap leaves up to PRECISION_SAMPLES samples
+/// rather than TARGET_SAMPLES
+pub fn precision_batch_size(loops: u64) -> u64 {
+    (loops / PRECISION_SAMPLES).clamp(1, SAMPLE_BATCH)
+}
+
+/// Like `measure`, calling `between_batches` untimed before each batch.
+///
+/// Stops early on a stop signal; `loops` then reflects what was timed.
+pub fn measure_with(bench: &mut Benchmark, loops: u64, between_batches: impl FnMut()) -> BenchmarkResult {
+    let batch_size = bench.batch.unwrap_or_else(|| default_batch_size(loops));
+    measure_batched(bench, loops, batch_size, between_batches)
+}
+
+/// Like `measure_with`, reading the timer once per `batch_size` calls
+/// (at least 1) and dividing by them, so the two timer reads per batch
+/// add only their cost / `batch_size` to each call.
+///
+/// Bigger batches suit sub-10 ns operations but give fewer samples for
+/// the percentiles.
+pub fn measure_batched(
+    bench: &mut Benchmark,
+    loops: u64,
+    batch_size: u64,
+    between_batches: impl FnMut(),
+) -> BenchmarkResult {
+    measure_until(bench, loops, batch_size, between_batches, |_| false)
+}
+
+/// Like `measure_batched`, but stops as soon as the mean is known to
+/// within `target` of itself (see `precision_reached`), with `max_loops`
+/// as the cap. Whether it got there is for the caller to check on the
+/// samples: a stop signal also ends it early.
+pub fn measure_until_precise(
+    bench: &mut Benchmark,
+    max_loops: u64,
+    batch_size: u64,
+    target: f64,
+    between_batches: impl FnMut(),
+) -> BenchmarkResult {
+    measure_until(bench, max_loops, batch_size, between_batches, |moments| precision_reached(moments, target))
+}
+
+/// The batch loop, ending early once `done` holds for the samples so
+/// far (checked untimed after each batch)
+fn measure_until(
+    bench: &mut Benchmark,
+    loops: u64,
+    batch_size: u64,
+    mut between_batches: impl FnMut(),
+    mut done: impl FnMut(&RunningMoments) -> bool,
+) -> BenchmarkResult {
+    let batch_size = batch_size.max(1);
+    let mut moments = RunningMoments::new();
+    let mut samples = Vec::with_capacity(loops.div_ceil(batch_size) as usize);
+    let mut sample_times = Vec::with_capacity(samples.capacity());
+    let mut cores = Vec::with_capacity(samples.capacity() + 1);
+    let mut total_ns = 0u128;
+    let mut remaining = loops;
+    let span = HighResolutionTimer::start();
+    let wall_start = current_timestamp();
+
+    while remaining > 0 && !stop_requested() {
+        let batch = remaining.min(batch_size);
+        between_batches();
+        bench.prepare(batch);
+        cores.extend(current_core_id());
+        sample_times.push(now_anchored_ns());
+        let timer = HighResolutionTimer::start();
+        bench.run(batch);
+        let ns = timer.ns();
+
+        total_ns += ns;
+        samples.push(ns as f64 / batch as f64);
+        remaining -= batch;
+        moments.record(ns as f64 / batch as f64);
+        if done(&moments) {
+            break;
+        }
+    }
+    cores.extend(current_core_id());
+    let clock_anomaly = clocks_diverge(span.ns(), current_timestamp().saturating_sub(wall_start));
+
+    let migrations = count_migrations(cores);
+    LAST_MIGRATIONS.store(migrations, Ordering::Relaxed);
+
+    BenchmarkResult {
+        name: bench.name.to_string(),
+        label: bench.label.to_string(),
+        loops: loops - remaining,
+        total_ns,
+        stats: Stats::from_samples(&samples),
+        samples,
+        sample_times,
+        cold_samples: Vec::new(),
+        first_call_ns: None,
+        migrations,
+        clock_anomaly,
+        interrupts: None,
+        warnings: Vec::new(),
+    }
+}
+
+/// How far apart (as a ratio) the timer and the wall clock may put a
+/// run's length before `clocks_diverge` flags it
+pub const CLOCK_DIVERGENCE_RATIO: f64 = 2.0;
+
+/// Runs shorter than this are never flagged: a coarse SystemTime
+/// (~15.6 ms on Windows) can't bracket them reliably
+pub const MIN_DIVERGENCE_SPAN_NS: u128 = 100_000_000;
+
+/// Whether the timer's `hrt_ns` and the wall clock's `wall_ns` for the
+/// same interval differ by more than `CLOCK_DIVERGENCE_RATIO`.
+///
+/// A suspend mid-run shows up this way where the counter stops during
+/// sleep but the wall clock doesn't; so do NTP steps and a wrong
+/// calibration.
+pub fn clocks_diverge(hrt_ns: u128, wall_ns: u64) -> bool {
+    let (shorter, longer) = if hrt_ns < wall_ns as u128 {
+        (hrt_ns, wall_ns as u128)
+    } else {
+        (wall_ns as u128, hrt_ns)
+    };
+    longer >= MIN_DIVERGENCE_SPAN_NS && longer as f64 > shorter as f64 * CLOCK_DIVERGENCE_RATIO
+}
+
+static LAST_MIGRATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Core migrations seen by the most recent `measure`/`measure_with`.
+///
+/// 0 is a clean run; many mean the scheduler moved the thread and the
+/// run is worth repeating (or pinning with `--cpu`).
+pub fn migration_count() -> u64 {
+    LAST_MIGRATIONS.load(Ordering::Relaxed)
+}
+
+/// Number of changes between consecutive core ids.
+///
+/// The ids are sampled at batch boundaries, so this is a lower bound on
+/// migrations (a move there and back within one batch is missed).
+pub fn count_migrations(core_ids: impl IntoIterator<Item = u32>) -> u64 {
+    let mut ids = core_ids.into_iter();
+    let Some(mut previous) = ids.next() else {
+        return 0;
+    };
+    let mut changes = 0;
+    for id in ids {
+        if id != previous {
+            changes += 1;
+            previous = id;
+        }
+    }
+    changes
+}
+
+/// Time a single isolated call, meant to run before any warmup so it
+/// captures the cold path (lazy vdso mapping, page faults).
+///
+/// Includes the cost of one timer start/read pair.
+pub fn measure_first_call(bench: &mut Benchmark) -> u64 {
+    bench.prepare(1);
+    let timer = HighResolutionTimer::start();
+    bench.run(1);
+    timer.ns() as u64
+}
+
+/// Fold repeated runs of one benchmark into a single result
+pub fn merge_runs(runs: Vec<BenchmarkResult>) -> Option<BenchmarkResult> {
+    let mut runs = runs.into_iter();
+    let mut merged = runs.next()?;
+    for run in runs {
+        merged.loops += run.loops;
+        merged.total_ns += run.total_ns;
+        merged.migrations += run.migrations;
+        merged.clock_anomaly |= run.clock_anomaly;
+        merged.samples.extend(run.samples);
+        merged.sample_times.extend(run.sample_times);
+        merged.cold_samples.extend(run.cold_samples);
+        merged.warnings.extend(run.warnings);
+    }
+    merged.stats = Stats::from_samples(&merged.samples);
+    Some(merged)
+}
+
+/// `merge_runs` without the first `cold` runs, whose samples are kept
+/// only as `cold_samples`. The last run always counts, so a run cut
+/// short (deadline, Ctrl-C) before passing the cold ones still gives a
+/// result.
+pub fn merge_warm_runs(mut runs: Vec<BenchmarkResult>, cold: usize) -> Option<BenchmarkResult> {
+    let cold_runs: Vec<BenchmarkResult> = runs.drain(..cold.min(runs.len().saturating_sub(1))).collect();
+    let mut merged = merge_runs(runs)?;
+    let cold_samples = cold_runs.into_iter().flat_map(|run| run.timeline());
+    merged.cold_samples.splice(0..0, cold_samples);
+    Some(merged)
+}
+
+// ============================================================
+// Iteration sweep
+// ============================================================
+
+/// Iteration counts a sweep times, capped at the benchmark's loops
+pub const SWEEP_COUNTS: [u64; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+/// Total time at several iteration counts and the line through them
+#[derive(Debug, Clone)]
+pub struct SweepResult {
+    pub name: String,
+    pub label: String,
+    /// (iterations, total ns) per count
+    pub points: Vec<(u64, u128)>,
+    /// `total = intercept + slope * n`: slope is the per-call cost,
+    /// intercept the fixed setup and timer overhead
+    pub fit: Option<LinearFit>,
+}
+
+/// Time `bench` once at each of `SWEEP_COUNTS` up to its default loops
+/// (at least two counts) and fit a line to total ns over iterations.
+///
+/// Unlike the ns/call of `measure`, the slope excludes any fixed cost
+/// per timed block.
+pub fn sweep_benchmark(bench: &mut Benchmark) -> SweepResult {
+    let mut points = Vec::new();
+    for (i, &n) in SWEEP_COUNTS.iter().enumerate() {
+        if (i >= 2 && n > bench.default_loops) || stop_requested() {
+            break;
+        }
+        bench.prepare(n);
+        let timer = HighResolutionTimer::start();
+        bench.run(n);
+        points.push((n, timer.ns()));
+    }
+
+    let xy: Vec<(f64, f64)> = points.iter().map(|&(n, ns)| (n as f64, ns as f64)).collect();
+    SweepResult {
+        name: bench.name.to_string(),
+        label: bench.label.to_string(),
+        fit: linear_fit(&xy),
+        points,
+    }
+}
+
+// ============================================================
+// Thread scaling
+// ============================================================
+
+/// Per-call cost, relative to one thread, above which a level is
+/// flagged as contended
+pub const SCALING_CONTENTION_RATIO: f64 = 1.5;
+
+/// Clock-read cost with `threads` threads reading at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalingPoint {
+    pub threads: usize,
+    /// Mean over the threads of each one's ns per `ns()` call
+    pub ns_per_call: f64,
+    /// More than `SCALING_CONTENTION_RATIO` times the first level's
+    /// cost: the clock serializes readers (QPC, some vDSO setups)
+    pub contended: bool,
+}
+
+/// 1, 2, 4, ... up to `max`, plus `max` itself if not a power of two
+pub fn thread_levels(max: usize) -> Vec<usize> {
+    let max = max.max(1);
+    let mut levels: Vec<usize> = std::iter::successors(Some(1usize), |n| n.checked_mul(2))
+        .take_while(|&n| n <= max)
+        .collect();
+    if levels.last() != Some(&max) {
+        levels.push(max);
+    }
+    levels
+}
+
+/// Time `loops` `HighResolutionTimer::ns()` calls on every thread at
+/// each level in `levels` (all threads released together), one point
+/// per level.
+pub fn measure_scaling(levels: &[usize], loops: u64) -> Vec<ScalingPoint> {
+    let mut points: Vec<ScalingPoint> = Vec::with_capacity(levels.len());
+    for &threads in levels {
+        if stop_requested() {
+            break;
+        }
+        let ns_per_call = parallel_clock_reads(threads, loops);
+        let baseline = points.first().map_or(ns_per_call, |p| p.ns_per_call);
+        points.push(ScalingPoint {
+            threads,
+            ns_per_call,
+            contended: baseline > 0.0 && ns_per_call > baseline * SCALING_CONTENTION_RATIO,
+        });
+    }
+    points
+}
+
+/// Mean ns per `ns()` call over `threads` threads each making `loops`
+fn parallel_clock_reads(threads: usize, loops: u64) -> f64 {
+    let threads = threads.max(1);
+    let barrier = Barrier::new(threads);
+    let per_thread: Vec<f64> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let reader = HighResolutionTimer::start();
+                    barrier.wait();
+                    let timer = HighResolutionTimer::start();
+                    for _ in 0..loops {
+                        black_box(reader.ns());
+                    }
+                    timer.ns() as f64 / loops.max(1) as f64
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap_or(0.0)).collect()
+    });
+    per_thread.iter().sum::<f64>() / per_thread.len() as f64
+}
+
+// ============================================================
+// Assertions
+// ============================================================
+
+/// Measure `bench` over its default loops and fail if the median
+/// per-call cost is above `max_ns`, e.g. to gate CI without a
+/// baseline file.
+///
+/// One `WARMUP_BATCH` of untimed calls runs first. Returns the stats
+/// on success; a run that took no samples (zero loops, or stopped
+/// early) fails with a NaN median.
+pub fn run_and_assert(bench: &mut Benchmark, max_ns: f64) -> Result<Stats, AssertionFailure> {
+    bench.run(WARMUP_BATCH);
+    let result = measure(bench, bench.default_loops);
+    if result.samples.is_empty() {
+        return Err(AssertionFailure {
+            name: result.name,
+            median_ns: f64::NAN,
+            max_ns,
+        });
+    }
+    if result.stats.p50 > max_ns {
+        return Err(AssertionFailure {
+            name: result.name,
+            median_ns: result.stats.p50,
+            max_ns,
+        });
+    }
+    Ok(result.stats)
+}
+
+// ============================================================
+// Memory pressure
+// ============================================================
+
+/// A buffer rewritten between batches to evict caches and TLB entries,
+/// so clock reads are measured cold (experimental)
+pub struct MemoryPressure {
+    buffer: Vec<u8>,
+    page_size: usize,
+}
+
+impl MemoryPressure {
+    pub fn new(megabytes: usize) -> Self {
+        Self {
+            buffer: vec![0u8; megabytes * 1024 * 1024],
+            page_size: page_size(),
+        }
+    }
+
+    /// Touch every page of the buffer, returning the pages written
+    pub fn apply(&mut self) -> usize {
+        touch_pages(&mut self.buffer, self.page_size)
+    }
+}
+
+/// Write one byte in every `page_size` page of `buffer`
+pub fn touch_pages(buffer: &mut [u8], page_size: usize) -> usize {
+    let mut pages = 0;
+    for offset in (0..buffer.len()).step_by(page_size.max(1)) {
+        // volatile so the writes survive optimization
+        unsafe {
+            let byte = buffer.as_mut_ptr().add(offset);
+            std::ptr::write_volatile(byte, std::ptr::read_volatile(byte).wrapping_add(1));
+        }
+        pages += 1;
+    }
+    pages
+}
+
+/// OS page size in bytes (4096 where it can't be queried)
+pub fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if size > 0 { size as usize } else { 4096 }
+    }
+
+    #[cfg(not(unix))]
+    {
+        4096
+    }
+}
+
+/// Every clock source this build can read
+pub fn clock_sources() -> Vec<Benchmark> {
+    // started on first run: building the list (e.g. to validate names)
+    // must not trigger calibration before its settings are applied
+    let mut timer = None;
+
+    vec![
+        Benchmark::new("systemtime", "SystemTime::now()", SystemTime::now),
+        Benchmark::new("instant", "Instant::now()", Instant::now),
+        // one counter read + conversion against a fixed start, versus
+        // the two reads + conversion of timing each call on its own
+        Benchmark::new("hrt", "HighResolutionTimer::ns()", move || {
+            timer.get_or_insert_with(HighResolutionTimer::start).ns()
+        }),
+        Benchmark::new("hrt_start_stop", "HighResolutionTimer::start() + stop()", || {
+            HighResolutionTimer::start().stop()
+        }),
+        Benchmark::new("ticks", "HighResolutionTimer::ticks()", HighResolutionTimer::ticks),
+        Benchmark::new("ticks_noinline", "HighResolutionTimer::ticks() (not inlined)", ticks_noinline),
+        #[cfg(unix)]
+        Benchmark::new("clock_gettime", "clock_gettime(CLOCK_MONOTONIC)", clock_gettime_monotonic),
+    ]
+}
+
+/// Backend a `clock_sources()` benchmark reads, for the ones that read
+/// exactly one (`hrt` adds a conversion, std clocks may be either)
+pub fn clock_read_backend(name: &str) -> Option<TimerBackend> {
+    match name {
+        "ticks" | "ticks_noinline" => Some(active_backend()),
+        #[cfg(unix)]
+        "clock_gettime" => Some(TimerBackend::Monotonic),
+        _ => None,
+    }
+}
+
+/// `HighResolutionTimer::ticks()` behind a real call, as at a call site
+/// the optimizer doesn't inline into (the `ticks` benchmark is the
+/// inlined best case)
+#[inline(never)]
+pub fn ticks_noinline() -> u64 {
+    HighResolutionTimer::ticks()
+}
+
+#[cfg(unix)]
+#[inline(always)]
+pub(crate) fn clock_gettime_monotonic() -> libc::timespec {
+    let mut ts: libc::timespec = unsafe { core::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts
+}
+
+/// Every registered benchmark: clock sources first, then the catalog
+pub fn registry() -> Vec<Benchmark> {
+    let mut all = clock_sources();
+    all.extend(catalog());
+    all
+}
+
+/// Sort results ascending by ns/call
+pub fn rank_by_cost(results: &mut [BenchmarkResult]) {
+    results.sort_by(|a, b| a.ns_per_call().total_cmp(&b.ns_per_call()));
+}
+
+// ============================================================
+// Warmup detection
+// ============================================================
+
+/// Calls per warmup batch
+pub const WARMUP_BATCH: u64 = 1_000;
+/// Upper bound on batches before giving up
+pub const WARMUP_MAX_BATCHES: usize = 500;
+/// Rolling window (in batches) used to judge stability
+pub const WARMUP_WINDOW: usize = 10;
+/// Relative tolerance against the steady-state mean
+pub const WARMUP_TOLERANCE: f64 = 0.05;
+
+/// Run `f` in small batches and return how many iterations it took
+/// until the rolling mean stabilized, or None if it never did.
+pub fn detect_warmup<T>(mut f: impl FnMut() -> T) -> Option<u64> {
+    let mut batch_ns = Vec::with_capacity(WARMUP_MAX_BATCHES);
+    for _ in 0..WARMUP_MAX_BATCHES {
+        let timer = HighResolutionTimer::start();
+        for _ in 0..WARMUP_BATCH {
+            black_box(f());
+        }
+        batch_ns.push(timer.ns() as f64 / WARMUP_BATCH as f64);
+    }
+
+    steady_state_index(&batch_ns, WARMUP_WINDOW, WARMUP_TOLERANCE)
+        .map(|batch| batch as u64 * WARMUP_BATCH)
+}
+
+/// Index of the first batch whose rolling-window mean lies within
+/// `tolerance` of the steady-state mean.
+///
+/// The steady-state reference is the median of all window means, so a
+/// short warmup ramp or a lone spike doesn't move it.
+pub fn steady_state_index(batch_ns: &[f64], window: usize, tolerance: f64) -> Option<usize> {
+    if window == 0 || batch_ns.len() < window {
+        return None;
+    }
+
+    let means: Vec<f64> = batch_ns
+        .windows(window)
+        .map(|w| w.iter().sum::<f64>() / window as f64)
+        .collect();
+
+    let mut sorted = means.clone();
+    sorted.sort_by(f64::total_cmp);
+    let reference = sorted[sorted.len() / 2];
+    if reference <= 0.0 {
+        return None;
+    }
+
+    means
+        .iter()
+        .position(|mean| (mean - reference).abs() / reference <= tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::high_resolution_timer::test_lock;
+
+    #[test]
+    fn clock_sources_rank_ascending() {
+        let _lock = test_lock();
+        let mut sources = clock_sources();
+        let mut names: Vec<&str> = sources.iter().map(|b| b.name).collect();
+        let mut results: Vec<BenchmarkResult> = sources.iter_mut().map(|b| measure(b, 1_000)).collect();
+        rank_by_cost(&mut results);
+
+        assert!(results.windows(2).all(|w| w[0].ns_per_call() <= w[1].ns_per_call()));
+        let mut ranked: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        names.sort_unstable();
+        ranked.sort_unstable();
+        assert_eq!(ranked, names);
+    }
+
+    #[test]
+    fn touch_pages_writes_every_page() {
+        let mut buffer = vec![0u8; 10 * 4096 + 1];
+        assert_eq!(touch_pages(&mut buffer, 4096), 11);
+        for (offset, &byte) in buffer.iter().enumerate() {
+            assert_eq!(byte, (offset % 4096 == 0) as u8, "offset {}", offset);
+        }
+        assert_eq!(MemoryPressure::new(1).apply(), 1024 * 1024 / page_size());
+    }
+
+    #[test]
+    fn steady_state_after_decay() {
+        let mut batch_ns = vec![100.0, 80.0, 60.0, 40.0, 20.0];
+        batch_ns.extend([10.0; 20]);
+        // the first window of 3 holding only flat batches starts at 5
+        assert_eq!(steady_state_index(&batch_ns, 3, 0.05), Some(5));
+        assert_eq!(steady_state_index(&[10.0; 20], 3, 0.05), Some(0));
+        assert_eq!(steady_state_index(&batch_ns[..2], 3, 0.05), None);
+    }
+
+    /// A benchmark counting its calls in the returned cell
+    fn counting(name: &'static str) -> (Benchmark, std::rc::Rc<std::cell::Cell<u64>>) {
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = calls.clone();
+        (Benchmark::new(name, name, move || counter.set(counter.get() + 1)), calls)
+    }
+
+    #[test]
+    fn first_call_is_one_isolated_call() {
+        let _lock = test_lock();
+        let (mut bench, calls) = counting("count");
+        measure_first_call(&mut bench);
+        assert_eq!(calls.get(), 1);
+        let result = measure(&mut bench, 100);
+        assert_eq!(calls.get(), 1 + 100);
+        assert_eq!(result.loops, 100);
+    }
+
+    #[test]
+    fn instant_source_measures_within_wall_time() {
+        let mut instant = clock_sources().into_iter().find(|b| b.name == "instant").unwrap();
+        let start = Instant::now();
+        let result = measure(&mut instant, 20_000);
+        let wall_ns = start.elapsed().as_nanos() as f64;
+
+        assert_eq!((result.name.as_str(), result.loops), ("instant", 20_000));
+        assert_eq!(result.samples.len() as u64, TARGET_SAMPLES);
+        assert!(result.samples.iter().all(|&ns| ns > 0.0));
+        assert!(result.ns_per_call() * 20_000.0 <= wall_ns, "{} ns/call", result.ns_per_call());
+    }
+
+    #[test]
+    fn counts_core_changes() {
+        assert_eq!(count_migrations([3, 3, 3]), 0);
+        assert_eq!(count_migrations([0, 0, 2, 2, 0, 5]), 3);
+        assert_eq!(count_migrations([]), 0);
+    }
+
+    #[test]
+    fn ticks_noinline_reads_the_same_counter() {
+        let _lock = test_lock();
+        for _ in 0..1000 {
+            let before = HighResolutionTimer::ticks();
+            let wrapped = ticks_noinline();
+            let after = HighResolutionTimer::ticks();
+            assert!(before <= wrapped && wrapped <= after, "{} {} {}", before, wrapped, after);
+        }
+    }
+
+    #[test]
+    fn assert_passes_fast_and_fails_slow() {
+        let _lock = test_lock();
+        let (bench, _) = counting("fast");
+        let stats = run_and_assert(&mut bench.loops(10_000), 1_000_000.0).unwrap();
+        assert!(stats.p50 < 1_000_000.0);
+
+        let mut slow = Benchmark::new("slow", "spin 20 µs", || {
+            let start = std::time::Instant::now();
+            while start.elapsed() < std::time::Duration::from_micros(20) {}
+        })
+        .loops(100);
+        let failure = run_and_assert(&mut slow, 1_000.0).unwrap_err();
+        assert_eq!(failure.name, "slow");
+        assert_eq!(failure.max_ns, 1_000.0);
+        assert!(failure.median_ns >= 20_000.0, "{}", failure.median_ns);
+    }
+
+    #[test]
+    fn run_and_assert_fails_without_samples() {
+        let (bench, _) = counting("empty");
+        let failure = run_and_assert(&mut bench.loops(0), 1_000.0).unwrap_err();
+        assert_eq!(failure.name, "empty");
+        assert!(failure.median_ns.is_nan());
+        assert!(failure.to_string().contains("no samples"), "{failure}");
+    }
+
+    #[test]
+    fn timeline_timestamps_increase() {
+        let _lock = test_lock();
+        let (mut bench, _) = counting("count");
+        let result = measure(&mut bench, 10_000);
+        assert_eq!(result.sample_times.len(), result.samples.len());
+        assert!(result.sample_times.windows(2).all(|w| w[0] < w[1]), "{:?}", result.sample_times);
+
+        let timeline = result.timeline();
+        assert_eq!(timeline.len(), result.samples.len());
+        assert_eq!(timeline[0], (result.sample_times[0], result.samples[0]));
+    }
+
+    #[test]
+    fn scaling_has_one_point_per_level() {
+        let _lock = test_lock();
+        assert_eq!(thread_levels(6), [1, 2, 4, 6]);
+        assert_eq!(thread_levels(8), [1, 2, 4, 8]);
+        assert_eq!(thread_levels(0), [1]);
+
+        let points = measure_scaling(&[1, 2, 3], 1_000);
+        assert_eq!(points.iter().map(|p| p.threads).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(points.iter().all(|p| p.ns_per_call.is_finite() && p.ns_per_call > 0.0), "{:?}", points);
+        assert!(!points[0].contended);
+    }
+
+    #[test]
+    fn ops_per_sec_is_the_reciprocal() {
+        assert_eq!(ops_per_sec(25.0), 40_000_000.0);
+        assert_eq!(ops_per_sec(1e9), 1.0);
+        assert_eq!(ops_per_sec(0.0), f64::INFINITY);
+        // 4 calls in 100 ns: 25 ns each
+        let result = BenchmarkResult::from_samples("x", &[10, 20, 30, 40]);
+        assert_eq!(result.ops_per_sec(), 40_000_000.0);
+        assert_eq!(crate::report::format_ops(result.ops_per_sec()), "40.0M");
+    }
+
+    #[test]
+    fn divergent_clocks_are_detected() {
+        const S: u128 = 1_000_000_000;
+        // a 30 s suspend during a 1 s run: the counter stopped
+        assert!(clocks_diverge(S, 31 * S as u64));
+        // a calibration off by 3x the other way
+        assert!(clocks_diverge(3 * S, S as u64));
+        // ordinary disagreement
+        assert!(!clocks_diverge(S, (S + S / 10) as u64));
+        assert!(!clocks_diverge(2 * S, S as u64), "2x is the limit, not over it");
+        // too short to bracket with a coarse wall clock
+        assert!(!clocks_diverge(1_000_000, 40_000_000));
+        assert!(clocks_diverge(0, MIN_DIVERGENCE_SPAN_NS as u64));
+    }
+
+    #[test]
+    fn batching_keeps_the_per_call_cost() {
+        let _lock = test_lock();
+        let mut spin = Benchmark::new("spin", "spin 5 µs", || {
+            let start = std::time::Instant::now();
+            while start.elapsed() < std::time::Duration::from_micros(5) {}
+        });
+        let per_call = measure_batched(&mut spin, 200, 1, || {});
+        let batched = measure_batched(&mut spin, 200, 20, || {});
+        assert_eq!((per_call.samples.len(), batched.samples.len()), (200, 10));
+
+        // medians, so one preempted batch can't decide it
+        let ratio = batched.stats.p50 / per_call.stats.p50;
+        assert!((0.8..1.25).contains(&ratio), "{} vs {}", batched.stats.p50, per_call.stats.p50);
+    }
+
+    #[test]
+    fn with_input_generates_once_per_iteration() {
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = seen.clone();
+        let mut next = 0u64;
+        let mut bench = Benchmark::with_input(
+            "inputs",
+            "inputs",
+            move || {
+                next += 1;
+                next
+            },
+            move |input| log.borrow_mut().push(input),
+        );
+
+        measure_batched(&mut bench, 50, 10, || {});
+        assert_eq!(*seen.borrow(), (1..=50).collect::<Vec<_>>());
+        // a bare run generates inline, still one per call
+        bench.run(3);
+        assert_eq!(seen.borrow()[50..], [51, 52, 53]);
+    }
+
+    #[test]
+    fn discarded_runs_leave_the_stats() {
+        let run = |start: u64, samples: &[u64]| {
+            let mut result = BenchmarkResult::from_samples("b", samples);
+            result.sample_times = (start..start + samples.len() as u64).collect();
+            result
+        };
+        let runs = vec![run(0, &[900, 800]), run(10, &[10, 12]), run(20, &[14, 16])];
+
+        let merged = merge_warm_runs(runs.clone(), 1).unwrap();
+        assert_eq!(merged.samples, [10.0, 12.0, 14.0, 16.0]);
+        assert_eq!((merged.loops, merged.total_ns), (4, 52));
+        assert_eq!((merged.stats.mean, merged.stats.max), (13.0, 16.0));
+        // the cold run stays visible on the timeline only
+        assert_eq!(merged.cold_samples, [(0, 900.0), (1, 800.0)]);
+        assert_eq!(merged.timeline().len(), 6);
+
+        // the last run always counts
+        assert_eq!(merge_warm_runs(runs.clone(), 5).unwrap().samples, [14.0, 16.0]);
+        assert_eq!(merge_warm_runs(runs, 0).unwrap().samples.len(), 6);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hrt_sources_read_the_counter_once_and_twice() {
+        use crate::high_resolution_timer::{backend, select_backend, MONOTONIC_READS};
+        let _lock = test_lock();
+
+        // on the monotonic backend every counter read is counted
+        select_backend(TimerBackend::Monotonic).unwrap();
+        let reads_per_call: Vec<(&str, f64)> = ["hrt", "hrt_start_stop", "ticks"]
+            .into_iter()
+            .map(|name| {
+                let mut bench = clock_sources().into_iter().find(|b| b.name == name).unwrap();
+                // `hrt` starts its timer on the first call
+                bench.run(1);
+                MONOTONIC_READS.with(|reads| reads.set(0));
+                bench.run(100);
+                (name, MONOTONIC_READS.with(|reads| reads.get()) as f64 / 100.0)
+            })
+            .collect();
+        select_backend(backend()).unwrap();
+        assert_eq!(reads_per_call, [("hrt", 1.0), ("hrt_start_stop", 2.0), ("ticks", 1.0)]);
+    }
+}
@@ -0,0 +1,1236 @@
+use std::alloc::Layout;
+use std::hint::black_box;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+#[cfg(unix)]
+use crate::benchmark::clock_gettime_monotonic;
+use crate::benchmark::{page_size, Benchmark};
+use crate::high_resolution_timer::{ns_reciprocal, tick_hz, ticks_to_ns, ticks_to_ns_fast, HighResolutionTimer};
+
+// ============================================================
+// "What does X cost in nanoseconds" catalog
+// ============================================================
+// Registered alongside the clock sources but only run when selected
+// with --bench.
+
+/// All catalog benchmarks
+pub fn catalog() -> Vec<Benchmark> {
+    vec![
+        context_switch(),
+        thread_spawn(),
+        channel_send_recv(),
+        sync_channel_send_recv(),
+        mutex_uncontended(),
+        mutex_contended(),
+        cas_uncontended(),
+        cas_contended(),
+        direct_call(),
+        dyn_call(),
+        predicted_branch(),
+        mispredicted_branch(),
+        checked_index(),
+        unchecked_index(),
+        inline_array_sum(),
+        vec_sum(),
+        cached_read(),
+        uncached_read(),
+        page_fault(),
+        page_rewrite(),
+        #[cfg(unix)]
+        getpid(),
+        #[cfg(unix)]
+        clock_gettime_after_syscall(),
+        timer_start(),
+        ns_conversion(),
+        ns_division(),
+        u64_division(),
+        f64_division(),
+        thread_local_read(),
+        atomic_read(),
+        format_string(),
+        vec_push_growing(),
+        vec_push_preallocated(),
+        box_alloc(),
+        rc_clone(),
+        arc_clone(),
+        hashmap_hit(),
+        hashmap_miss(),
+    ]
+}
+
+/// Round trip between two threads over a pair of channels: each
+/// iteration wakes the partner thread and waits to be woken back.
+///
+/// The partner is spawned on first run, not at registration.
+pub fn context_switch() -> Benchmark {
+    let mut channels = None;
+
+    Benchmark::new("context_switch", "thread ping-pong round trip", move || {
+        let (ping_tx, pong_rx) = channels.get_or_insert_with(spawn_echo_thread);
+        ping_tx.send(1).expect("ping-pong thread exited");
+        pong_rx.recv().expect("ping-pong thread exited")
+    })
+    .loops(100_000)
+}
+
+/// Spawn a thread that echoes every value back; it exits once the
+/// returned sender is dropped
+fn spawn_echo_thread() -> (mpsc::Sender<u64>, mpsc::Receiver<u64>) {
+    let (ping_tx, ping_rx) = mpsc::channel::<u64>();
+    let (pong_tx, pong_rx) = mpsc::channel::<u64>();
+
+    thread::spawn(move || {
+        for value in ping_rx {
+            if pong_tx.send(value).is_err() {
+                break;
+            }
+        }
+    });
+
+    (ping_tx, pong_rx)
+}
+
+/// `thread::spawn` of a closure that returns at once, and its `join`:
+/// one OS thread created and reaped per call, typically tens of
+/// microseconds (stack mapping, clone, scheduling both ways). The
+/// cost a thread pool saves per task.
+pub fn thread_spawn() -> Benchmark {
+    thread_spawn_running(|| black_box(1u64))
+}
+
+/// `thread_spawn` with each thread running a copy of `work`
+fn thread_spawn_running<T: Send + 'static>(work: impl Fn() -> T + Clone + Send + 'static) -> Benchmark {
+    Benchmark::new("thread_spawn", "thread::spawn + join (empty thread)", move || {
+        thread::spawn(work.clone()).join().expect("spawned thread panicked")
+    })
+    .loops(10_000)
+}
+
+/// `Mutex::lock` + unlock with no other thread around: the atomic
+/// fast path only
+pub fn mutex_uncontended() -> Benchmark {
+    mutex_uncontended_on(Arc::new(Mutex::new(0)))
+}
+
+/// `mutex_uncontended` on a given `mutex`, each call adding 1 to it
+fn mutex_uncontended_on(mutex: Arc<Mutex<u64>>) -> Benchmark {
+    Benchmark::new("mutex_uncontended", "Mutex lock/unlock (uncontended)", move || {
+        let mut guard = mutex.lock().unwrap();
+        *guard += 1;
+        *guard
+    })
+}
+
+/// `Mutex::lock` + unlock while a second thread keeps taking the same
+/// lock, so calls regularly hit the parking slow path.
+///
+/// The contending thread is spawned on first run and stopped when the
+/// benchmark is dropped.
+pub fn mutex_contended() -> Benchmark {
+    let mut contender = None;
+
+    Benchmark::new("mutex_contended", "Mutex lock/unlock (contended, 2 threads)", move || {
+        let contender = contender.get_or_insert_with(Contender::spawn);
+        let mut guard = contender.mutex.lock().unwrap();
+        *guard += 1;
+        *guard
+    })
+    .loops(1_000_000)
+}
+
+/// Thread locking `mutex` in a loop until dropped
+struct Contender {
+    mutex: Arc<Mutex<u64>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Contender {
+    fn spawn() -> Self {
+        let mutex = Arc::new(Mutex::new(0u64));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (mutex, stop) = (Arc::clone(&mutex), Arc::clone(&stop));
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    *mutex.lock().unwrap() += 1;
+                }
+            })
+        };
+        Self { mutex, stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for Contender {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// getpid(): about the cheapest real syscall, so it shows the kernel
+/// entry/exit cost that the vDSO-served clock_gettime avoids
+/// (glibc >= 2.25 no longer caches the pid)
+#[cfg(unix)]
+pub fn getpid() -> Benchmark {
+    Benchmark::new("getpid", "getpid() syscall", || unsafe { libc::getpid() }).loops(1_000_000)
+}
+
+/// getpid() then clock_gettime(CLOCK_MONOTONIC) each call: the vDSO
+/// read right after a kernel round trip has evicted some of its cache
+/// lines and TLB entries. Minus `getpid`, compare with the back-to-back
+/// `clock_gettime` source.
+#[cfg(unix)]
+pub fn clock_gettime_after_syscall() -> Benchmark {
+    Benchmark::new("clock_gettime_after_syscall", "getpid() + clock_gettime(CLOCK_MONOTONIC)", || {
+        let pid = unsafe { libc::getpid() };
+        (black_box(pid), clock_gettime_monotonic())
+    })
+    .loops(1_000_000)
+}
+
+/// `HighResolutionTimer::start()` once calibrated: a tick read plus the
+/// backend and calibration bookkeeping, to weigh one timer per
+/// operation against reusing one (the first call includes calibration)
+pub fn timer_start() -> Benchmark {
+    Benchmark::new("timer_start", "HighResolutionTimer::start()", HighResolutionTimer::start)
+}
+
+/// Only the tick-to-ns arithmetic of `HighResolutionTimer::ns()`, on
+/// a fixed delta of one million ticks: subtract it from the `hrt` cost
+/// to split counter read from conversion.
+///
+/// The frequency and its reciprocal are read together on first run, so
+/// a later recalibration can't pair an old reciprocal with a new rate.
+pub fn ns_conversion() -> Benchmark {
+    let mut conversion = None;
+
+    Benchmark::new("ns_conversion", "ticks_to_ns_fast() of a fixed delta", move || {
+        let (hz, recip) = *conversion.get_or_insert_with(|| {
+            let hz = tick_hz();
+            (hz, ns_reciprocal(hz))
+        });
+        ticks_to_ns_fast(black_box(1_000_000), black_box(hz), black_box(recip))
+    })
+}
+
+/// The exact 128-bit division that `ns_conversion` replaces
+pub fn ns_division() -> Benchmark {
+    Benchmark::new("ns_division", "ticks_to_ns() of a fixed delta", || {
+        ticks_to_ns(black_box(1_000_000), tick_hz())
+    })
+}
+
+/// One 64-bit integer division; black_box keeps both operands opaque
+/// so it can't be folded or turned into a multiply
+pub fn u64_division() -> Benchmark {
+    Benchmark::new("u64_division", "u64 / u64", || {
+        black_box(2_099_999_999_999u64) / black_box(1_000_003u64)
+    })
+}
+
+/// One f64 division, the float counterpart of `u64_division`
+pub fn f64_division() -> Benchmark {
+    Benchmark::new("f64_division", "f64 / f64", || {
+        black_box(2_099_999_999_999.0f64) / black_box(1_000_003.0f64)
+    })
+}
+
+/// `format!("{}", x)` of a u64: formatting plus one heap allocation
+/// and free, the cost of building a log line in a hot path
+pub fn format_string() -> Benchmark {
+    Benchmark::new("format_string", "format!(\"{}\", u64) (allocates)", || format!("{}", black_box(2_099_999_999u64)))
+}
+
+// --------------------------
+// Channels
+// --------------------------
+// One thread sends and receives, so these are the channel operations
+// alone; `context_switch` is the same round trip across two threads.
+
+/// `mpsc::channel` send then recv of one message
+pub fn channel_send_recv() -> Benchmark {
+    let (tx, rx) = mpsc::channel::<u64>();
+    let mut sent = 0u64;
+
+    Benchmark::new("channel_send_recv", "mpsc::channel send + recv", move || {
+        sent += 1;
+        tx.send(sent).expect("receiver is alive");
+        rx.recv().expect("sender is alive")
+    })
+}
+
+/// `mpsc::sync_channel(1)` (bounded) send then recv of one message
+pub fn sync_channel_send_recv() -> Benchmark {
+    let (tx, rx) = mpsc::sync_channel::<u64>(1);
+    let mut sent = 0u64;
+
+    Benchmark::new("sync_channel_send_recv", "mpsc::sync_channel(1) send + recv", move || {
+        sent += 1;
+        tx.send(sent).expect("receiver is alive");
+        rx.recv().expect("sender is alive")
+    })
+}
+
+// --------------------------
+// Compare-and-swap
+// --------------------------
+
+/// One increment through a `compare_exchange_weak` retry loop,
+/// returning the value written
+#[inline(always)]
+fn cas_increment(value: &AtomicU64) -> u64 {
+    let mut current = value.load(Ordering::Relaxed);
+    loop {
+        match value.compare_exchange_weak(current, current + 1, Ordering::AcqRel, Ordering::Relaxed) {
+            Ok(_) => return current + 1,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+/// CAS increment with no other thread around: almost every
+/// `compare_exchange_weak` succeeds first time (a spurious LL/SC
+/// failure on ARM retries)
+pub fn cas_uncontended() -> Benchmark {
+    let value = AtomicU64::new(0);
+
+    Benchmark::new("cas_uncontended", "compare_exchange_weak loop (uncontended)", move || cas_increment(black_box(&value)))
+}
+
+/// CAS increment while a second thread keeps incrementing the same
+/// atomic, so the cache line bounces and CASes fail and retry.
+///
+/// Like `mutex_contended`, the other thread starts on first run and
+/// stops when the benchmark is dropped.
+pub fn cas_contended() -> Benchmark {
+    let mut contender = None;
+
+    Benchmark::new("cas_contended", "compare_exchange_weak loop (contended, 2 threads)", move || {
+        let contender = contender.get_or_insert_with(CasContender::spawn);
+        cas_increment(black_box(&contender.value))
+    })
+    .loops(1_000_000)
+}
+
+/// Thread CAS-incrementing `value` in a loop until dropped
+struct CasContender {
+    value: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CasContender {
+    fn spawn() -> Self {
+        let value = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (value, stop) = (Arc::clone(&value), Arc::clone(&stop));
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    cas_increment(&value);
+                }
+            })
+        };
+        Self { value, stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for CasContender {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// --------------------------
+// Vec growth
+// --------------------------
+
+/// Elements pushed before a Vec benchmark starts over
+pub const VEC_FILL: usize = 4096;
+
+/// One `push` per call into a Vec started from `Vec::new()` every
+/// `VEC_FILL` elements, so each fill pays its ~12 reallocations and
+/// copies (amortized over the elements)
+pub fn vec_push_growing() -> Benchmark {
+    // same shape as vec_push_preallocated so only the growth differs
+    let mut state: Option<Vec<u64>> = None;
+    Benchmark::new("vec_push_growing", "Vec::push (growing, reallocates)", move || {
+        let v = state.get_or_insert_with(Vec::new);
+        if v.len() == VEC_FILL {
+            *v = Vec::new();
+        }
+        black_box(&mut *v).push(black_box(1));
+    })
+}
+
+/// One `push` per call into a Vec with `VEC_FILL` capacity, cleared
+/// when full: the same writes as `vec_push_growing` without the growth
+pub fn vec_push_preallocated() -> Benchmark {
+    let mut state: Option<Vec<u64>> = None;
+    Benchmark::new("vec_push_preallocated", "Vec::push (preallocated)", move || {
+        let v = state.get_or_insert_with(|| Vec::with_capacity(VEC_FILL));
+        if v.len() == VEC_FILL {
+            v.clear();
+        }
+        black_box(&mut *v).push(black_box(1));
+    })
+}
+
+// --------------------------
+// Heap allocation
+// --------------------------
+
+/// `Box::new` of a u64 and its drop: one small allocation and free
+/// through the global allocator, its thread cache warm after warmup.
+/// black_box on the Box keeps the allocation from being elided.
+pub fn box_alloc() -> Benchmark {
+    Benchmark::new("box_alloc", "Box::new(u64) + drop", || {
+        let boxed = black_box(Box::new(black_box(7u64)));
+        drop(boxed);
+    })
+}
+
+// --------------------------
+// Reference counting
+// --------------------------
+// Rc bumps a plain counter, Arc an atomic one; the difference is the
+// price of sharing across threads. Each clone is dropped again, so the
+// strong count is back at 1 after every iteration.
+
+/// `Rc::clone` and its drop: a non-atomic increment and decrement
+pub fn rc_clone() -> Benchmark {
+    rc_clone_of(Rc::new(7u64))
+}
+
+/// `rc_clone` cloning a given `shared`
+fn rc_clone_of(shared: Rc<u64>) -> Benchmark {
+    Benchmark::new("rc_clone", "Rc::clone + drop", move || {
+        let clone = black_box(Rc::clone(black_box(&shared)));
+        drop(clone);
+    })
+}
+
+/// `Arc::clone` and its drop: an atomic increment and decrement,
+/// uncontended
+pub fn arc_clone() -> Benchmark {
+    arc_clone_of(Arc::new(7u64))
+}
+
+/// `arc_clone` cloning a given `shared`
+fn arc_clone_of(shared: Arc<u64>) -> Benchmark {
+    Benchmark::new("arc_clone", "Arc::clone + drop", move || {
+        let clone = black_box(Arc::clone(black_box(&shared)));
+        drop(clone);
+    })
+}
+
+// --------------------------
+// Thread-local vs global
+// --------------------------
+// The two ways tick_hz() could be cached: this tells whether moving
+// the global frequency into a thread-local would speed up ns().
+
+thread_local! {
+    static LOCAL_VALUE: Cell<u64> = const { Cell::new(2_099_999_999) };
+}
+
+static GLOBAL_VALUE: AtomicU64 = AtomicU64::new(2_099_999_999);
+
+/// One `thread_local!` Cell read
+pub fn thread_local_read() -> Benchmark {
+    Benchmark::new("thread_local_read", "thread_local! Cell read", || LOCAL_VALUE.with(|v| black_box(v).get()))
+}
+
+/// One acquire load of a `static AtomicU64`, as `tick_hz()` reads it
+/// (a plain load on x86, ldar on ARM64)
+pub fn atomic_read() -> Benchmark {
+    Benchmark::new("atomic_read", "static AtomicU64 acquire load", || black_box(&GLOBAL_VALUE).load(Ordering::Acquire))
+}
+
+/// Body shared by the call benchmarks, so only the dispatch differs
+#[inline(always)]
+fn add_one(x: u64) -> u64 {
+    x.wrapping_add(1)
+}
+
+/// Direct, inlined call: the baseline for `dyn_call`
+pub fn direct_call() -> Benchmark {
+    Benchmark::new("direct_call", "direct inlined call", || add_one(black_box(1)))
+}
+
+/// Indirect call through `Box<dyn Fn>`. black_box on the box hides the
+/// concrete type, so the call goes through the vtable.
+pub fn dyn_call() -> Benchmark {
+    dyn_call_to(Box::new(add_one))
+}
+
+/// `dyn_call` with the callee behind the trait object given
+fn dyn_call_to(f: Box<dyn Fn(u64) -> u64>) -> Benchmark {
+    Benchmark::new("dyn_call", "call via Box<dyn Fn>", move || black_box(&f)(black_box(1)))
+}
+
+// --------------------------
+// Branch prediction
+// --------------------------
+
+/// Entries in a branch pattern: too long for the predictor to learn
+/// when random, small enough to stay in L1
+pub const BRANCH_PATTERN_LEN: usize = 1 << 14;
+
+/// Precomputed taken/not-taken pattern: all taken, or random bits from
+/// a fixed-seed xorshift so every run sees the same sequence
+pub fn branch_pattern(len: usize, random: bool) -> Vec<bool> {
+    let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..len)
+        .map(|_| {
+            if !random {
+                return true;
+            }
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x & 1 == 1
+        })
+        .collect()
+}
+
+/// One data-dependent branch per call, driven by the next pattern
+/// entry. black_box in both arms keeps it a real branch, not a cmov.
+fn branch(name: &'static str, label: &'static str, random: bool) -> Benchmark {
+    let mut state: Option<(Vec<bool>, usize)> = None;
+
+    Benchmark::new(name, label, move || {
+        let (pattern, next) = state.get_or_insert_with(|| (branch_pattern(BRANCH_PATTERN_LEN, random), 0));
+        let taken = black_box(pattern[*next]);
+        *next = (*next + 1) % pattern.len();
+        if taken { black_box(*next + 1) } else { black_box(*next * 3) }
+    })
+}
+
+/// Always-taken branch: the baseline for `mispredicted_branch`
+pub fn predicted_branch() -> Benchmark {
+    branch("predicted_branch", "branch (always taken)", false)
+}
+
+/// Branch taken at random: about half the calls pay a misprediction,
+/// so the difference to `predicted_branch` is ~half the penalty
+pub fn mispredicted_branch() -> Benchmark {
+    branch("mispredicted_branch", "branch (random, ~50% mispredicted)", true)
+}
+
+// --------------------------
+// HashMap lookup
+// --------------------------
+
+/// Entries in the registered HashMap benchmarks' map (~1.5 MiB with
+/// SipHash buckets: past L2 on most cores)
+pub const HASHMAP_ENTRIES: u64 = 1 << 16;
+
+/// One `HashMap<u64, u64>::get` per call against a map of `entries`
+/// even keys, built on first run. Hits cycle through the stored keys,
+/// misses look up the odd keys between them.
+pub fn hashmap_lookup(name: &'static str, label: &'static str, entries: u64, hit: bool) -> Benchmark {
+    let mut state: Option<(HashMap<u64, u64>, u64)> = None;
+
+    Benchmark::new(name, label, move || {
+        let (map, next) = state.get_or_insert_with(|| ((0..entries.max(1)).map(|k| (k * 2, k)).collect(), 0));
+        *next = (*next + 1) % entries.max(1);
+        let key = if hit { *next * 2 } else { *next * 2 + 1 };
+        black_box(map.get(&black_box(key)).copied())
+    })
+}
+
+/// Lookup of a key that is in the map
+pub fn hashmap_hit() -> Benchmark {
+    hashmap_lookup("hashmap_hit", "HashMap<u64, u64> lookup (hit, 64K entries)", HASHMAP_ENTRIES, true)
+}
+
+/// Lookup of a key that is not in the map
+pub fn hashmap_miss() -> Benchmark {
+    hashmap_lookup("hashmap_miss", "HashMap<u64, u64> lookup (miss, 64K entries)", HASHMAP_ENTRIES, false)
+}
+
+// --------------------------
+// Array indexing
+// --------------------------
+
+/// Elements in the indexed slice (32 KiB of u64, L1/L2-resident so the
+/// bounds check, not the load, is what differs) and in the index sequence
+pub const INDEX_LEN: usize = 1 << 12;
+
+/// Fixed-seed xorshift indices into a slice of `range` elements, so the
+/// compiler can't prove them in bounds and every run sees the same ones
+pub fn index_sequence(len: usize, range: usize) -> Vec<usize> {
+    let mut x: u64 = 0x2545_F491_4F6C_DD1D;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            (x % range.max(1) as u64) as usize
+        })
+        .collect()
+}
+
+/// One slice load per call at the next black-boxed index, with or
+/// without the bounds check; data and indices are built on first run
+fn index(name: &'static str, label: &'static str, checked: bool) -> Benchmark {
+    let mut state: Option<(Vec<u64>, Vec<usize>, usize)> = None;
+
+    Benchmark::new(name, label, move || {
+        let (data, indices, next) =
+            state.get_or_insert_with(|| ((0..INDEX_LEN as u64).collect(), index_sequence(INDEX_LEN, INDEX_LEN), 0));
+        let i = black_box(indices[*next]);
+        *next = (*next + 1) % indices.len();
+        if checked {
+            black_box(data[i])
+        } else {
+            // index_sequence keeps every index below data.len()
+            black_box(unsafe { *data.get_unchecked(i) })
+        }
+    })
+}
+
+/// `v[i]`: a compare and branch before the load
+pub fn checked_index() -> Benchmark {
+    index("checked_index", "slice index v[i] (bounds-checked)", true)
+}
+
+/// `get_unchecked(i)`: the same loads without the check, so the
+/// difference to `checked_index` is the bounds check
+pub fn unchecked_index() -> Benchmark {
+    index("unchecked_index", "slice get_unchecked(i)", false)
+}
+
+// --------------------------
+// Array vs Vec iteration
+// --------------------------
+// The same elements summed one per call, held inline in the benchmark's
+// closure or behind a Vec's pointer. The closure is boxed, so neither is
+// on the stack: what differs is the Vec's extra pointer load and its
+// bounds check on an index the array's length proves. Both are
+// L1-resident.
+
+/// Elements summed by `inline_array_sum` and `vec_sum` (8 KiB of u64);
+/// a power of two, so the next index is a mask
+pub const ITER_LEN: usize = 1 << 10;
+
+/// The elements both variants sum: 1..=ITER_LEN
+pub fn iteration_data() -> [u64; ITER_LEN] {
+    std::array::from_fn(|i| i as u64 + 1)
+}
+
+/// One element of a `[u64; ITER_LEN]` added to a black-boxed sum per call
+pub fn inline_array_sum() -> Benchmark {
+    inline_array_sum_into(Rc::default())
+}
+
+/// `inline_array_sum`, keeping the running sum in `total`
+fn inline_array_sum_into(total: Rc<Cell<u64>>) -> Benchmark {
+    let data = iteration_data();
+    let mut next = 0;
+    Benchmark::new("inline_array_sum", "[u64; 1024] element sum (inline)", move || {
+        total.set(black_box(total.get().wrapping_add(data[next])));
+        next = (next + 1) & (ITER_LEN - 1);
+    })
+}
+
+/// The same sum over a `Vec<u64>` of the same elements
+pub fn vec_sum() -> Benchmark {
+    vec_sum_into(Rc::default())
+}
+
+/// `vec_sum`, keeping the running sum in `total`
+fn vec_sum_into(total: Rc<Cell<u64>>) -> Benchmark {
+    let data = iteration_data().to_vec();
+    let mut next = 0;
+    Benchmark::new("vec_sum", "Vec<u64> element sum (1024)", move || {
+        total.set(black_box(total.get().wrapping_add(data[next])));
+        next = (next + 1) & (ITER_LEN - 1);
+    })
+}
+
+// --------------------------
+// Memory read latency
+// --------------------------
+
+/// Bytes per cache line assumed by the read benchmarks
+pub const CACHE_LINE: usize = 64;
+
+const WORDS_PER_LINE: usize = CACHE_LINE / core::mem::size_of::<usize>();
+
+/// Pointer-chase chain over `lines` cache lines: the first word of line
+/// i holds the index of line (i + stride) % lines.
+///
+/// With `stride` coprime to `lines` the walk visits every line once
+/// before repeating. Each read depends on the previous one, so the
+/// loads can't overlap and each costs a full access latency.
+pub fn stride_chain(lines: usize, stride: usize) -> Vec<usize> {
+    let mut chain = vec![0usize; lines * WORDS_PER_LINE];
+    for line in 0..lines {
+        chain[line * WORDS_PER_LINE] = (line + stride) % lines * WORDS_PER_LINE;
+    }
+    chain
+}
+
+/// Benchmark that follows a `stride_chain` one read per call; the
+/// chain is allocated on first run
+fn chase(name: &'static str, label: &'static str, lines: usize, stride: usize) -> Benchmark {
+    let mut state: Option<(Vec<usize>, usize)> = None;
+
+    Benchmark::new(name, label, move || {
+        let (chain, next) = state.get_or_insert_with(|| (stride_chain(lines, stride), 0));
+        *next = chain[*next];
+        *next
+    })
+}
+
+/// Dependent reads within 4 KiB, which stays L1-resident
+pub fn cached_read() -> Benchmark {
+    chase("cached_read", "memory read (L1-resident)", 64, 1)
+}
+
+/// Dependent reads across 64 MiB with a 4099-line (~256 KiB) stride:
+/// larger than the last-level cache and too far apart for the
+/// prefetchers, so every read goes to DRAM
+pub fn uncached_read() -> Benchmark {
+    chase("uncached_read", "memory read (evicted, DRAM)", 1 << 20, 4099).loops(1_000_000)
+}
+
+// --------------------------
+// Page faults
+// --------------------------
+
+/// Pages in each `PageRegion` (64 MiB at 4 KiB pages), and the default
+/// loops of the page benchmarks so a run uses one region
+pub const PAGE_REGION_PAGES: usize = 16_384;
+
+/// A fresh anonymous mapping, so every page starts unmapped (mmap on
+/// Unix, VirtualAlloc behind HeapAlloc on Windows).
+///
+/// On Linux it is marked MADV_NOHUGEPAGE: with transparent huge pages
+/// the first write would map 2 MiB at once and the next 511 writes
+/// would not fault at all.
+struct PageRegion {
+    ptr: *mut u8,
+    layout: Layout,
+    page_size: usize,
+    next: usize,
+}
+
+impl PageRegion {
+    fn new() -> Self {
+        let page_size = page_size();
+        let layout = Layout::from_size_align(PAGE_REGION_PAGES * page_size, page_size).expect("page region layout");
+        Self { ptr: map_pages(layout), layout, page_size, next: 0 }
+    }
+
+    /// Write one byte to the next page, wrapping at the end
+    fn write_next(&mut self) -> usize {
+        let page = self.next;
+        self.next = (self.next + 1) % PAGE_REGION_PAGES;
+        unsafe { std::ptr::write_volatile(self.ptr.add(page * self.page_size), 1) };
+        page
+    }
+
+    /// Pages not written yet (0 once `write_next` has wrapped)
+    fn fresh_pages(&self) -> usize {
+        PAGE_REGION_PAGES - self.next
+    }
+
+    /// A region with every page already written (faulted in)
+    fn mapped() -> Self {
+        let mut region = Self::new();
+        for _ in 0..PAGE_REGION_PAGES {
+            region.write_next();
+        }
+        region
+    }
+}
+
+#[cfg(unix)]
+fn map_pages(layout: Layout) -> *mut u8 {
+    let ptr = unsafe {
+        libc::mmap(
+            core::ptr::null_mut(),
+            layout.size(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        panic!("mmap of {} bytes failed: {}", layout.size(), std::io::Error::last_os_error());
+    }
+    // best effort: a kernel without THP rejects the advice and has no
+    // huge pages to avoid
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unsafe {
+        libc::madvise(ptr, layout.size(), libc::MADV_NOHUGEPAGE);
+    }
+    ptr.cast()
+}
+
+#[cfg(not(unix))]
+fn map_pages(layout: Layout) -> *mut u8 {
+    let ptr = unsafe { std::alloc::alloc(layout) };
+    if ptr.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    ptr
+}
+
+impl Drop for PageRegion {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr.cast(), self.layout.size());
+        }
+        #[cfg(not(unix))]
+        unsafe {
+            std::alloc::dealloc(self.ptr, self.layout)
+        };
+    }
+}
+
+/// Replace `region` with a new one unless it has `pages` fresh pages
+/// left (the old one is unmapped first, so at most one is held)
+fn ensure_fresh_pages(region: &mut Option<PageRegion>, pages: usize) -> &mut PageRegion {
+    if region.as_ref().is_some_and(|region| region.fresh_pages() < pages) {
+        *region = None;
+    }
+    region.get_or_insert_with(PageRegion::new)
+}
+
+/// First write to a never-touched page: a minor fault, where the kernel
+/// maps a zeroed page.
+///
+/// One call per sample: the prepare step maps a new region before any
+/// batch the current one can't serve, so no timed call pays for the
+/// mmap or munmap.
+pub fn page_fault() -> Benchmark {
+    let region: Rc<RefCell<Option<PageRegion>>> = Rc::default();
+    let prepared = Rc::clone(&region);
+
+    Benchmark::new("page_fault", "first write to a fresh page (minor fault)", move || {
+        ensure_fresh_pages(&mut region.borrow_mut(), 1).write_next()
+    })
+    .with_prepare(move |iterations| {
+        ensure_fresh_pages(&mut prepared.borrow_mut(), iterations as usize);
+    })
+    .fixed_batch(1)
+    .loops(PAGE_REGION_PAGES as u64)
+}
+
+/// Write to a page already faulted in, walking a region the same size
+/// as `page_fault`'s (so both pay the same TLB misses); the baseline
+/// for the fault cost. The prepare step faults the region in.
+pub fn page_rewrite() -> Benchmark {
+    let region: Rc<RefCell<Option<PageRegion>>> = Rc::default();
+    let prepared = Rc::clone(&region);
+
+    Benchmark::new("page_rewrite", "write to an already-mapped page", move || {
+        region.borrow_mut().get_or_insert_with(PageRegion::mapped).write_next()
+    })
+    .with_prepare(move |_| {
+        prepared.borrow_mut().get_or_insert_with(PageRegion::mapped);
+    })
+    .fixed_batch(1)
+    .loops(PAGE_REGION_PAGES as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::measure;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// The system allocator, counting this thread's allocations and
+    /// reallocations (and frees) so tests can see what a benchmark body
+    /// allocates
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+        static FREES: Cell<u64> = const { Cell::new(0) };
+    }
+
+    fn count_allocation() {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_allocation();
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = FREES.try_with(|n| n.set(n.get() + 1));
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count_allocation();
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    /// Allocations and reallocations `f` makes on this thread
+    fn allocations_during(f: impl FnOnce()) -> u64 {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    /// Frees `f` makes on this thread
+    fn frees_during(f: impl FnOnce()) -> u64 {
+        let before = FREES.with(Cell::get);
+        f();
+        FREES.with(Cell::get) - before
+    }
+
+    #[test]
+    fn ping_pong_round_trips() {
+        let (ping_tx, pong_rx) = spawn_echo_thread();
+        for value in 0..1_000 {
+            ping_tx.send(value).unwrap();
+            assert_eq!(pong_rx.recv().unwrap(), value);
+        }
+        // the benchmark's own partner answers every round trip too
+        context_switch().run(1_000);
+    }
+
+    #[test]
+    fn dyn_call_goes_through_the_trait_object() {
+        let calls = Rc::new(Cell::new(0u64));
+        let counter = calls.clone();
+        let mut bench = dyn_call_to(Box::new(move |x| {
+            counter.set(counter.get() + 1);
+            add_one(x)
+        }));
+        bench.run(500);
+        assert_eq!(calls.get(), 500);
+    }
+
+    /// Distinct cache lines a walk of `steps` reads visits from index 0
+    fn lines_visited(chain: &[usize], steps: usize) -> usize {
+        let mut next = 0;
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..steps {
+            next = chain[next];
+            assert_eq!(next % WORDS_PER_LINE, 0, "reads the first word of a line");
+            seen.insert(next / WORDS_PER_LINE);
+        }
+        seen.len()
+    }
+
+    #[test]
+    fn stride_chain_touches_every_line() {
+        let chain = stride_chain(1 << 10, 4099);
+        assert_eq!(chain.len() * core::mem::size_of::<usize>(), (1 << 10) * CACHE_LINE);
+        assert_eq!(lines_visited(&chain, 1 << 10), 1 << 10);
+        // twice round the chain: still the same lines
+        assert_eq!(lines_visited(&chain, 2 << 10), 1 << 10);
+        // a stride sharing a factor with the length only covers part
+        assert_eq!(lines_visited(&stride_chain(64, 8), 64), 8);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn getpid_times_a_real_syscall() {
+        let mut bench = getpid();
+        assert_eq!(bench.default_loops, 1_000_000);
+        let result = measure(&mut bench, 10_000);
+        assert_eq!((result.name.as_str(), result.loops), ("getpid", 10_000));
+        // a syscall round trip is never free, even in every batch
+        assert!(result.samples.iter().all(|&ns| ns > 0.0), "{:?}", result.samples);
+    }
+
+    #[test]
+    fn ns_conversion_is_pure_arithmetic() {
+        let mut bench = catalog().into_iter().find(|b| b.name == "ns_conversion").unwrap();
+        let result = measure(&mut bench, 100_000);
+        assert_eq!(result.loops, 100_000);
+        // no counter read or syscall: far below a microsecond per call
+        assert!(result.ns_per_call() < 1_000.0, "{} ns/call", result.ns_per_call());
+    }
+
+    #[test]
+    fn uncontended_mutex_is_released() {
+        let mutex = Arc::new(Mutex::new(0));
+        let mut bench = mutex_uncontended_on(Arc::clone(&mutex));
+        bench.run(10_000);
+        assert_eq!(*mutex.try_lock().expect("left locked"), 10_000);
+        bench.run(5);
+        assert_eq!(*mutex.try_lock().expect("left locked"), 10_005);
+    }
+
+    #[test]
+    fn divisions_measure_finite_costs() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        for mut bench in [u64_division(), f64_division()] {
+            let result = crate::benchmark::measure(&mut bench, 10_000);
+            assert_eq!(result.loops, 10_000, "{}", result.name);
+            assert!(!result.samples.is_empty());
+            assert!(result.samples.iter().all(|ns| ns.is_finite() && *ns >= 0.0), "{:?}", result.samples);
+            assert!(result.stats.mean.is_finite() && result.stats.p50.is_finite(), "{:?}", result.stats);
+        }
+    }
+
+    #[test]
+    fn random_branches_cost_more() {
+        let random = branch_pattern(BRANCH_PATTERN_LEN, true);
+        assert_eq!(random, branch_pattern(BRANCH_PATTERN_LEN, true), "fixed seed");
+        let taken = random.iter().filter(|&&t| t).count();
+        assert!((BRANCH_PATTERN_LEN * 2 / 5..BRANCH_PATTERN_LEN * 3 / 5).contains(&taken), "{}", taken);
+        assert!(branch_pattern(64, false).iter().all(|&t| t));
+
+        let _lock = crate::high_resolution_timer::test_lock();
+        // best median of a few interleaved runs, each wrapping the pattern
+        let loops = 4 * BRANCH_PATTERN_LEN as u64;
+        let (mut predictable, mut random) = (predicted_branch(), mispredicted_branch());
+        predictable.run(loops);
+        random.run(loops);
+        let (mut predicted, mut mispredicted) = (f64::INFINITY, f64::INFINITY);
+        for _ in 0..5 {
+            predicted = predicted.min(measure(&mut predictable, loops).stats.p50);
+            mispredicted = mispredicted.min(measure(&mut random, loops).stats.p50);
+        }
+        assert!(mispredicted > predicted, "{} vs {} ns/call", mispredicted, predicted);
+    }
+
+    #[test]
+    fn access_benchmarks_measure_finite_costs() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        for mut bench in [thread_local_read(), atomic_read()] {
+            let result = crate::benchmark::measure(&mut bench, 10_000);
+            assert_eq!(result.loops, 10_000, "{}", result.name);
+            assert!(result.samples.iter().all(|ns| ns.is_finite() && *ns >= 0.0), "{:?}", result.samples);
+            assert!(result.stats.p50.is_finite());
+        }
+    }
+
+    #[test]
+    fn format_string_costs_more_than_a_call() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        // formatting and allocating on every call can't fold into the
+        // loop the way a plain call can
+        let best_p50 = |mut bench: Benchmark| {
+            (0..3).map(|_| measure(&mut bench, 100_000).stats.p50).fold(f64::INFINITY, f64::min)
+        };
+        let format = best_p50(format_string());
+        let call = best_p50(direct_call());
+        assert!(format > call, "{} vs {} ns/call", format, call);
+    }
+
+    #[test]
+    fn only_the_growing_vec_reallocates() {
+        let (mut growing, mut preallocated) = (vec_push_growing(), vec_push_preallocated());
+        // the first call allocates either Vec
+        growing.run(1);
+        preallocated.run(1);
+
+        // to the end of the fill and one push into the next: doubling
+        // from 4 to VEC_FILL elements, then a fresh Vec
+        let grown = allocations_during(|| growing.run(VEC_FILL as u64));
+        assert!(grown >= VEC_FILL.ilog2() as u64 - 1, "{} allocations", grown);
+        assert_eq!(allocations_during(|| preallocated.run(4 * VEC_FILL as u64)), 0);
+    }
+
+    #[test]
+    fn hashmap_is_built_once_then_only_looked_up() {
+        for mut bench in [hashmap_hit(), hashmap_miss()] {
+            // the first call fills HASHMAP_ENTRIES entries
+            assert!(allocations_during(|| bench.run(1)) > 0, "{}", bench.name);
+            assert_eq!(allocations_during(|| bench.run(2 * HASHMAP_ENTRIES)), 0, "{}", bench.name);
+
+            let _lock = crate::high_resolution_timer::test_lock();
+            let result = measure(&mut bench, 10_000);
+            assert_eq!(result.loops, 10_000);
+            assert!(result.stats.p50 > 0.0, "{:?}", result.stats);
+        }
+    }
+
+    #[test]
+    fn cas_increments_every_call() {
+        let value = AtomicU64::new(0);
+        for expected in 1..=10_000 {
+            assert_eq!(cas_increment(&value), expected);
+        }
+        assert_eq!(value.load(Ordering::Relaxed), 10_000);
+
+        // two threads: no increment is lost
+        let shared = Arc::new(AtomicU64::new(0));
+        let other = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    cas_increment(&shared);
+                }
+            })
+        };
+        for _ in 0..10_000 {
+            cas_increment(&shared);
+        }
+        other.join().unwrap();
+        assert_eq!(shared.load(Ordering::Relaxed), 20_000);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn page_fault_times_one_fresh_page_per_sample() {
+        use crate::noise::ResourceUsage;
+
+        let minor_faults_during = |f: &mut dyn FnMut()| {
+            let before = ResourceUsage::now().unwrap();
+            f();
+            before.delta(&ResourceUsage::now().unwrap()).minor_faults
+        };
+
+        let mut fault = page_fault();
+        let mut result = None;
+        let faults = minor_faults_during(&mut || result = Some(measure(&mut fault, 1_000)));
+        assert_eq!(result.take().unwrap().samples.len(), 1_000, "one sample per page");
+        assert!(faults >= 1_000, "{faults} faults for 1000 fresh pages");
+
+        // the region is faulted in by prepare, outside the timed calls
+        let mut rewrite = page_rewrite();
+        rewrite.prepare(1);
+        let faults = minor_faults_during(&mut || result = Some(measure(&mut rewrite, 1_000)));
+        assert_eq!(result.take().unwrap().samples.len(), 1_000);
+        assert!(faults < 100, "{faults} faults rewriting mapped pages");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn after_syscall_costs_more_than_the_syscall() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        // best median of a few interleaved runs: the clock read on top
+        // of getpid shows up as extra cost
+        let (mut syscall, mut after_syscall) = (getpid(), clock_gettime_after_syscall());
+        let (mut alone, mut with_read) = (f64::INFINITY, f64::INFINITY);
+        for _ in 0..5 {
+            alone = alone.min(measure(&mut syscall, 100_000).stats.p50);
+            with_read = with_read.min(measure(&mut after_syscall, 100_000).stats.p50);
+        }
+        assert!(with_read > alone, "{} vs {} ns/call", with_read, alone);
+    }
+
+    #[test]
+    fn timer_start_yields_working_timers() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let result = crate::benchmark::measure(&mut timer_start(), 1_000);
+        assert_eq!(result.loops, 1_000);
+
+        // every timer it builds measures real elapsed time
+        let timers: Vec<HighResolutionTimer> = (0..100).map(|_| HighResolutionTimer::start()).collect();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        for timer in &timers {
+            let ns = timer.ns();
+            assert!((2_000_000..1_000_000_000).contains(&ns), "{}", ns);
+        }
+    }
+
+    #[test]
+    fn index_benchmarks_wrap_around_in_bounds_indices() {
+        let indices = index_sequence(INDEX_LEN, INDEX_LEN);
+        assert_eq!(indices, index_sequence(INDEX_LEN, INDEX_LEN), "fixed seed");
+        assert!(indices.iter().all(|&i| i < INDEX_LEN));
+        // spread over the slice, not stuck in a short cycle
+        let distinct: std::collections::HashSet<_> = indices.iter().collect();
+        assert!(distinct.len() > INDEX_LEN / 2, "{} distinct", distinct.len());
+
+        let _lock = crate::high_resolution_timer::test_lock();
+        // past the end of the index sequence, which panics unless the
+        // cursor wraps
+        let loops = 3 * INDEX_LEN as u64;
+        for mut bench in [checked_index(), unchecked_index()] {
+            let result = measure(&mut bench, loops);
+            assert_eq!(result.loops, loops, "{}", result.name);
+            assert!(result.samples.iter().all(|ns| ns.is_finite() && *ns >= 0.0), "{:?}", result.samples);
+        }
+    }
+
+    #[test]
+    fn channels_carry_one_message_per_call() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        // a call leaving its message queued would block the bounded
+        // channel's next send forever, with no other thread to drain it
+        for mut bench in [channel_send_recv(), sync_channel_send_recv()] {
+            let result = measure(&mut bench, 10_000);
+            assert_eq!(result.loops, 10_000, "{}", result.name);
+            assert!(result.samples.iter().all(|ns| ns.is_finite() && *ns > 0.0), "{:?}", result.samples);
+        }
+    }
+
+    #[test]
+    fn box_alloc_frees_every_box_it_allocates() {
+        let mut bench = box_alloc();
+        let mut allocated = 0;
+        let freed = frees_during(|| allocated = allocations_during(|| bench.run(1_000)));
+        assert_eq!((allocated, freed), (1_000, 1_000));
+    }
+
+    #[test]
+    fn clone_benchmarks_drop_every_clone() {
+        let rc = Rc::new(7u64);
+        let arc = Arc::new(7u64);
+        let (mut rc_bench, mut arc_bench) = (rc_clone_of(Rc::clone(&rc)), arc_clone_of(Arc::clone(&arc)));
+        rc_bench.run(1_000);
+        arc_bench.run(1_000);
+        // ours plus the benchmark's: no clone outlived its iteration
+        assert_eq!((Rc::strong_count(&rc), Arc::strong_count(&arc)), (2, 2));
+        drop((rc_bench, arc_bench));
+        assert_eq!((Rc::strong_count(&rc), Arc::strong_count(&arc)), (1, 1));
+    }
+
+    #[test]
+    fn array_and_vec_sums_agree() {
+        let (array_total, vec_total) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let mut array = inline_array_sum_into(array_total.clone());
+        let mut vec = vec_sum_into(vec_total.clone());
+        array.run(3 * ITER_LEN as u64);
+        vec.run(3 * ITER_LEN as u64);
+        // three passes over 1..=ITER_LEN
+        let n = ITER_LEN as u64;
+        assert_eq!(array_total.get(), 3 * n * (n + 1) / 2);
+        assert_eq!(vec_total.get(), array_total.get());
+    }
+
+    #[test]
+    fn thread_spawn_joins_every_thread() {
+        let ran = Arc::new(AtomicU64::new(0));
+        let elsewhere = Arc::new(AtomicU64::new(0));
+        let main = thread::current().id();
+        let (counter, other) = (Arc::clone(&ran), Arc::clone(&elsewhere));
+        let mut bench = thread_spawn_running(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            if thread::current().id() != main {
+                other.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        bench.run(50);
+        // joined: every thread's work is done when run returns
+        assert_eq!((ran.load(Ordering::SeqCst), elsewhere.load(Ordering::SeqCst)), (50, 50));
+        drop(bench);
+        assert_eq!(Arc::strong_count(&ran), 1, "every thread dropped its copy");
+    }
+}
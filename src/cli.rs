@@ -0,0 +1,495 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::runner::{Format, RunConfig};
+use crate::stats::HistogramSpec;
+
+/// ------------------------------------------------------------
+/// Command line options
+/// ------------------------------------------------------------
+/// Populates a `RunConfig` from flags:
+///
+/// --loops N, --batch-size N, --warmup N, --runs N, --discard-runs K,
+/// --format auto|text|json|csv|prometheus|gobench|oneline|bmf, --oneline,
+/// --precision N, --units ns|us|auto, --cpu N, --bench NAME (repeatable), --detect-warmup,
+/// --memory-pressure MB, --trim PCT, --calibration-ms MS, --calibration-cache,
+/// --history PATH, --rt, --hist-buckets N, --hist-max NS,
+/// --hist-scale log|linear, --relative-to NAME, --list-backends,
+/// --backend tsc|monotonic|qpc, --plot PREFIX, --sweep, --sweep-cpus,
+/// --timeline PATH, --version, --recommend, --label-width N, --scaling, --trace,
+/// --max-runtime DURATION (e.g. 30s, 500ms, 2m), --high-timer-res,
+/// --dump-calibration, --freq-monitor DURATION, --validate DURATION, --assert NAME=NS
+/// (repeatable), --fail-fast, --priority high|realtime, --min-samples N,
+/// --diff OLD NEW, --thread-stack MB, --target-precision FRACTION,
+/// --tag KEY=VALUE (repeatable), --clock-drift DURATION
+///
+/// See `parse_args_from_sources` for the `TIMING_EVAL_*` variables and
+/// the config file.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<RunConfig, String> {
+    let mut config = RunConfig::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        config = match arg.as_str() {
+            "--loops" => config.loops(parse_value(&arg, args.next())?),
+            "--batch-size" => config.batch_size(parse_value(&arg, args.next())?),
+            "--target-precision" => config.target_precision(parse_value(&arg, args.next())?),
+            "--thread-stack" => config.thread_stack(parse_value(&arg, args.next())?),
+            "--warmup" => config.warmup(parse_value(&arg, args.next())?),
+            "--runs" => config.runs(parse_value(&arg, args.next())?),
+            "--discard-runs" => config.discard_runs(parse_value(&arg, args.next())?),
+            "--format" => config.format(parse_value(&arg, args.next())?),
+            "--precision" => config.precision(parse_value(&arg, args.next())?),
+            "--units" => config.units(parse_value(&arg, args.next())?),
+            "--label-width" => config.label_width(parse_value(&arg, args.next())?),
+            "--cpu" => config.cpu(parse_value(&arg, args.next())?),
+            "--bench" => config.benchmark(parse_value::<String>(&arg, args.next())?),
+            "--detect-warmup" => config.detect_warmup(true),
+            "--fail-fast" => config.fail_fast(true),
+            "--oneline" => config.format(Format::Oneline),
+            "--rt" => config.realtime(true),
+            "--high-timer-res" => config.high_timer_res(true),
+            "--sweep" => config.sweep(true),
+            "--sweep-cpus" => config.sweep_cpus(true),
+            "--scaling" => config.scaling(true),
+            "--trace" => config.trace(true),
+            "--list-backends" => config.list_backends(true),
+            "--version" => config.version(true),
+            "--recommend" => config.recommend(true),
+            "--backend" => config.backend(parse_value(&arg, args.next())?),
+            "--priority" => config.priority(parse_value(&arg, args.next())?),
+            "--calibration-ms" => {
+                config.calibration_window(Duration::from_millis(parse_value(&arg, args.next())?))
+            }
+            "--calibration-cache" => config.calibration_cache(true),
+            "--dump-calibration" => config.dump_calibration(true),
+            "--hist-buckets" => {
+                let buckets = parse_value(&arg, args.next())?;
+                let spec = config.histogram;
+                config.histogram(HistogramSpec { buckets, ..spec })
+            }
+            "--hist-max" => {
+                let max = parse_value(&arg, args.next())?;
+                let spec = config.histogram;
+                config.histogram(HistogramSpec { max: Some(max), ..spec })
+            }
+            "--hist-scale" => {
+                let scale = parse_value(&arg, args.next())?;
+                let spec = config.histogram;
+                config.histogram(HistogramSpec { scale, ..spec })
+            }
+            "--tag" => {
+                let (key, value) = parse_tag(&arg, args.next())?;
+                config.tag(key, value)
+            }
+            "--assert" => {
+                let (name, max_ns) = parse_assertion(&arg, args.next())?;
+                config.assertion(name, max_ns)
+            }
+            "--relative-to" => config.relative_to(parse_value::<String>(&arg, args.next())?),
+            "--trim" => config.trim(parse_value(&arg, args.next())?),
+            "--min-samples" => config.min_samples(parse_value(&arg, args.next())?),
+            "--history" => config.history(parse_value::<String>(&arg, args.next())?),
+            "--diff" => {
+                let old = parse_value::<String>(&arg, args.next())?;
+                config.diff(old, parse_value::<String>(&arg, args.next())?)
+            }
+            "--plot" => config.plot(parse_value::<String>(&arg, args.next())?),
+            "--timeline" => config.timeline(parse_value::<String>(&arg, args.next())?),
+            "--freq-monitor" => config.freq_monitor(parse_duration(&arg, args.next())?),
+            "--validate" => config.validate(parse_duration(&arg, args.next())?),
+            "--clock-drift" => config.clock_drift(parse_duration(&arg, args.next())?),
+            "--max-runtime" => config.max_runtime(parse_duration(&arg, args.next())?),
+            "--memory-pressure" => config.memory_pressure(parse_value(&arg, args.next())?),
+            other => return Err(format!("unknown argument: {}", other)),
+        };
+    }
+
+    config.build()
+}
+
+/// Prefix of the environment variables that stand in for flags
+pub const ENV_PREFIX: &str = "TIMING_EVAL_";
+
+/// Variable naming a config file when `--config` isn't given
+pub const CONFIG_ENV: &str = "TIMING_EVAL_CONFIG";
+
+/// How many values follow a flag, which decides how the environment and
+/// a config file spell it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// No value; `1`/`true`/`yes`/`on` or `0`/`false`/`no`/`off`
+    Switch,
+    Value,
+    /// Repeatable: comma-separated in a variable, one line per value in
+    /// a config file
+    Repeated,
+    /// Two values separated by whitespace
+    Pair,
+}
+
+impl Arity {
+    /// Arguments following the flag on the command line
+    fn values(self) -> usize {
+        match self {
+            Arity::Switch => 0,
+            Arity::Value | Arity::Repeated => 1,
+            Arity::Pair => 2,
+        }
+    }
+}
+
+/// Every flag `parse_args` takes (without the `--`), each settable as
+/// `TIMING_EVAL_<FLAG>` (upper case, `-` as `_`) and as `flag = value`
+/// in a config file
+pub const FLAGS: &[(&str, Arity)] = &[
+    ("loops", Arity::Value),
+    ("batch-size", Arity::Value),
+    ("target-precision", Arity::Value),
+    ("thread-stack", Arity::Value),
+    ("warmup", Arity::Value),
+    ("runs", Arity::Value),
+    ("discard-runs", Arity::Value),
+    ("format", Arity::Value),
+    ("precision", Arity::Value),
+    ("units", Arity::Value),
+    ("label-width", Arity::Value),
+    ("cpu", Arity::Value),
+    ("bench", Arity::Repeated),
+    ("detect-warmup", Arity::Switch),
+    ("fail-fast", Arity::Switch),
+    ("oneline", Arity::Switch),
+    ("rt", Arity::Switch),
+    ("high-timer-res", Arity::Switch),
+    ("sweep", Arity::Switch),
+    ("sweep-cpus", Arity::Switch),
+    ("scaling", Arity::Switch),
+    ("trace", Arity::Switch),
+    ("list-backends", Arity::Switch),
+    ("version", Arity::Switch),
+    ("recommend", Arity::Switch),
+    ("backend", Arity::Value),
+    ("priority", Arity::Value),
+    ("calibration-ms", Arity::Value),
+    ("calibration-cache", Arity::Switch),
+    ("dump-calibration", Arity::Switch),
+    ("hist-buckets", Arity::Value),
+    ("hist-max", Arity::Value),
+    ("hist-scale", Arity::Value),
+    ("assert", Arity::Repeated),
+    ("tag", Arity::Repeated),
+    ("relative-to", Arity::Value),
+    ("trim", Arity::Value),
+    ("min-samples", Arity::Value),
+    ("history", Arity::Value),
+    ("diff", Arity::Pair),
+    ("plot", Arity::Value),
+    ("timeline", Arity::Value),
+    ("freq-monitor", Arity::Value),
+    ("validate", Arity::Value),
+    ("clock-drift", Arity::Value),
+    ("max-runtime", Arity::Value),
+    ("memory-pressure", Arity::Value),
+];
+
+/// The environment variable standing in for `flag`: `batch-size` is
+/// `TIMING_EVAL_BATCH_SIZE`
+pub fn env_var_name(flag: &str) -> String {
+    format!("{}{}", ENV_PREFIX, flag.to_ascii_uppercase().replace('-', "_"))
+}
+
+/// One knob as a source set it: the flag and the arguments standing in
+/// for it (none for a switch turned off)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub flag: &'static str,
+    pub args: Vec<String>,
+}
+
+impl Setting {
+    /// `flag` set to `value`; `split_list` reads a repeatable flag's
+    /// value as a comma-separated list
+    fn parse(flag: &'static str, arity: Arity, value: &str, split_list: bool) -> Result<Self, String> {
+        let name = format!("--{}", flag);
+        let invalid = || format!("invalid value for {}: {}", name, value);
+        let args = match arity {
+            Arity::Switch => match value.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => vec![name],
+                "0" | "false" | "no" | "off" => Vec::new(),
+                _ => return Err(invalid()),
+            },
+            Arity::Value => vec![name, value.to_string()],
+            Arity::Repeated if split_list => value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .flat_map(|v| [name.clone(), v.to_string()])
+                .collect(),
+            Arity::Repeated => vec![name, value.to_string()],
+            Arity::Pair => match value.split_whitespace().collect::<Vec<_>>()[..] {
+                [first, second] => vec![name, first.to_string(), second.to_string()],
+                _ => return Err(invalid()),
+            },
+        };
+        Ok(Setting { flag, args })
+    }
+}
+
+/// The `TIMING_EVAL_*` variables found by `lookup`, in `FLAGS` order
+/// (empty values are ignored)
+pub fn env_settings(lookup: impl Fn(&str) -> Option<String>) -> Result<Vec<Setting>, String> {
+    FLAGS
+        .iter()
+        .filter_map(|&(flag, arity)| {
+            let name = env_var_name(flag);
+            let value = lookup(&name).filter(|v| !v.is_empty())?;
+            Some(Setting::parse(flag, arity, &value, true).map_err(|e| format!("{} (from {})", e, name)))
+        })
+        .collect()
+}
+
+/// The settings of a config file: `flag = value` lines (`--` and `_`
+/// for `-` allowed in the flag), `#` comments and blank lines skipped
+pub fn config_settings(text: &str) -> Result<Vec<Setting>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected FLAG = VALUE, got {}", number, line))?;
+            let key = key.trim().trim_start_matches("--").replace('_', "-");
+            let &(flag, arity) = FLAGS
+                .iter()
+                .find(|(flag, _)| *flag == key)
+                .ok_or_else(|| format!("line {}: unknown flag {}", number, key))?;
+            Setting::parse(flag, arity, value.trim(), false).map_err(|e| format!("line {}: {}", number, e))
+        })
+        .collect()
+}
+
+/// The command line with `--config PATH` split off
+struct CommandLine {
+    config: Option<PathBuf>,
+    args: Vec<String>,
+    /// Flags `args` sets
+    flags: Vec<&'static str>,
+}
+
+fn split_command_line(args: Vec<String>) -> Result<CommandLine, String> {
+    let mut config = None;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut mentioned = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            config = Some(PathBuf::from(parse_value::<String>(&arg, args.next())?));
+            continue;
+        }
+        let known = arg.strip_prefix("--").and_then(|name| FLAGS.iter().find(|(flag, _)| *flag == name));
+        rest.push(arg);
+        if let Some(&(flag, arity)) = known {
+            mentioned.push(flag);
+            rest.extend(args.by_ref().take(arity.values()));
+        }
+    }
+    Ok(CommandLine { config, args: rest, flags: mentioned })
+}
+
+/// `parse_args` over every source, reading a config file with
+/// `read_config`. This is the one place the precedence is decided: a
+/// flag on the command line beats its `TIMING_EVAL_*` variable, which
+/// beats the config file (`--config PATH`, else `TIMING_EVAL_CONFIG`),
+/// which beats the default. A source that sets a flag at all hides what
+/// lower ones set for it, so a variable can switch a file's `rt = true`
+/// back off and a `--bench` list replaces rather than extends one.
+pub fn parse_args_from_sources(
+    args: impl IntoIterator<Item = String>,
+    lookup: impl Fn(&str) -> Option<String>,
+    read_config: impl Fn(&Path) -> std::io::Result<String>,
+) -> Result<RunConfig, String> {
+    let CommandLine { config: config_path, args: command_line, flags: on_command_line } =
+        split_command_line(args.into_iter().collect())?;
+    let config_path = config_path.or_else(|| lookup(CONFIG_ENV).filter(|v| !v.is_empty()).map(PathBuf::from));
+    let file = match &config_path {
+        Some(path) => {
+            let text = read_config(path).map_err(|e| format!("cannot read config {}: {}", path.display(), e))?;
+            config_settings(&text).map_err(|e| format!("config {}: {}", path.display(), e))?
+        }
+        None => Vec::new(),
+    };
+    let env = env_settings(&lookup)?;
+
+    let env_flags: Vec<&str> = env.iter().map(|s| s.flag).collect();
+    let file_args: Vec<String> = file
+        .into_iter()
+        .filter(|s| !on_command_line.contains(&s.flag) && !env_flags.contains(&s.flag))
+        .flat_map(|s| s.args)
+        .collect();
+    let env: Vec<Setting> = env.into_iter().filter(|s| !on_command_line.contains(&s.flag)).collect();
+    let env_args: Vec<String> = env.iter().flat_map(|s| s.args.iter().cloned()).collect();
+
+    // Every setter overwrites what an earlier flag set, so lower sources go first
+    let layered = file_args.iter().chain(&env_args).chain(&command_line).cloned();
+    parse_args(layered).map_err(|e| {
+        // Blame a source that is bad on its own
+        if let (Some(path), Err(_)) = (&config_path, parse_args(file_args.clone())) {
+            format!("{} (from config {})", e, path.display())
+        } else if let Some(bad) = env.iter().find(|s| parse_args(s.args.clone()).is_err()) {
+            format!("{} (from {})", e, env_var_name(bad.flag))
+        } else if parse_args(env_args.clone()).is_err() {
+            format!("{} (from {}* environment)", e, ENV_PREFIX)
+        } else {
+            e
+        }
+    })
+}
+
+/// `parse_args_from_sources` with config files read from disk
+pub fn parse_args_with_env(
+    args: impl IntoIterator<Item = String>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<RunConfig, String> {
+    parse_args_from_sources(args, lookup, |path| std::fs::read_to_string(path))
+}
+
+/// Parse a duration following `flag`: a number with an `ms`, `s` or
+/// `m` suffix, or bare seconds
+fn parse_duration(flag: &str, value: Option<String>) -> Result<Duration, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    let (number, unit_ms) = if let Some(n) = value.strip_suffix("ms") {
+        (n, 1)
+    } else if let Some(n) = value.strip_suffix('s') {
+        (n, 1_000)
+    } else if let Some(n) = value.strip_suffix('m') {
+        (n, 60_000)
+    } else {
+        (value.as_str(), 1_000)
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit_ms))
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("invalid value for {}: {}", flag, value))
+}
+
+/// Parse a `NAME=NS` limit following `flag`
+fn parse_assertion(flag: &str, value: Option<String>) -> Result<(String, f64), String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value
+        .split_once('=')
+        .and_then(|(name, ns)| Some((name.to_string(), ns.parse().ok()?)))
+        .ok_or_else(|| format!("invalid value for {}: {} (expected NAME=NS)", flag, value))
+}
+
+/// Parse a `KEY=VALUE` tag following `flag` (checked by `build()`)
+fn parse_tag(flag: &str, value: Option<String>) -> Result<(String, String), String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value
+        .split_once('=')
+        .map(|(key, tag)| (key.to_string(), tag.to_string()))
+        .ok_or_else(|| format!("invalid value for {}: {} (expected KEY=VALUE)", flag, value))
+}
+
+/// Parse the value following `flag`
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    /// `parse_args_from_sources` over `vars` and, as the one config file
+    /// there is, `config`
+    fn parse_sources(line: &str, vars: &[(&str, &str)], config: &str) -> Result<RunConfig, String> {
+        let lookup = |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string());
+        parse_args_from_sources(args(line), lookup, |_| Ok(config.to_string()))
+    }
+
+    #[test]
+    fn command_line_beats_env_beats_file() {
+        let file = "# shared defaults\nloops = 10\nrt = true\nbench = instant\nbench = hrt\n";
+        let config = CONFIG_ENV;
+        let env = [(config, "bench.conf"), ("TIMING_EVAL_LOOPS", "20")];
+
+        assert_eq!(parse_sources("", &[], file).unwrap().loops, None);
+        assert_eq!(parse_sources("", &env[..1], file).unwrap().loops, Some(10));
+        assert_eq!(parse_sources("", &env, file).unwrap().loops, Some(20));
+        assert_eq!(parse_sources("--loops 30", &env, file).unwrap().loops, Some(30));
+        // --config works without the variable, and a variable beats it
+        assert_eq!(parse_sources("--config bench.conf", &env[1..], file).unwrap().loops, Some(20));
+
+        // a source that sets a flag hides what lower ones set for it
+        let from_file = parse_sources("", &env, file).unwrap();
+        assert!(from_file.realtime);
+        assert_eq!(from_file.benchmarks, ["instant", "hrt"]);
+        let vars = [env[0], ("TIMING_EVAL_RT", "0"), ("TIMING_EVAL_BENCH", "ticks, getpid")];
+        let overridden = parse_sources("", &vars, file).unwrap();
+        assert!(!overridden.realtime);
+        assert_eq!(overridden.benchmarks, ["ticks", "getpid"]);
+        assert_eq!(parse_sources("--bench hrt", &env, file).unwrap().benchmarks, ["hrt"]);
+    }
+
+    #[test]
+    fn bad_sources_are_named() {
+        let err = parse_sources("", &[("TIMING_EVAL_RUNS", "many")], "").unwrap_err();
+        assert!(err.ends_with("(from TIMING_EVAL_RUNS)") && err.contains("--runs"), "{}", err);
+        let err = parse_sources("", &[("TIMING_EVAL_TRACE", "maybe")], "").unwrap_err();
+        assert!(err.contains("TIMING_EVAL_TRACE"), "{}", err);
+
+        let err = parse_sources("--config x.conf", &[], "loops = 5\nwarmupp = 1").unwrap_err();
+        assert!(err.contains("x.conf") && err.contains("line 2") && err.contains("warmupp"), "{}", err);
+        let err = parse_sources("--config x.conf", &[], "loops = 0").unwrap_err();
+        assert!(err.contains("from config x.conf"), "{}", err);
+        let err = parse_args_from_sources(args("--config gone.conf"), |_| None, |_| {
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+        })
+        .unwrap_err();
+        assert!(err.starts_with("cannot read config gone.conf"), "{}", err);
+    }
+
+    #[test]
+    fn every_flag_has_a_variable_and_a_file_key() {
+        assert_eq!(env_var_name("batch-size"), "TIMING_EVAL_BATCH_SIZE");
+        for &(flag, arity) in FLAGS {
+            // parse_args knows it: at worst it wants a value
+            let err = parse_args([format!("--{}", flag)]).err().unwrap_or_default();
+            assert!(!err.starts_with("unknown argument"), "{}", err);
+            let value = if arity == Arity::Switch { "true" } else { "x y" };
+            assert_eq!(config_settings(&format!("{} = {}", flag.replace('-', "_"), value)).unwrap()[0].flag, flag);
+        }
+
+        let file = "--diff = old.json new.json\nhigh_timer_res = yes\nassert = hrt=50";
+        let config = parse_sources("--config c", &[], file).unwrap();
+        assert!(config.high_timer_res && config.diff.is_some());
+        assert_eq!(config.assertions, [("hrt".to_string(), 50.0)]);
+    }
+
+    #[test]
+    fn every_parsed_flag_is_in_flags() {
+        // the reverse of the check above: read the flags off the arms of
+        // parse_args' match, so a new flag can't miss its variable
+        let source = include_str!("cli.rs");
+        let body = &source[source.find("pub fn parse_args(").unwrap()..];
+        let body = &body[..body.find("\n}\n").unwrap()];
+        let parsed: Vec<&str> = body
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("\"--")?.split_once("\" =>").map(|(flag, _)| flag))
+            .collect();
+        assert!(parsed.len() > 30, "{:?}", parsed);
+        for flag in parsed {
+            assert!(FLAGS.iter().any(|(known, _)| *known == flag), "--{} is missing from FLAGS", flag);
+        }
+    }
+}
@@ -1,10 +1,223 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::high_resolution_timer::HighResolutionTimer;
 
 pub fn current_timestamp() -> u64 {
     //time::Instant::now().elapsed().as_nanos() as u64
-    let now_nanos = SystemTime::now()
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("fail")
-        .as_nanos() as u64;
-    now_nanos
+        .as_nanos() as u64
+}
+
+/// Monotonic nanoseconds since the first call in this process.
+///
+/// Unlike `current_timestamp()` it never steps backwards (NTP, manual
+/// clock changes), so it is safe for bracketing measurements.
+pub fn monotonic_timestamp() -> u64 {
+    static ANCHOR: OnceLock<Instant> = OnceLock::new();
+    ANCHOR.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Changes of `SystemTime::now()` that `system_time_resolution_ns`
+/// measures the step of
+const RESOLUTION_STEPS: usize = 8;
+
+/// Smallest observable step of `SystemTime::now()`, in ns: ~1 ns via the
+/// Linux vDSO, 100 ns on Windows with a precise clock, ~15.6 ms where it
+/// follows the default timer tick.
+///
+/// Spins until the value changes `RESOLUTION_STEPS` times (or for at
+/// most a second) and keeps the shortest forward step. Never below 1,
+/// nor (with a fine clock) below the cost of one read.
+pub fn system_time_resolution_ns() -> u64 {
+    clock_step_ns(current_timestamp)
+}
+
+/// Smallest observable step of any ns clock `read`, measured the way
+/// `system_time_resolution_ns` measures SystemTime
+pub fn clock_step_ns(mut read: impl FnMut() -> u64) -> u64 {
+    let give_up = Instant::now() + Duration::from_secs(1);
+    let mut min_step = u64::MAX;
+    let mut prev = read();
+    for _ in 0..RESOLUTION_STEPS {
+        let now = loop {
+            let now = read();
+            if now != prev || Instant::now() >= give_up {
+                break now;
+            }
+        };
+        // a backwards step is a clock adjustment, not the granularity
+        if now > prev {
+            min_step = min_step.min(now - prev);
+        }
+        prev = now;
+        if Instant::now() >= give_up {
+            break;
+        }
+    }
+    if min_step == u64::MAX { 1 } else { min_step.max(1) }
+}
+
+/// Wall-clock epoch nanoseconds at HRT resolution, without a
+/// `SystemTime` read per call.
+///
+/// The first call captures `current_timestamp()` next to a started
+/// `HighResolutionTimer`; every call returns that anchor plus the
+/// timer's elapsed ns. The result drifts from the real wall clock over
+/// long periods (calibration error, NTP slewing), so re-anchor by
+/// restarting the process when that matters.
+pub fn now_anchored_ns() -> u64 {
+    let (wall_ns, timer) = anchor();
+    wall_ns + timer.ns() as u64
+}
+
+/// Wall-clock epoch ns of the anchor behind `now_anchored_ns`, captured
+/// on the first call to either.
+///
+/// Each process anchors its own timer, so `now_anchored_ns() -
+/// timer_epoch_ns()` is a per-process HRT offset; adding this process's
+/// epoch back puts offsets from several processes on one wall-clock
+/// scale. Alignment is only as good as `current_timestamp()` (its
+/// resolution, see `system_time_resolution_ns`) at the two anchors.
+pub fn timer_epoch_ns() -> u64 {
+    anchor().0
+}
+
+/// Monotonic nanoseconds at HRT resolution since the first call to
+/// this, `now_anchored_ns` or `timer_epoch_ns`: the simplest way to
+/// read the timer, re-exported as `timing_test::now_ns`.
+///
+/// Calibration happens once, on the first call; later calls are one
+/// tick read, a conversion and an atomic max. A `recalibrate()`, tick
+/// frequency override or `select_backend` afterwards can move the
+/// reading back; `now_ns` then holds at the highest value it returned
+/// until the timer passes it, so it never goes backwards.
+pub fn now_ns() -> u64 {
+    static LATEST: AtomicU64 = AtomicU64::new(0);
+    let ns = anchor().1.ns() as u64;
+    LATEST.fetch_max(ns, Ordering::Relaxed).max(ns)
+}
+
+fn anchor() -> &'static (u64, HighResolutionTimer) {
+    static ANCHOR: OnceLock<(u64, HighResolutionTimer)> = OnceLock::new();
+    ANCHOR.get_or_init(|| {
+        let timer = HighResolutionTimer::start();
+        (current_timestamp(), timer)
+    })
+}
+
+/// Inverse of `current_timestamp()`: epoch nanoseconds back to a
+/// `SystemTime`.
+///
+/// Every u64 (up to year 2554) is representable wherever SystemTime has
+/// a 64-bit seconds field, i.e. all supported targets.
+pub fn timestamp_to_system_time(timestamp_ns: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(timestamp_ns)
+}
+
+/// Format epoch nanoseconds as an ISO-8601 UTC timestamp,
+/// e.g. `2024-03-01T12:34:56Z`
+pub fn iso8601_utc(timestamp_ns: u64) -> String {
+    let secs = timestamp_ns / 1_000_000_000;
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // civil-from-days (Howard Hinnant), shifted so the era starts in March
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_round_trips_through_system_time() {
+        for timestamp in [current_timestamp(), 0, 1_709_296_496_123_456_789, u64::MAX] {
+            let back = timestamp_to_system_time(timestamp).duration_since(UNIX_EPOCH).unwrap();
+            assert_eq!(back.as_nanos(), timestamp as u128);
+        }
+    }
+
+    #[test]
+    fn anchored_reads_advance_with_the_timer() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let first = now_anchored_ns();
+        let timer = HighResolutionTimer::start();
+        std::thread::sleep(Duration::from_millis(2));
+        let inner = timer.ns() as u64;
+        let second = now_anchored_ns();
+        assert!(second - first >= inner, "{} < {}", second - first, inner);
+        assert!(second - first - inner < 1_000_000, "{} vs {}", second - first, inner);
+        assert!(inner >= 2_000_000);
+    }
+
+    #[test]
+    fn system_time_resolution_is_positive() {
+        let resolution = system_time_resolution_ns();
+        // Windows' coarsest default tick is 15.6 ms
+        assert!(resolution > 0 && resolution <= 20_000_000, "{} ns", resolution);
+    }
+
+    #[test]
+    fn epochs_of_close_anchors_agree() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let epoch = timer_epoch_ns();
+        assert_eq!(timer_epoch_ns(), epoch);
+        // a second anchor taken now, as another process would, puts the
+        // first one's start within a few ms of its recorded epoch
+        let (wall, offset) = (current_timestamp(), now_anchored_ns() - epoch);
+        let other_epoch = wall - offset;
+        assert!(other_epoch.abs_diff(epoch) < 5_000_000, "{} vs {}", other_epoch, epoch);
+        assert!(epoch <= wall);
+    }
+
+    #[test]
+    fn now_ns_is_monotonic_and_advances() {
+        let reads: Vec<u64> = (0..1_000).map(|_| now_ns()).collect();
+        assert!(reads.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let before = now_ns();
+        let mut spins = 0u64;
+        while now_ns() - before < 100_000 {
+            spins += 1;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        let after = now_ns();
+        assert!(spins > 0 && after - before >= 1_100_000, "{} ns", after - before);
+    }
+
+    #[test]
+    fn now_ns_holds_across_a_recalibration() {
+        use crate::high_resolution_timer::{recalibrate, set_tick_hz_override, tick_hz};
+        // the lock keeps the frequency changes away from other tests
+        let _lock = crate::high_resolution_timer::test_lock();
+        let before = now_ns();
+        // four times the frequency: the raw reading drops to a quarter
+        set_tick_hz_override(Some(tick_hz() * 4));
+        let overridden = now_ns();
+        set_tick_hz_override(None);
+        recalibrate().unwrap();
+        let recalibrated = now_ns();
+        assert!(before <= overridden && overridden <= recalibrated, "{} {} {}", before, overridden, recalibrated);
+    }
 }
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use crate::json::parse_json;
+use crate::report::render_table;
+
+// ============================================================
+// Offline before/after comparison (--diff)
+// ============================================================
+
+/// Changes within this many percent either way are left uncolored
+pub const DIFF_NOISE_PERCENT: f64 = 2.0;
+
+/// (name, ns_per_call) of each result in a `--format json` file
+pub fn read_results_json(path: &Path) -> Result<Vec<(String, f64)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let document = parse_json(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let results = document
+        .as_array()
+        .ok_or_else(|| format!("{}: expected a JSON array of results", path.display()))?;
+    results
+        .iter()
+        .map(|result| {
+            let name = result.get("name").and_then(|v| v.as_str());
+            let ns = result.get("ns_per_call").and_then(|v| v.as_f64());
+            match (name, ns) {
+                (Some(name), Some(ns)) => Ok((name.to_string(), ns)),
+                _ => Err(format!("{}: result without name or ns_per_call", path.display())),
+            }
+        })
+        .collect()
+}
+
+/// One benchmark present in both files
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffRow {
+    pub name: String,
+    pub old_ns: f64,
+    pub new_ns: f64,
+}
+
+impl DiffRow {
+    /// New minus old, ns/call
+    pub fn delta_ns(&self) -> f64 {
+        self.new_ns - self.old_ns
+    }
+
+    /// Change relative to old, percent (positive is slower); 0 when the
+    /// old cost was 0
+    pub fn percent(&self) -> f64 {
+        if self.old_ns > 0.0 { self.delta_ns() / self.old_ns * 100.0 } else { 0.0 }
+    }
+}
+
+/// Rows for the benchmarks in both `old` and `new`, largest regression
+/// first, plus the names found in only one of them (old-only, new-only)
+pub fn diff_results(old: &[(String, f64)], new: &[(String, f64)]) -> (Vec<DiffRow>, Vec<String>, Vec<String>) {
+    let mut rows: Vec<DiffRow> = old
+        .iter()
+        .filter_map(|(name, old_ns)| {
+            let (_, new_ns) = new.iter().find(|(n, _)| n == name)?;
+            Some(DiffRow { name: name.clone(), old_ns: *old_ns, new_ns: *new_ns })
+        })
+        .collect();
+    rows.sort_by(|a, b| b.percent().total_cmp(&a.percent()));
+
+    let only_old = old.iter().filter(|(n, _)| !new.iter().any(|(m, _)| m == n)).map(|(n, _)| n.clone()).collect();
+    let only_new = new.iter().filter(|(n, _)| !old.iter().any(|(m, _)| m == n)).map(|(n, _)| n.clone()).collect();
+    (rows, only_old, only_new)
+}
+
+/// The diff as a table (benchmark, old, new, delta, percent), signed;
+/// with `color`, regressions past `DIFF_NOISE_PERCENT` are red and
+/// improvements green
+pub fn render_diff(rows: &[DiffRow], precision: usize, color: bool) -> String {
+    let p = precision;
+    let headers = ["Benchmark", "old ns", "new ns", "delta", "change"];
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.name.clone(),
+                format!("{:.p$}", row.old_ns),
+                format!("{:.p$}", row.new_ns),
+                format!("{:+.p$}", row.delta_ns()),
+                format!("{:+.1}%", row.percent()),
+            ]
+        })
+        .collect();
+
+    let table = render_table(&headers, &cells);
+    if !color {
+        return table;
+    }
+    let mut lines = table.lines();
+    let mut out = format!("{}\n", lines.next().unwrap_or(""));
+    for (line, row) in lines.zip(rows) {
+        match row.percent() {
+            pct if pct > DIFF_NOISE_PERCENT => out.push_str(&format!("\x1b[31m{}\x1b[0m\n", line)),
+            pct if pct < -DIFF_NOISE_PERCENT => out.push_str(&format!("\x1b[32m{}\x1b[0m\n", line)),
+            _ => out.push_str(&format!("{}\n", line)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_row_per_matched_benchmark() {
+        let named = |pairs: &[(&str, f64)]| pairs.iter().map(|&(n, ns)| (n.to_string(), ns)).collect::<Vec<_>>();
+        let old = named(&[("instant", 20.0), ("ticks", 10.0), ("gone", 5.0)]);
+        let new = named(&[("ticks", 8.0), ("instant", 25.0), ("added", 1.0)]);
+
+        let (rows, only_old, only_new) = diff_results(&old, &new);
+        // sorted by change, worst regression first
+        let changes: Vec<(&str, f64)> = rows.iter().map(|r| (r.name.as_str(), r.percent())).collect();
+        assert_eq!(changes, [("instant", 25.0), ("ticks", -20.0)]);
+        assert_eq!(rows[1].delta_ns(), -2.0);
+        assert_eq!(only_old, ["gone"]);
+        assert_eq!(only_new, ["added"]);
+
+        let table = render_diff(&rows, 1, false);
+        assert!(table.contains("+25.0%") && table.contains("-20.0%"), "{}", table);
+    }
+
+    #[test]
+    fn reads_back_what_render_json_writes() {
+        use crate::benchmark::BenchmarkResult;
+        use crate::report::render_json;
+        use crate::runner::RunConfig;
+
+        let mut quoted = BenchmarkResult::from_samples("quoted", &[30, 31, 33]);
+        quoted.name = "say \"hi\" \\ é \u{1}".to_string();
+        let results = [BenchmarkResult::from_samples("ticks", &[9, 10, 12]), quoted];
+        let path = std::env::temp_dir().join(format!("timing-eval-test-{}-diff.json", std::process::id()));
+        std::fs::write(&path, render_json(&results, &RunConfig::default())).unwrap();
+        let read = read_results_json(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let expected: Vec<(String, f64)> = results.iter().map(|r| (r.name.clone(), r.ns_per_call())).collect();
+        let read = read.unwrap();
+        assert_eq!(read.len(), expected.len());
+        for ((name, ns), (expected_name, expected_ns)) in read.iter().zip(&expected) {
+            assert_eq!(name, expected_name);
+            // written at --precision 3
+            assert!((ns - expected_ns).abs() < 1e-3, "{} vs {}", ns, expected_ns);
+        }
+    }
+}
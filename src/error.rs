@@ -0,0 +1,139 @@
+use std::fmt;
+
+// ============================================================
+// Errors and exit codes
+// ============================================================
+// 0 success, 2 bad arguments, 3 calibration failure,
+// 4 regression against a baseline, 5 end before start.
+
+/// Failure that ends a run; each variant has a fixed exit code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimingError {
+    /// Command line could not be parsed
+    BadArguments(String),
+    /// No usable tick frequency
+    Calibration(String),
+    /// A result got slower than its baseline allows
+    Regression(String),
+    /// The bracketing clock read an end before the start
+    EndBeforeStart { start_ns: u64, end_ns: u64 },
+}
+
+impl TimingError {
+    /// Process exit code for this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            TimingError::BadArguments(_) => 2,
+            TimingError::Calibration(_) => 3,
+            TimingError::Regression(_) => 4,
+            TimingError::EndBeforeStart { .. } => 5,
+        }
+    }
+}
+
+impl fmt::Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimingError::BadArguments(message) => write!(f, "{}", message),
+            TimingError::Calibration(message) => write!(f, "calibration failed: {}", message),
+            TimingError::Regression(message) => write!(f, "regression: {}", message),
+            TimingError::EndBeforeStart { start_ns, end_ns } => write!(
+                f,
+                "end time must be after start time (start {} ns, end {} ns)",
+                start_ns, end_ns
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimingError {}
+
+/// Why `high_resolution_timer::try_calibrate` found no trustworthy
+/// tick frequency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalibrationError {
+    /// No monotonic clock to calibrate against (only the wall clock)
+    ReferenceClockUnavailable,
+    /// The counter did not advance, or reported 0 Hz
+    ZeroDelta,
+    /// Outside the plausible band (see `plausible_tick_hz`)
+    OutOfRange(u64),
+    /// Segments of the window disagree by this many ppm, above
+    /// `MAX_CALIBRATION_ERROR_PPM` (clock stepped mid-window)
+    Unstable(u64),
+    /// A segment took this many ns, over `PREEMPTED_SEGMENT_RATIO`
+    /// times its target: the thread was descheduled mid-window
+    Preempted(u64),
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibrationError::ReferenceClockUnavailable => write!(f, "no monotonic reference clock"),
+            CalibrationError::ZeroDelta => write!(f, "counter did not advance"),
+            CalibrationError::OutOfRange(hz) => write!(f, "implausible tick frequency {} Hz", hz),
+            CalibrationError::Unstable(ppm) => {
+                write!(f, "calibration segments disagree by {} ppm (clock stepped?)", ppm)
+            }
+            CalibrationError::Preempted(ns) => {
+                write!(f, "a calibration segment took {} us (thread descheduled?)", ns / 1_000)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {}
+
+impl From<CalibrationError> for TimingError {
+    fn from(err: CalibrationError) -> Self {
+        TimingError::Calibration(err.to_string())
+    }
+}
+
+/// A benchmark's median per-call cost exceeded its limit (see
+/// `benchmark::run_and_assert`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionFailure {
+    pub name: String,
+    /// Measured median, ns/call
+    pub median_ns: f64,
+    /// Allowed median, ns/call
+    pub max_ns: f64,
+}
+
+impl fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.median_ns.is_nan() {
+            return write!(f, "{}: no samples to check against the {:.3} ns limit", self.name, self.max_ns);
+        }
+        write!(
+            f,
+            "{}: median {:.3} ns/call exceeds the {:.3} ns limit",
+            self.name, self.median_ns, self.max_ns
+        )
+    }
+}
+
+impl std::error::Error for AssertionFailure {}
+
+impl From<AssertionFailure> for TimingError {
+    fn from(failure: AssertionFailure) -> Self {
+        TimingError::Regression(failure.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_exit_codes() {
+        assert_eq!(TimingError::BadArguments("--loops".into()).exit_code(), 2);
+        assert_eq!(TimingError::Calibration("no clock".into()).exit_code(), 3);
+        assert_eq!(TimingError::Regression("slower".into()).exit_code(), 4);
+        assert_eq!(TimingError::EndBeforeStart { start_ns: 2, end_ns: 1 }.exit_code(), 5);
+        // converted errors keep the code of what they mean
+        let failure = AssertionFailure { name: "ticks".into(), median_ns: 9.0, max_ns: 5.0 };
+        assert_eq!(TimingError::from(failure).exit_code(), 4);
+    }
+}
@@ -1,5 +1,20 @@
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-use core::arch::x86_64::{_rdtsc, _mm_lfence};
+// 32-bit x86 has the same intrinsics under core::arch::x86
+#[cfg(target_arch = "x86")]
+use core::arch::x86 as arch;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64 as arch;
+
+#[cfg(all(not(target_env = "sgx"), any(target_arch = "x86", target_arch = "x86_64")))]
+use arch::__cpuid_count;
+
+#[cfg(all(not(windows), any(target_arch = "x86", target_arch = "x86_64")))]
+use arch::{_rdtsc, _mm_lfence};
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+use arch::__rdtscp;
 
 #[cfg(windows)]
 use windows_sys::Win32::System::Performance::{
@@ -7,29 +22,348 @@ use windows_sys::Win32::System::Performance::{
     QueryPerformanceFrequency,
 };
 
+use std::cell::Cell;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(all(not(windows), any(target_arch = "x86", target_arch = "x86_64")))]
 use std::sync::OnceLock;
+use std::sync::{Mutex, MutexGuard, Once};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::CalibrationError;
+use crate::trace::{trace_event, TraceKind};
 
 /// ------------------------------------------------------------
 /// High-Resolution Timer (Cross-Platform)
 /// ------------------------------------------------------------
-/// • Windows: QueryPerformanceCounter
+/// • Windows (x86_64 and ARM64): QueryPerformanceCounter
 /// • x86_64 (Linux/macOS): rdtsc + startup calibration
-/// • ARM64 (Linux/macOS): cntvct_el0 + cntfrq_el0
+/// • ARM64 (Linux/macOS): cntvct_el0 + cntfrq_el0 (the Mach timebase on macOS)
+///
+/// Every backend cfg excludes `windows` explicitly, so Windows-on-ARM
+/// never reaches the cntvct path.
 /// ------------------------------------------------------------
 #[derive(Debug)]
 pub struct HighResolutionTimer {
-    start_cycles: u64
+    start_cycles: u64,
+    backend: TimerBackend,
+    /// Calibration generation in effect at start
+    generation: u64,
+    /// Ticks since start, widened past counter wraps
+    accumulator: TickAccumulator,
+}
+
+/// Counter a timer reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerBackend {
+    /// Windows QueryPerformanceCounter
+    Qpc,
+    /// x86 rdtsc
+    Tsc,
+    /// ARM64 cntvct_el0
+    Cntvct,
+    /// OS monotonic clock (clock_gettime); never chosen by `backend()`,
+    /// only by `select_backend`
+    Monotonic,
+    /// No hardware counter on this target
+    Fallback,
+}
+
+impl TimerBackend {
+    /// Short lowercase name, as printed by `--list-backends`
+    pub fn name(self) -> &'static str {
+        match self {
+            TimerBackend::Qpc => "qpc",
+            TimerBackend::Tsc => "tsc",
+            TimerBackend::Cntvct => "cntvct",
+            TimerBackend::Monotonic => "monotonic",
+            TimerBackend::Fallback => "fallback",
+        }
+    }
+
+    /// What the backend reads
+    pub fn description(self) -> &'static str {
+        match self {
+            TimerBackend::Qpc => "Windows QueryPerformanceCounter",
+            TimerBackend::Tsc => "x86 rdtsc",
+            TimerBackend::Cntvct => "ARM64 cntvct_el0",
+            TimerBackend::Monotonic => "clock_gettime(CLOCK_MONOTONIC)",
+            TimerBackend::Fallback => "no hardware counter",
+        }
+    }
+}
+
+impl std::str::FromStr for TimerBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qpc" => Ok(TimerBackend::Qpc),
+            "tsc" => Ok(TimerBackend::Tsc),
+            "cntvct" => Ok(TimerBackend::Cntvct),
+            "monotonic" => Ok(TimerBackend::Monotonic),
+            "fallback" => Ok(TimerBackend::Fallback),
+            other => Err(format!("unknown backend: {}", other)),
+        }
+    }
+}
+
+/// Backend compiled in for this target
+pub const fn backend() -> TimerBackend {
+    if cfg!(windows) {
+        TimerBackend::Qpc
+    } else if cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
+        TimerBackend::Tsc
+    } else if cfg!(target_arch = "aarch64") {
+        TimerBackend::Cntvct
+    } else {
+        TimerBackend::Fallback
+    }
+}
+
+/// Backends compiled into this build and usable here, the chosen
+/// `backend()` first; Monotonic only where clock_gettime works (a
+/// seccomp filter may reject it)
+pub fn available_backends() -> Vec<TimerBackend> {
+    #[cfg(unix)]
+    if monotonic_available() {
+        return vec![backend(), TimerBackend::Monotonic];
+    }
+    vec![backend()]
+}
+
+// Windows always reads QPC, whatever the arch
+#[cfg(windows)]
+const _: () = assert!(matches!(backend(), TimerBackend::Qpc));
+
+// on i686 rdtsc still returns the full 64-bit counter (EDX:EAX)
+#[cfg(all(not(windows), target_arch = "x86"))]
+const _: unsafe fn() -> u64 = _rdtsc;
+
+/// Set by `select_backend`: timers read CLOCK_MONOTONIC instead of
+/// the `backend()` counter
+#[cfg(unix)]
+static MONOTONIC_SELECTED: AtomicBool = AtomicBool::new(false);
+
+/// Backend that timers started now read
+pub fn active_backend() -> TimerBackend {
+    #[cfg(unix)]
+    if MONOTONIC_SELECTED.load(Ordering::Relaxed) {
+        return TimerBackend::Monotonic;
+    }
+    backend()
+}
+
+/// Make every timer started from now on read `selected`, or an error
+/// if it is not in `available_backends()`.
+///
+/// The switch changes the tick frequency (Monotonic ticks are ns), so
+/// like `recalibrate()` it makes earlier timers incomparable with
+/// later ones.
+pub fn select_backend(selected: TimerBackend) -> Result<(), String> {
+    let available = available_backends();
+    if !available.contains(&selected) {
+        let names: Vec<&str> = available.iter().map(|b| b.name()).collect();
+        return Err(format!(
+            "backend {} is not available here (available: {})",
+            selected.name(),
+            names.join(", ")
+        ));
+    }
+    if selected == active_backend() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    MONOTONIC_SELECTED.store(selected == TimerBackend::Monotonic, Ordering::Relaxed);
+    // the old frequency is in the wrong unit, so never keep it
+    replace_tick_hz(calibrate_active_backend());
+    Ok(())
 }
 
 // ==========================
 // Global tick frequency (Hz)
 // ==========================
 
-static TICK_HZ: OnceLock<u64> = OnceLock::new();
+/// 0 until the first calibration
+static TICK_HZ: AtomicU64 = AtomicU64::new(0);
+/// `ns_reciprocal(TICK_HZ)`, stored before TICK_HZ so it is always
+/// visible once the frequency is
+static TICK_RECIP: AtomicU64 = AtomicU64::new(0);
+static FIRST_CALIBRATION: Once = Once::new();
+/// Bumped by every (re)calibration
+static CALIBRATION_GENERATION: AtomicU64 = AtomicU64::new(0);
 
 #[inline(always)]
 fn global_tick_hz() -> u64 {
-    *TICK_HZ.get_or_init(|| calibrate_tick_hz())
+    let hz = TICK_HZ.load(Ordering::Acquire);
+    if hz != 0 {
+        return hz;
+    }
+    FIRST_CALIBRATION.call_once(|| {
+        store_tick_hz(initial_tick_hz());
+    });
+    TICK_HZ.load(Ordering::Acquire)
+}
+
+fn store_tick_hz(hz: u64) {
+    TICK_RECIP.store(ns_reciprocal(hz), Ordering::Relaxed);
+    TICK_HZ.store(hz, Ordering::Release);
+    CALIBRATION_GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+thread_local! {
+    /// (generation, tick_hz, reciprocal) this thread last read; the
+    /// u64::MAX generation forces the first read through the globals
+    static LOCAL_TICK_HZ: Cell<(u64, u64, u64)> = const { Cell::new((u64::MAX, 0, 0)) };
+}
+
+#[cfg(all(test, unix))]
+thread_local! {
+    /// Reads of the monotonic backend on this thread: tests select it to
+    /// count how often a benchmark reads the counter
+    pub(crate) static MONOTONIC_READS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// `(tick_hz, ns_reciprocal)` from this thread's cache, refreshed from
+/// the globals whenever `CALIBRATION_GENERATION` has moved on.
+///
+/// The hot path is one generation load plus a thread-local read,
+/// instead of the frequency and reciprocal loads. Every tick-to-ns (or
+/// ns-to-tick) conversion takes its frequency from here, so they all
+/// switch to a new calibration together.
+#[inline(always)]
+fn cached_tick_hz() -> (u64, u64) {
+    // read before the values it tags: a recalibration racing the
+    // refresh leaves an old tag, so the next call refreshes again
+    let generation = CALIBRATION_GENERATION.load(Ordering::Acquire);
+    LOCAL_TICK_HZ
+        .try_with(|local| {
+            let (seen, hz, recip) = local.get();
+            if seen == generation {
+                return (hz, recip);
+            }
+            let hz = global_tick_hz();
+            let recip = TICK_RECIP.load(Ordering::Relaxed);
+            local.set((generation, hz, recip));
+            (hz, recip)
+        })
+        // thread-locals already destroyed (thread exit)
+        .unwrap_or_else(|_| (global_tick_hz(), TICK_RECIP.load(Ordering::Relaxed)))
+}
+
+/// Calibrated tick frequency (Hz), calibrating on first use
+pub fn tick_hz() -> u64 {
+    cached_tick_hz().0
+}
+
+/// Re-run calibration and replace the global frequency, or keep the
+/// current one if the new calibration fails `check_calibration` (e.g.
+/// outside `plausible_tick_hz()`), returning why. Under a
+/// `set_tick_hz_override` nothing is measured and the override stays.
+///
+/// Before any frequency exists a rejected calibration still falls back
+/// as on first use, and the error is returned all the same.
+///
+/// Timers started before a replacement are no longer comparable with
+/// ones started after (see `is_comparable_with`).
+pub fn recalibrate() -> Result<u64, CalibrationError> {
+    if let Some(hz) = tick_hz_override() {
+        return Ok(hz);
+    }
+    if active_backend() == TimerBackend::Monotonic {
+        return Ok(replace_tick_hz(calibrate_active_backend()));
+    }
+    match calibrate_with_retries(calibration_window()) {
+        Ok(calibration) => Ok(replace_tick_hz(record_calibration(calibration))),
+        Err(err) => {
+            trace_event(TraceKind::CalibrationRejected, || format!("recalibration kept the old frequency: {}", err));
+            if TICK_HZ.load(Ordering::Acquire) == 0 {
+                replace_tick_hz(record_calibration(fallback_calibration(&err.to_string())));
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Frequency of the active backend, never failing (see
+/// `calibrate_tick_hz_with_window`)
+fn calibrate_active_backend() -> u64 {
+    if active_backend() == TimerBackend::Monotonic {
+        1_000_000_000
+    } else {
+        calibrate_tick_hz_with_window(calibration_window())
+    }
+}
+
+/// Install the calibrated `hz` as the global frequency, in place of
+/// first-use calibration if that hasn't run, and return the frequency
+/// now in effect.
+///
+/// Under a `set_tick_hz_override` (even one set while this calibration
+/// ran) the override stays, and `hz` is what clearing it restores.
+fn replace_tick_hz(hz: u64) -> u64 {
+    let mut overridden = overridden_tick_hz();
+    if let Some(pinned) = tick_hz_override() {
+        *overridden = hz;
+        return pinned;
+    }
+    install_tick_hz(hz);
+    hz
+}
+
+fn install_tick_hz(hz: u64) {
+    FIRST_CALIBRATION.call_once(|| {});
+    store_tick_hz(hz);
+    trace_event(TraceKind::Recalibrated, || format!("freq={} Hz", hz));
+}
+
+// --------------------------
+// Frequency override
+// --------------------------
+
+/// Frequency set by `set_tick_hz_override`, 0 for none
+static TICK_HZ_OVERRIDE: AtomicU64 = AtomicU64::new(0);
+/// Frequency in effect when the override was set, or calibrated since
+/// (0 if none yet), restored when it is cleared. Its lock also orders
+/// overrides against `replace_tick_hz`.
+static OVERRIDDEN_TICK_HZ: Mutex<u64> = Mutex::new(0);
+
+fn overridden_tick_hz() -> MutexGuard<'static, u64> {
+    OVERRIDDEN_TICK_HZ.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Use `hz` for every conversion instead of the calibrated frequency
+/// (Some), or go back to it (None): for known fixed-frequency hardware,
+/// tests, or reproducing another machine's numbers.
+///
+/// Setting skips first-use calibration; clearing restores the frequency
+/// from before the override, or the latest calibration made under it
+/// (calibrating now if there was neither). As
+/// with `recalibrate()`, timers started before either are no longer
+/// comparable with later ones. `Some(0)` counts as None.
+pub fn set_tick_hz_override(hz: Option<u64>) {
+    let mut overridden = overridden_tick_hz();
+    match hz.filter(|&hz| hz > 0) {
+        Some(hz) => {
+            if TICK_HZ_OVERRIDE.swap(hz, Ordering::AcqRel) == 0 {
+                *overridden = TICK_HZ.load(Ordering::Acquire);
+            }
+            install_tick_hz(hz);
+        }
+        None => {
+            if TICK_HZ_OVERRIDE.swap(0, Ordering::AcqRel) == 0 {
+                return;
+            }
+            install_tick_hz(if *overridden != 0 { *overridden } else { initial_tick_hz() });
+        }
+    }
+}
+
+/// The `set_tick_hz_override` frequency in effect, if any
+pub fn tick_hz_override() -> Option<u64> {
+    Some(TICK_HZ_OVERRIDE.load(Ordering::Acquire)).filter(|&hz| hz > 0)
 }
 
 impl HighResolutionTimer {
@@ -39,19 +373,55 @@ impl HighResolutionTimer {
     /// - tick_hz not belongs to instance
     /// - calibrate on start
     pub fn start() -> Self {
-        // calibrate on start when not done yet
-        let _ = global_tick_hz();
+        // calibrate on start when not done yet (and fill this thread's
+        // cache for the first conversion)
+        let _ = cached_tick_hz();
 
+        let generation = CALIBRATION_GENERATION.load(Ordering::Acquire);
         let start_cycles = Self::get_ticks();
 
         Self {
-            start_cycles
+            start_cycles,
+            backend: active_backend(),
+            generation,
+            accumulator: TickAccumulator::new(start_cycles, COUNTER_BITS),
         }
     }
 
-    /// Read hardware ticks
+    /// `start()` plus the backend this timer reads, from the same
+    /// lookup: an `active_backend()` call afterwards could see a
+    /// `select_backend` made in between
+    pub fn start_with_backend() -> (Self, TimerBackend) {
+        let timer = Self::start();
+        let backend = timer.backend;
+        (timer, backend)
+    }
+
+    /// Whether ticks from the two timers can be compared: same backend
+    /// and same calibration (no `recalibrate()` in between)
+    pub fn is_comparable_with(&self, other: &HighResolutionTimer) -> bool {
+        self.backend == other.backend && self.generation == other.generation
+    }
+
+    /// Current raw counter value (uncalibrated ticks)
+    #[inline(always)]
+    pub fn ticks() -> u64 {
+        Self::get_ticks()
+    }
+
+    /// Read ticks of the active backend
     #[inline(always)]
     fn get_ticks() -> u64 {
+        #[cfg(unix)]
+        if MONOTONIC_SELECTED.load(Ordering::Relaxed) {
+            return monotonic_ticks();
+        }
+        Self::hardware_ticks()
+    }
+
+    /// Read the `backend()` counter
+    #[inline(always)]
+    fn hardware_ticks() -> u64 {
         // --------------------------
         // Windows
         // --------------------------
@@ -59,7 +429,7 @@ impl HighResolutionTimer {
         unsafe {
             let mut v: i64 = 0;
             QueryPerformanceCounter(&mut v);
-            return v as u64;
+            v as u64
         }
 
         // --------------------------
@@ -73,19 +443,19 @@ impl HighResolutionTimer {
             _mm_lfence();
             let t = _rdtsc();
             _mm_lfence();
-            return t;
+            t
         }
 
         // --------------------------
         // ARM64 (Linux / macOS)
         // --------------------------
-        #[cfg(target_arch = "aarch64")]
+        #[cfg(all(not(windows), target_arch = "aarch64"))]
         {
             let val: u64;
             unsafe {
                 core::arch::asm!("mrs {}, cntvct_el0", out(reg) val);
             }
-            return val;
+            val
         }
 
         // --------------------------
@@ -102,12 +472,130 @@ impl HighResolutionTimer {
         }
     }
 
-    /// Return elapsed time in **nanoseconds** (integer)
+    /// Return elapsed time in **nanoseconds** (integer).
+    ///
+    /// Converts with the precomputed reciprocal (see
+    /// `ticks_to_ns_fast`), so it may read up to 1 ns below the exact
+    /// `ticks_to_ns` result.
     pub fn ns(&self) -> u128 {
         let end_ticks = Self::get_ticks();
-        let delta = end_ticks.wrapping_sub(self.start_cycles) as u128;
+        let delta = end_ticks.wrapping_sub(self.start_cycles);
+
+        let (hz, recip) = cached_tick_hz();
+        ticks_to_ns_fast(delta, hz, recip)
+    }
+
+    /// Stop the timer and return elapsed **nanoseconds**.
+    ///
+    /// Same reading as `ns()`, but consumes the timer so it can't be
+    /// reused by accident.
+    pub fn stop(self) -> u128 {
+        self.ns()
+    }
+
+    /// Elapsed **reference cycles**, not retired instructions: the raw
+    /// tick delta of a constant-rate counter (invariant TSC or cntvct).
+    ///
+    /// None when the timer's backend isn't cycle-based (QPC, the
+    /// monotonic clock) or the TSC is not invariant.
+    pub fn cycles(&self) -> Option<u64> {
+        // read first: the invariance check is a (possibly trapped) CPUID
+        self.cycles_at(Self::get_ticks())
+    }
+
+    /// `cycles` with the counter reading `now`
+    fn cycles_at(&self, now: u64) -> Option<u64> {
+        let ticks = now.wrapping_sub(self.start_cycles);
+        let cycle_based = match self.backend {
+            TimerBackend::Tsc => tsc_is_invariant(),
+            TimerBackend::Cntvct => true,
+            _ => false,
+        };
+        cycle_based.then_some(ticks)
+    }
+
+    /// Elapsed ticks with integer and fractional nanoseconds, all from
+    /// a single counter read
+    pub fn elapsed_detailed(&self) -> ElapsedTime {
+        let ticks = Self::get_ticks().wrapping_sub(self.start_cycles);
+        ElapsedTime::from_ticks(ticks, cached_tick_hz().0)
+    }
+
+    /// Nanoseconds between two timers' start points.
+    ///
+    /// Order-insensitive: returns the magnitude whether `earlier` was
+    /// really started first or not. Only meaningful when the timers are
+    /// `is_comparable_with` each other.
+    pub fn since(&self, earlier: &HighResolutionTimer) -> u128 {
+        // signed distance so a wrapped counter still measures correctly
+        let delta = self.start_cycles.wrapping_sub(earlier.start_cycles) as i64;
+        ticks_to_ns(delta.unsigned_abs() as u128, cached_tick_hz().0)
+    }
+
+    /// Read the tick counter and the wall clock together, to place timer
+    /// ticks on a wall-clock timeline (stitching into external traces).
+    ///
+    /// The two can't be read at once: the counter is read before and
+    /// after `SystemTime::now()` and `ticks` is the midpoint, so the
+    /// pairing is off by at most `skew_ns` (half a wall-clock read,
+    /// tens of ns through the Linux vDSO) plus the wall clock's own step.
+    pub fn capture() -> Capture {
+        let hz = global_tick_hz();
+        let before = Self::get_ticks();
+        let wall_ns = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        let half = Self::get_ticks().wrapping_sub(before) / 2;
+        Capture {
+            ticks: before.wrapping_add(half),
+            wall_ns,
+            skew_ns: ticks_to_ns(half as u128, hz) as u64,
+        }
+    }
+
+    // --------------------------
+    // Instant-compatible subset
+    // --------------------------
+    // Supported: now, elapsed, duration_since, checked_duration_since,
+    // saturating_duration_since, and From<HighResolutionTimer> for
+    // Instant. Not supported: Copy, Ord/Hash, and the Add/Sub<Duration>
+    // arithmetic (checked_add/checked_sub), since a timer is a start
+    // point on the tick counter rather than a point in time.
+
+    /// Same as `start()`, named like `Instant::now()`
+    pub fn now() -> Self {
+        Self::start()
+    }
+
+    /// Time since start, like `Instant::elapsed()`
+    pub fn elapsed(&self) -> Duration {
+        duration_from_ns(self.ns())
+    }
+
+    /// Time from `earlier` to `self`, or None if `earlier` started later
+    pub fn checked_duration_since(&self, earlier: &HighResolutionTimer) -> Option<Duration> {
+        let delta = self.start_cycles.wrapping_sub(earlier.start_cycles) as i64;
+        if delta < 0 {
+            return None;
+        }
+        Some(duration_from_ns(ticks_to_ns(delta as u128, cached_tick_hz().0)))
+    }
+
+    /// Time from `earlier` to `self`, zero if `earlier` started later
+    pub fn saturating_duration_since(&self, earlier: &HighResolutionTimer) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// Time from `earlier` to `self`; saturates at zero like
+    /// `Instant::duration_since` does since Rust 1.60
+    pub fn duration_since(&self, earlier: &HighResolutionTimer) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
 
-        (delta * 1_000_000_000u128) / global_tick_hz() as u128
+    /// Ticks since start as a 128-bit count that survives counter wraps.
+    ///
+    /// Each call folds in the ticks since the previous one, so call it
+    /// at least once per counter wrap period on long runs.
+    pub fn accumulated_ticks(&mut self) -> u128 {
+        self.accumulator.update(Self::get_ticks())
     }
 
     // pub fn us(&self) -> u64 {
@@ -119,115 +607,1903 @@ impl HighResolutionTimer {
     // }
 }
 
+/// CLOCK_MONOTONIC in ns, the tick of the Monotonic backend.
+///
+/// `select_backend` only offers the backend once a probe read worked;
+/// should a later read fail anyway, it repeats the last good value
+/// rather than returning a zeroed timespec or another clock's time.
+#[cfg(unix)]
+#[inline(always)]
+fn monotonic_ticks() -> u64 {
+    static LAST_GOOD: AtomicU64 = AtomicU64::new(0);
+    #[cfg(test)]
+    MONOTONIC_READS.with(|reads| reads.set(reads.get() + 1));
+    monotonic_or_last(read_clock_monotonic(), &LAST_GOOD)
+}
+
+/// `read` if the clock read worked, recorded in `last_good`; else the
+/// last good read, so a failure times as a zero-length interval
+#[cfg(unix)]
+#[inline(always)]
+fn monotonic_or_last(read: Option<u64>, last_good: &AtomicU64) -> u64 {
+    match read {
+        Some(ns) => {
+            last_good.store(ns, Ordering::Relaxed);
+            ns
+        }
+        None => last_good.load(Ordering::Relaxed),
+    }
+}
+
+/// CLOCK_MONOTONIC in ns, or None if clock_gettime fails
+#[cfg(unix)]
+#[inline(always)]
+fn read_clock_monotonic() -> Option<u64> {
+    let mut ts: libc::timespec = unsafe { core::mem::zeroed() };
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) } != 0 {
+        return None;
+    }
+    Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+/// Whether CLOCK_MONOTONIC can be read here, probed once
+#[cfg(unix)]
+fn monotonic_available() -> bool {
+    static AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *AVAILABLE.get_or_init(|| read_clock_monotonic().is_some())
+}
+
+// --------------------------
+// Reciprocal fast path
+// --------------------------
+
+/// Fixed-point shift of `ns_reciprocal`. Results are within 1 ns of
+/// the exact division for deltas below 2^48 ticks (over a day at 3 GHz);
+/// longer deltas take the exact path.
+pub const RECIP_SHIFT: u32 = 48;
+
+/// `(1e9 << RECIP_SHIFT) / tick_hz`, or 0 when it would not fit in a
+/// u64 (tick_hz below ~15 kHz) or tick_hz is 0
+pub fn ns_reciprocal(tick_hz: u64) -> u64 {
+    if tick_hz == 0 {
+        return 0;
+    }
+    u64::try_from((1_000_000_000u128 << RECIP_SHIFT) / tick_hz as u128).unwrap_or(0)
+}
+
+/// Ticks to nanoseconds by multiply-and-shift with `recip` (from
+/// `ns_reciprocal(tick_hz)`) instead of a 128-bit division.
+///
+/// Never above the exact `ticks_to_ns` and at most 1 ns below it; falls
+/// back to the exact division when `recip` is 0 or `ticks` >= 2^48.
+#[inline(always)]
+pub fn ticks_to_ns_fast(ticks: u64, tick_hz: u64, recip: u64) -> u128 {
+    if recip == 0 || ticks >> RECIP_SHIFT != 0 {
+        return ticks_to_ns(ticks as u128, tick_hz);
+    }
+    (ticks as u128 * recip as u128) >> RECIP_SHIFT
+}
+
+/// Elapsed time from one counter read: `ns_int` is `ns_frac` truncated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElapsedTime {
+    pub ticks: u64,
+    pub ns_int: u128,
+    pub ns_frac: f64,
+}
+
+impl ElapsedTime {
+    /// Convert `ticks` at `tick_hz`
+    pub fn from_ticks(ticks: u64, tick_hz: u64) -> Self {
+        let ns_int = ticks_to_ns(ticks as u128, tick_hz);
+        // sub-ns part from the exact remainder, so the integer part of
+        // ns_frac always agrees with ns_int (up to f64 precision)
+        let fraction = match tick_hz as u128 {
+            0 => 0.0,
+            hz => (ticks as u128 * 1_000_000_000u128 % hz) as f64 / hz as f64,
+        };
+
+        Self {
+            ticks,
+            ns_int,
+            ns_frac: ns_int as f64 + fraction,
+        }
+    }
+}
+
+/// A tick counter value and the wall time it was read at (see
+/// `HighResolutionTimer::capture`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capture {
+    /// Raw counter value, as `HighResolutionTimer::ticks()`
+    pub ticks: u64,
+    /// Nanoseconds since the Unix epoch
+    pub wall_ns: u64,
+    /// Upper bound on how far apart the two reads were, ns
+    pub skew_ns: u64,
+}
+
 // ============================================================
-// Tick calibration
+// Wrap-aware tick accumulation
 // ============================================================
 
-fn calibrate_tick_hz() -> u64 {
-    // --------------------------
-    // Windows: QPC frequency
-    // --------------------------
-    #[cfg(windows)]
-    unsafe {
-        let mut freq: i64 = 0;
-        QueryPerformanceFrequency(&mut freq);
-        return freq as u64;
+/// Significant bits of the raw counter; every current backend
+/// exposes a full 64
+pub const COUNTER_BITS: u32 = 64;
+
+/// Widens a `bits`-wide counter to 128 bits by polling.
+///
+/// Each `update` adds the distance from the previous reading modulo
+/// 2^bits, so one wrap between polls is absorbed; more than one is lost.
+#[derive(Debug, Clone, Copy)]
+pub struct TickAccumulator {
+    last: u64,
+    total: u128,
+    mask: u64,
+}
+
+impl TickAccumulator {
+    /// Start accumulating from raw reading `initial` (`bits` in 1..=64)
+    pub fn new(initial: u64, bits: u32) -> Self {
+        let mask = if bits >= 64 { u64::MAX } else { (1u64 << bits.max(1)) - 1 };
+        Self {
+            last: initial & mask,
+            total: 0,
+            mask,
+        }
     }
 
-    // --------------------------
-    // x86 Linux / macOS
-    // --------------------------
-    #[cfg(all(
-        not(windows),
-        any(target_arch = "x86", target_arch = "x86_64")
-    ))]
-    {
-        return calibrate_tsc_with_monotonic();
+    /// Fold in a new raw reading and return the accumulated total
+    pub fn update(&mut self, raw: u64) -> u128 {
+        let raw = raw & self.mask;
+        self.total += (raw.wrapping_sub(self.last) & self.mask) as u128;
+        self.last = raw;
+        self.total
     }
 
-    // --------------------------
-    // ARM64
-    // --------------------------
-    #[cfg(target_arch = "aarch64")]
-    {
-        return read_cntfrq_el0();
+    /// Ticks accumulated so far
+    pub fn total(&self) -> u128 {
+        self.total
     }
-    // fallback
-    2_500_000_000
 }
 
-// --------------------------
-// x86 TSC calibration (integer)
-// --------------------------
+/// Convert a tick count at `tick_hz` into **nanoseconds**.
+///
+/// Any 64-bit tick count takes the single-division path. Larger counts
+/// split whole seconds from the remainder so the product can't overflow,
+/// and the result saturates at u128::MAX if it doesn't fit. A tick_hz of
+/// 0 yields 0.
+pub fn ticks_to_ns(ticks: u128, tick_hz: u64) -> u128 {
+    if tick_hz == 0 {
+        return 0;
+    }
+    let hz = tick_hz as u128;
+    if let Some(product) = ticks.checked_mul(1_000_000_000u128) {
+        return product / hz;
+    }
 
-#[cfg(all(
-    not(windows),
-    any(target_arch = "x86", target_arch = "x86_64")
-))]
-fn calibrate_tsc_with_monotonic() -> u64 {
-    use libc::{clock_gettime, timespec, CLOCK_MONOTONIC_RAW};
+    let secs = ticks / hz;
+    let rem = ticks % hz;
 
-    unsafe {
-        let mut ts_start = timespec { tv_sec: 0, tv_nsec: 0 };
-        let mut ts_end = timespec { tv_sec: 0, tv_nsec: 0 };
+    // rem < hz <= u64::MAX, so rem * 1e9 always fits
+    secs.saturating_mul(1_000_000_000u128)
+        .saturating_add(rem * 1_000_000_000u128 / hz)
+}
 
-        clock_gettime(CLOCK_MONOTONIC_RAW, &mut ts_start);
-        _mm_lfence();
-        let tsc_start = _rdtsc();
-        _mm_lfence();
+/// Nanoseconds expressed in counter ticks at the calibrated frequency.
+///
+/// On an invariant TSC this is core cycles at the base clock, for
+/// comparison with the documented rdtsc latency (~20-40 cycles).
+pub fn ns_to_cycles(ns: f64) -> f64 {
+    ns * cached_tick_hz().0 as f64 / 1e9
+}
 
-        spin_wait_ns(10_000_000); // ~10ms
+/// Length of one counter tick in ns: ~0.4 ns for a GHz TSC, ~41.7 ns
+/// for a 24 MHz Apple counter
+pub fn timer_resolution_ns() -> f64 {
+    1e9 / cached_tick_hz().0.max(1) as f64
+}
 
-        clock_gettime(CLOCK_MONOTONIC_RAW, &mut ts_end);
-        _mm_lfence();
-        let tsc_end = _rdtsc();
-        _mm_lfence();
+/// Whether a per-call cost is below one tick of the timer.
+///
+/// Batching lets averages go there, but such a reading mostly shows
+/// how the loop quantizes, not what the call costs.
+pub fn is_below_resolution(measured_ns: f64) -> bool {
+    below_resolution(measured_ns, timer_resolution_ns())
+}
+
+/// `is_below_resolution` against a given tick length
+pub fn below_resolution(measured_ns: f64, resolution_ns: f64) -> bool {
+    measured_ns < resolution_ns
+}
+
+// ============================================================
+// Expected clock-read cost
+// ============================================================
 
-        let delta_tsc = tsc_end - tsc_start;
-        let delta_ns =
-            (ts_end.tv_sec - ts_start.tv_sec) as u128 * 1_000_000_000u128 +
-            (ts_end.tv_nsec - ts_start.tv_nsec) as u128;
+/// How far outside its `typical_clock_read_ns` band a reading must be
+/// before the report calls it suspicious
+pub const SUSPICIOUS_BAND_FACTOR: f64 = 3.0;
 
-        (delta_tsc as u128 * 1_000_000_000u128 / delta_ns) as u64
+/// Usual (low, high) ns per read on current hardware, for telling a
+/// weird number from a normal one.
+///
+/// rdtsc/cntvct are a few ns, a vDSO clock_gettime tens of ns, and a
+/// clock that ends up in a real syscall (VM without a stable TSC,
+/// HPET) hundreds.
+pub fn typical_clock_read_ns(backend: TimerBackend) -> (f64, f64) {
+    match backend {
+        TimerBackend::Tsc => (3.0, 15.0),
+        TimerBackend::Cntvct => (1.0, 15.0),
+        TimerBackend::Qpc => (5.0, 40.0),
+        TimerBackend::Monotonic => (15.0, 40.0),
+        TimerBackend::Fallback => (15.0, 1000.0),
     }
 }
 
-#[cfg(all(
-    not(windows),
-    any(target_arch = "x86", target_arch = "x86_64")
-))]
-#[inline(always)]
-fn spin_wait_ns(ns: u64) {
-    use libc::{clock_gettime, timespec, CLOCK_MONOTONIC_RAW};
+/// Whether `measured_ns` per read is more than `SUSPICIOUS_BAND_FACTOR`
+/// outside the backend's typical band
+pub fn is_suspicious_clock_read(backend: TimerBackend, measured_ns: f64) -> bool {
+    let (low, high) = typical_clock_read_ns(backend);
+    measured_ns < low / SUSPICIOUS_BAND_FACTOR || measured_ns > high * SUSPICIOUS_BAND_FACTOR
+}
 
-    let start = unsafe {
-        let mut ts = timespec { tv_sec: 0, tv_nsec: 0 };
-        clock_gettime(CLOCK_MONOTONIC_RAW, &mut ts);
-        ts.tv_sec as u128 * 1_000_000_000u128 + ts.tv_nsec as u128
-    };
+/// Nanoseconds as a `Duration`, saturating beyond u64 (~584 years)
+fn duration_from_ns(ns: u128) -> Duration {
+    Duration::from_nanos(u64::try_from(ns).unwrap_or(u64::MAX))
+}
 
-    loop {
-        let now = unsafe {
-            let mut ts = timespec { tv_sec: 0, tv_nsec: 0 };
-            clock_gettime(CLOCK_MONOTONIC_RAW, &mut ts);
-            ts.tv_sec as u128 * 1_000_000_000u128 + ts.tv_nsec as u128
-        };
-        if now - start >= ns as u128 {
-            break;
-        }
+/// The `Instant` at which the timer started, reconstructed as now
+/// minus its elapsed time
+impl From<HighResolutionTimer> for Instant {
+    fn from(timer: HighResolutionTimer) -> Instant {
+        let now = Instant::now();
+        now.checked_sub(timer.elapsed()).unwrap_or(now)
     }
 }
 
-// --------------------------
-// ARM64 frequency
-// --------------------------
+/// Run `f` once and return its output with the elapsed time.
+///
+/// Two tick reads around the call and no allocation. Durations beyond
+/// u64 nanoseconds (~584 years) saturate.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let timer = HighResolutionTimer::start();
+    let output = f();
+    (output, timer.elapsed())
+}
 
-#[cfg(target_arch = "aarch64")]
-#[inline(always)]
-fn read_cntfrq_el0() -> u64 {
-    let freq: u64;
-    unsafe {
-        core::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq);
+/// Await `fut` and return its output with elapsed **nanoseconds**.
+///
+/// Executor-agnostic: the start tick is taken on first poll. The result
+/// is wall time until completion, so it includes time spent suspended at
+/// `.await` points, not only time spent polling.
+pub async fn time_future<F: Future>(fut: F) -> (F::Output, u64) {
+    let timer = HighResolutionTimer::start();
+    let output = fut.await;
+    (output, timer.ns() as u64)
+}
+
+// ============================================================
+// Tick calibration
+// ============================================================
+
+/// Default calibration window
+pub const DEFAULT_CALIBRATION_WINDOW: Duration = Duration::from_millis(10);
+
+/// Sub-windows the calibration window is split into; their spread gives
+/// the error estimate
+pub const CALIBRATION_SEGMENTS: usize = 5;
+
+static CALIBRATION_WINDOW_NS: AtomicU64 =
+    AtomicU64::new(DEFAULT_CALIBRATION_WINDOW.as_nanos() as u64);
+
+static LAST_CALIBRATION: Mutex<Option<Calibration>> = Mutex::new(None);
+
+/// Set the window used when the global frequency is calibrated.
+///
+/// Only takes effect if called before the first `tick_hz()`/`start()`.
+/// Longer windows reduce error on noisy VMs; shorter ones start faster.
+pub fn set_calibration_window(window: Duration) {
+    CALIBRATION_WINDOW_NS.store(window.as_nanos().max(1) as u64, Ordering::Relaxed);
+}
+
+/// Window the global frequency is (or will be) calibrated with
+pub fn calibration_window() -> Duration {
+    Duration::from_nanos(CALIBRATION_WINDOW_NS.load(Ordering::Relaxed))
+}
+
+/// One calibration segment: ticks counted against reference nanoseconds
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationSample {
+    pub delta_ticks: u64,
+    pub delta_ns: u64,
+}
+
+impl CalibrationSample {
+    pub fn hz(&self) -> f64 {
+        self.delta_ticks as f64 * 1e9 / self.delta_ns.max(1) as f64
+    }
+}
+
+/// Outcome of a calibration
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub tick_hz: u64,
+    /// Measured segments; empty when the frequency is reported by
+    /// hardware (QPC, cntfrq_el0) rather than measured
+    pub samples: Vec<CalibrationSample>,
+}
+
+impl Calibration {
+    /// Relative standard error of the frequency across segments
+    pub fn rse(&self) -> Option<f64> {
+        let n = self.samples.len();
+        if n < 2 {
+            return None;
+        }
+        let hz: Vec<f64> = self.samples.iter().map(CalibrationSample::hz).collect();
+        let mean = hz.iter().sum::<f64>() / n as f64;
+        let variance = hz.iter().map(|h| (h - mean) * (h - mean)).sum::<f64>() / (n - 1) as f64;
+        Some(variance.sqrt() / (n as f64).sqrt() / mean)
+    }
+
+    /// Estimated calibration error in parts per million
+    pub fn error_ppm(&self) -> Option<f64> {
+        self.rse().map(|rse| rse * 1e6)
+    }
+}
+
+/// Error estimate (ppm) of the most recent calibration, or None when
+/// the frequency came from hardware or nothing has calibrated yet
+pub fn calibration_error_ppm() -> Option<f64> {
+    let _ = global_tick_hz();
+    LAST_CALIBRATION.lock().ok()?.as_ref()?.error_ppm()
+}
+
+/// The calibration behind the global frequency, with its segments,
+/// calibrating on first use; None when the frequency came from the
+/// cache or a `--backend monotonic` switch never measured one
+pub fn last_calibration() -> Option<Calibration> {
+    let _ = global_tick_hz();
+    LAST_CALIBRATION.lock().ok()?.clone()
+}
+
+// --------------------------
+// Calibration cache (opt-in)
+// --------------------------
+
+static CALIBRATION_CACHE: Mutex<Option<PathBuf>> = Mutex::new(None);
+static CALIBRATION_FROM_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Default cache location, in the per-user cache directory:
+/// `$XDG_CACHE_HOME` or `~/.cache` on Unix, `~/Library/Caches` on
+/// macOS, `%LOCALAPPDATA%` on Windows. None when none is set.
+pub fn default_calibration_cache_path() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        var("LOCALAPPDATA")?
+    } else if cfg!(target_os = "macos") {
+        var("HOME")?.join("Library").join("Caches")
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|home| home.join(".cache")))?
+    };
+    Some(dir.join("timing-eval").join("tick-hz"))
+}
+
+/// Reuse the first calibration across launches via the file at `path`
+/// (None disables the cache).
+///
+/// The entry is only used while `cpu_fingerprint()` matches. Like
+/// `set_calibration_window`, call it before the first calibration.
+pub fn set_calibration_cache(path: Option<PathBuf>) {
+    if let Ok(mut cache) = CALIBRATION_CACHE.lock() {
+        *cache = path;
+    }
+}
+
+/// Whether the global frequency was loaded from the cache
+pub fn calibration_was_cached() -> bool {
+    let _ = global_tick_hz();
+    CALIBRATION_FROM_CACHE.load(Ordering::Relaxed)
+}
+
+/// CPU identity the cached frequency is tied to: CPUID vendor,
+/// family/model/stepping signature and brand string.
+///
+/// None off x86, where the frequency is read from hardware and there
+/// is nothing to cache.
+pub fn cpu_fingerprint() -> Option<String> {
+    let leaf0 = safe_cpuid(0, 0)?;
+    let vendor: Vec<u8> = [leaf0.ebx, leaf0.edx, leaf0.ecx]
+        .iter()
+        .flat_map(|r| r.to_le_bytes())
+        .collect();
+    let signature = safe_cpuid(1, 0)?.eax;
+    // parts sharing a signature can differ in base clock, and the
+    // brand ("... @ 2.10GHz") tells them apart
+    let brand: Vec<u8> = (0x8000_0002..=0x8000_0004)
+        .map_while(|leaf| safe_cpuid(leaf, 0))
+        .flat_map(|r| [r.eax, r.ebx, r.ecx, r.edx])
+        .flat_map(u32::to_le_bytes)
+        .collect();
+    let brand = String::from_utf8_lossy(&brand).replace(['\t', '\n', '\r'], " ");
+    Some(format!(
+        "{}-{:08x}-{}",
+        String::from_utf8_lossy(&vendor),
+        signature,
+        brand.trim_matches(['\0', ' '])
+    ))
+}
+
+/// Cached frequency at `path` if it was written for `fingerprint`.
+///
+/// A symlink at `path` is not followed (on Unix), so another user
+/// can't point the cache at a file of their choosing.
+pub fn read_calibration_cache(path: &Path, fingerprint: &str) -> Option<u64> {
+    use std::io::Read;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+    let mut contents = String::new();
+    options.open(path).ok()?.read_to_string(&mut contents).ok()?;
+    let (cached_fingerprint, hz) = contents.trim().split_once('\t')?;
+    if cached_fingerprint != fingerprint {
+        return None;
+    }
+    hz.parse().ok().filter(|&hz| hz != 0)
+}
+
+/// Store `tick_hz` for `fingerprint` at `path`, replacing any entry.
+///
+/// Creates the parent directory (owner-only on Unix) if needed, writes
+/// a fresh temporary file beside `path` (O_EXCL, never through a
+/// symlink) and renames it into place, so readers see the old entry or
+/// the new one and a planted link is replaced rather than written to.
+pub fn write_calibration_cache(path: &Path, fingerprint: &str, tick_hz: u64) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(dir)?;
+    }
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+    let _ = std::fs::remove_file(&temp);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+    let written = options
+        .open(&temp)
+        .and_then(|mut file| file.write_all(format!("{}\t{}\n", fingerprint, tick_hz).as_bytes()))
+        .and_then(|()| std::fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Frequency for the first calibration: the cache if enabled and
+/// valid, else a fresh measurement (which then refreshes the cache)
+fn initial_tick_hz() -> u64 {
+    let path = CALIBRATION_CACHE.lock().ok().and_then(|cache| cache.clone());
+    let (Some(path), Some(fingerprint)) = (path, cpu_fingerprint()) else {
+        return calibrate_tick_hz_with_window(calibration_window());
+    };
+
+    if let Some(hz) = read_calibration_cache(&path, &fingerprint) {
+        CALIBRATION_FROM_CACHE.store(true, Ordering::Relaxed);
+        return hz;
+    }
+    let hz = calibrate_tick_hz_with_window(calibration_window());
+    // best effort: an unwritable cache dir only costs the next launch
+    let _ = write_calibration_cache(&path, &fingerprint, hz);
+    hz
+}
+
+/// Calibrate the tick frequency over `window` and return it in Hz.
+///
+/// Never fails: a window that fails `check_calibration` is retried up
+/// to `CALIBRATION_ATTEMPTS` times, then replaced by the fallback
+/// frequency (with a warning), and without a monotonic clock the TSC
+/// is calibrated against the wall clock. The result is also recorded
+/// for `calibration_error_ppm()`.
+pub fn calibrate_tick_hz_with_window(window: Duration) -> u64 {
+    let calibration = match calibrate_with_retries(window) {
+        Ok(calibration) => calibration,
+        // e.g. unprogrammed cntfrq_el0 firmware or a counter that never advanced
+        Err(err) => fallback_calibration(&err.to_string()),
+    };
+    record_calibration(calibration)
+}
+
+/// Default lowest tick frequency calibration accepts (QPC is 10 MHz,
+/// ARM generic timers 1 MHz and up)
+pub const MIN_PLAUSIBLE_TICK_HZ: u64 = 1_000_000;
+/// Default highest tick frequency calibration accepts
+pub const MAX_PLAUSIBLE_TICK_HZ: u64 = 10_000_000_000;
+
+static PLAUSIBLE_MIN_HZ: AtomicU64 = AtomicU64::new(MIN_PLAUSIBLE_TICK_HZ);
+static PLAUSIBLE_MAX_HZ: AtomicU64 = AtomicU64::new(MAX_PLAUSIBLE_TICK_HZ);
+
+/// Narrow (or widen) the band `check_tick_hz` accepts, e.g. to about
+/// the known TSC rate for a long-running monitor; an error (band
+/// unchanged) if it is empty or starts at 0 Hz
+pub fn set_plausible_tick_hz(band: RangeInclusive<u64>) -> Result<(), String> {
+    if *band.start() == 0 {
+        return Err("plausible tick frequencies must start above 0 Hz".to_string());
+    }
+    if band.is_empty() {
+        return Err(format!("empty plausible band {}..={} Hz", band.start(), band.end()));
+    }
+    PLAUSIBLE_MIN_HZ.store(*band.start(), Ordering::Relaxed);
+    PLAUSIBLE_MAX_HZ.store(*band.end(), Ordering::Relaxed);
+    Ok(())
+}
+
+/// Band `check_tick_hz` accepts, by default
+/// `MIN_PLAUSIBLE_TICK_HZ..=MAX_PLAUSIBLE_TICK_HZ`
+pub fn plausible_tick_hz() -> RangeInclusive<u64> {
+    PLAUSIBLE_MIN_HZ.load(Ordering::Relaxed)..=PLAUSIBLE_MAX_HZ.load(Ordering::Relaxed)
+}
+/// Largest segment disagreement (ppm) a calibration may show; a quiet
+/// machine stays in the tens, a clock step mid-window goes far past
+pub const MAX_CALIBRATION_ERROR_PPM: f64 = 10_000.0;
+/// Windows measured before giving up on calibration
+pub const CALIBRATION_ATTEMPTS: u32 = 3;
+/// How many times its target length a calibration segment may take
+/// before the window counts as preempted; the spin overshoots by
+/// microseconds, a deschedule by a whole time slice (ms)
+pub const PREEMPTED_SEGMENT_RATIO: u64 = 2;
+
+/// Calibrate like `calibrate_tick_hz_with_window` over the configured
+/// window, but fail instead of falling back or degrading: no monotonic
+/// reference clock, or every attempt failed `check_calibration`.
+///
+/// Does not replace the global frequency (see `recalibrate`).
+pub fn try_calibrate() -> Result<u64, CalibrationError> {
+    #[cfg(all(
+        not(windows),
+        any(target_arch = "x86", target_arch = "x86_64")
+    ))]
+    if reference_clock() == ReferenceClock::SystemTime {
+        return Err(CalibrationError::ReferenceClockUnavailable);
+    }
+
+    let calibration = calibrate_with_retries(calibration_window())?;
+    Ok(record_calibration(calibration))
+}
+
+/// Measure windows until one passes `check_calibration`, at most
+/// `CALIBRATION_ATTEMPTS`; the last error if none does.
+///
+/// A preempted window (see `check_preemption`) is retried too, after
+/// yielding for a millisecond per attempt so whatever took the CPU can
+/// finish, but the last attempt is kept if only preemption is wrong
+/// with it: a paired read makes a preempted window noisier, not wrong.
+fn calibrate_with_retries(window: Duration) -> Result<Calibration, CalibrationError> {
+    retry_calibration(window, traced_calibration)
+}
+
+/// `calibrate_with_retries` with `measure` measuring each window
+fn retry_calibration(
+    window: Duration,
+    mut measure: impl FnMut(Duration) -> Calibration,
+) -> Result<Calibration, CalibrationError> {
+    let mut attempt = 1;
+    loop {
+        let calibration = measure(window);
+        let mut checked = check_calibration(&calibration);
+        if checked.is_ok() && attempt < CALIBRATION_ATTEMPTS {
+            checked = check_preemption(&calibration, window).and(checked);
+        }
+        match checked {
+            Ok(_) => return Ok(calibration),
+            Err(err) if attempt >= CALIBRATION_ATTEMPTS => return Err(err),
+            Err(err) => {
+                trace_event(TraceKind::CalibrationRejected, || format!("attempt {}: {}", attempt, err));
+                if matches!(err, CalibrationError::Preempted(_)) {
+                    std::thread::sleep(Duration::from_millis(attempt as u64));
+                }
+            }
+        }
+        attempt += 1;
+    }
+}
+
+/// Err(Preempted) when a segment of `calibration`, measured over
+/// `window`, took more than `PREEMPTED_SEGMENT_RATIO` times its share
+pub fn check_preemption(calibration: &Calibration, window: Duration) -> Result<(), CalibrationError> {
+    let segments = calibration.samples.len().max(1) as u64;
+    let limit = (window.as_nanos() as u64 / segments).max(1).saturating_mul(PREEMPTED_SEGMENT_RATIO);
+    match calibration.samples.iter().map(|s| s.delta_ns).max() {
+        Some(longest) if longest > limit => Err(CalibrationError::Preempted(longest)),
+        _ => Ok(()),
+    }
+}
+
+/// Frequency of `calibration` if it is usable: plausible (see
+/// `check_tick_hz`), every segment's reference clock advanced, and the
+/// segments agree to within `MAX_CALIBRATION_ERROR_PPM`.
+///
+/// An NTP step or smear of the reference clock mid-window shows up as
+/// a segment with no (or negative) elapsed time or one whose rate is
+/// far from the others.
+pub fn check_calibration(calibration: &Calibration) -> Result<u64, CalibrationError> {
+    let hz = check_tick_hz(calibration.tick_hz)?;
+    if calibration.samples.iter().any(|s| s.delta_ns == 0) {
+        return Err(CalibrationError::ZeroDelta);
+    }
+    match calibration.error_ppm() {
+        // NaN as u64 is 0, but a NaN spread is still not usable
+        Some(ppm) if ppm.is_nan() || ppm > MAX_CALIBRATION_ERROR_PPM => Err(CalibrationError::Unstable(ppm as u64)),
+        _ => Ok(hz),
+    }
+}
+
+/// `tick_hz` if it is a plausible counter frequency (within
+/// `plausible_tick_hz()`)
+pub fn check_tick_hz(tick_hz: u64) -> Result<u64, CalibrationError> {
+    match tick_hz {
+        0 => Err(CalibrationError::ZeroDelta),
+        hz if plausible_tick_hz().contains(&hz) => Ok(hz),
+        hz => Err(CalibrationError::OutOfRange(hz)),
+    }
+}
+
+/// `measure_calibration` between calibration start/end trace events
+fn traced_calibration(window: Duration) -> Calibration {
+    trace_event(TraceKind::CalibrationStart, || format!("window={} ms", window.as_millis()));
+    let calibration = measure_calibration(window);
+    trace_event(TraceKind::CalibrationEnd, || match calibration.error_ppm() {
+        Some(ppm) => format!("freq={} Hz rse={:.1} ppm", calibration.tick_hz, ppm),
+        None => format!("freq={} Hz", calibration.tick_hz),
+    });
+    calibration
+}
+
+/// Keep `calibration` for `calibration_error_ppm()`, returning its Hz
+fn record_calibration(calibration: Calibration) -> u64 {
+    let hz = calibration.tick_hz;
+    if let Ok(mut last) = LAST_CALIBRATION.lock() {
+        *last = Some(calibration);
+    }
+    hz
+}
+
+#[allow(unused_variables)]
+fn measure_calibration(window: Duration) -> Calibration {
+    // --------------------------
+    // Windows: QPC frequency
+    // --------------------------
+    #[cfg(windows)]
+    unsafe {
+        let mut freq: i64 = 0;
+        QueryPerformanceFrequency(&mut freq);
+        Calibration { tick_hz: freq as u64, samples: Vec::new() }
+    }
+
+    // --------------------------
+    // x86 Linux / macOS
+    // --------------------------
+    #[cfg(all(
+        not(windows),
+        any(target_arch = "x86", target_arch = "x86_64")
+    ))]
+    {
+        calibrate_tsc_with_monotonic(window)
+    }
+
+    // --------------------------
+    // ARM64
+    // --------------------------
+    #[cfg(all(not(windows), target_arch = "aarch64"))]
+    {
+        // cntvct_el0 is the Mach tick counter on macOS; its rate comes
+        // from the timebase there
+        let mach_hz = mach_timebase().map(|(numer, denom)| mach_timebase_hz(numer, denom)).filter(|&hz| hz > 0);
+        Calibration { tick_hz: mach_hz.unwrap_or_else(read_cntfrq_el0), samples: Vec::new() }
+    }
+
+    // fallback
+    #[cfg(not(any(
+        windows,
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64"
+    )))]
+    {
+        fallback_calibration("no hardware counter on this target")
+    }
+}
+
+// --------------------------
+// Fallback frequency
+// --------------------------
+
+/// Frequency assumed when none can be read or measured
+pub const DEFAULT_FALLBACK_TICK_HZ: u64 = 2_500_000_000;
+
+/// Environment variable overriding `DEFAULT_FALLBACK_TICK_HZ`
+pub const FALLBACK_TICK_HZ_ENV: &str = "TIMING_EVAL_FALLBACK_HZ";
+
+/// Effective fallback frequency: `TIMING_EVAL_FALLBACK_HZ` if set to a
+/// nonzero integer, else `DEFAULT_FALLBACK_TICK_HZ`
+pub fn fallback_tick_hz() -> u64 {
+    fallback_tick_hz_from(std::env::var(FALLBACK_TICK_HZ_ENV).ok().as_deref())
+}
+
+/// `fallback_tick_hz` given the variable's value, if set
+fn fallback_tick_hz_from(value: Option<&str>) -> u64 {
+    value
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&hz| hz != 0)
+        .unwrap_or(DEFAULT_FALLBACK_TICK_HZ)
+}
+
+/// Fallback "calibration", announced on stderr: every ns() built on it
+/// is off by however far the guess is from the real rate
+fn fallback_calibration(reason: &str) -> Calibration {
+    let hz = fallback_tick_hz();
+    eprintln!(
+        "Warning: using fallback tick frequency {} Hz ({}); set {} to the real rate",
+        hz, reason, FALLBACK_TICK_HZ_ENV
+    );
+    Calibration { tick_hz: hz, samples: Vec::new() }
+}
+
+/// Current raw counter value and its frequency (Hz), for checking ns()
+/// against external tools: QPC value and QueryPerformanceFrequency on
+/// Windows, TSC value and calibrated frequency on x86, cntvct_el0 and
+/// cntfrq_el0 on ARM64.
+pub fn raw_counter_and_frequency() -> (u64, u64) {
+    let hz = tick_hz();
+    (HighResolutionTimer::ticks(), hz)
+}
+
+// ============================================================
+// CPUID
+// ============================================================
+
+/// Registers returned by one CPUID query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+/// CPUID `leaf`/`subleaf`, or None when CPUID can't be used: off x86,
+/// inside SGX enclaves (where it faults), or when the leaf is above the
+/// maximum the CPU reports for its range.
+///
+/// All CPU detection goes through here so it degrades to "unknown".
+#[allow(unused_variables)]
+pub fn safe_cpuid(leaf: u32, subleaf: u32) -> Option<CpuidResult> {
+    #[cfg(all(not(target_env = "sgx"), any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        // leaf 0 / 0x8000_0000 report the highest leaf of their range
+        let range_base = leaf & 0x8000_0000;
+        if leaf != range_base && __cpuid_count(range_base, 0).eax < leaf {
+            return None;
+        }
+        let r = __cpuid_count(leaf, subleaf);
+        Some(CpuidResult { eax: r.eax, ebx: r.ebx, ecx: r.ecx, edx: r.edx })
+    }
+
+    #[cfg(not(all(not(target_env = "sgx"), any(target_arch = "x86", target_arch = "x86_64"))))]
+    {
+        None
+    }
+}
+
+// ============================================================
+// CPU frequency hints
+// ============================================================
+
+/// Whether the TSC ticks at a constant rate regardless of P/C-states
+/// (CPUID 0x8000_0007 EDX bit 8). False when CPUID is unavailable.
+pub fn tsc_is_invariant() -> bool {
+    safe_cpuid(0x8000_0007, 0).is_some_and(|r| r.edx & (1 << 8) != 0)
+}
+
+/// Whether the local APIC timer can fire at a TSC deadline (CPUID 1
+/// ECX bit 24) rather than after a coarser countdown, which lets the
+/// kernel wake sleepers closer to their target time. False when CPUID
+/// is unavailable; a hypervisor may hide it even where the host has it.
+pub fn tsc_deadline_supported() -> bool {
+    safe_cpuid(1, 0).is_some_and(|r| r.ecx & (1 << 24) != 0)
+}
+
+/// Estimated CPU base (nominal) frequency in MHz.
+///
+/// Only meaningful on invariant-TSC machines, where the TSC runs at the
+/// base clock, and while timers read the TSC. Returns None otherwise,
+/// since tick_hz is then not the core clock.
+pub fn estimated_base_frequency_mhz() -> Option<u64> {
+    base_frequency_mhz(tsc_is_invariant(), active_backend(), tick_hz())
+}
+
+/// `estimated_base_frequency_mhz` given the invariance bit, backend and
+/// frequency it reads
+fn base_frequency_mhz(invariant: bool, backend: TimerBackend, tick_hz: u64) -> Option<u64> {
+    (invariant && backend == TimerBackend::Tsc).then_some(tick_hz / 1_000_000)
+}
+
+/// Nominal base frequency in MHz as the CPU advertises it: CPUID leaf
+/// 0x16, else the "@ 2.10GHz" suffix of the brand string
+pub fn nominal_base_frequency_mhz() -> Option<f64> {
+    if let Some(r) = safe_cpuid(0x16, 0)
+        && r.eax & 0xffff != 0
+    {
+        return Some((r.eax & 0xffff) as f64);
+    }
+    model_name_mhz(&cpu_brand()?)
+}
+
+/// CPU brand string: CPUID leaves 0x8000_0002..=0x8000_0004, else the
+/// /proc/cpuinfo model name
+pub fn cpu_brand() -> Option<String> {
+    let leaves: Option<Vec<CpuidResult>> =
+        (0x8000_0002..=0x8000_0004).map(|leaf| safe_cpuid(leaf, 0)).collect();
+    if let Some(leaves) = leaves {
+        let bytes: Vec<u8> = leaves
+            .iter()
+            .flat_map(|r| [r.eax, r.ebx, r.ecx, r.edx])
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        let brand = String::from_utf8_lossy(&bytes).trim_matches(['\0', ' ']).to_string();
+        if !brand.is_empty() {
+            return Some(brand);
+        }
+    }
+
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    let (_, model) = cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))?
+        .split_once(':')?;
+    Some(model.trim().to_string())
+}
+
+/// MHz from a CPU brand string ending in "@ <N>GHz", e.g.
+/// "Intel(R) Xeon(R) Silver 4310 CPU @ 2.10GHz"
+pub fn model_name_mhz(model: &str) -> Option<f64> {
+    let (_, speed) = model.rsplit_once('@')?;
+    let ghz: f64 = speed.trim().strip_suffix("GHz")?.trim().parse().ok()?;
+    (ghz > 0.0).then_some(ghz * 1000.0)
+}
+
+/// Percent by which `measured_hz` is off `nominal_hz` (positive when
+/// faster), or None for a zero nominal frequency
+pub fn discrepancy_percent(measured_hz: f64, nominal_hz: f64) -> Option<f64> {
+    if nominal_hz <= 0.0 {
+        return None;
+    }
+    Some((measured_hz - nominal_hz) / nominal_hz * 100.0)
+}
+
+/// Calibrated TSC frequency vs the nominal base frequency, in percent.
+///
+/// Only defined where `estimated_base_frequency_mhz` is; more than a
+/// percent or so points at a calibration or invariance problem.
+pub fn frequency_discrepancy_percent() -> Option<f64> {
+    estimated_base_frequency_mhz()?;
+    discrepancy_percent(tick_hz() as f64, nominal_base_frequency_mhz()? * 1e6)
+}
+
+// ============================================================
+// rdtsc emulation check
+// ============================================================
+
+/// Per-read cost above which rdtsc is assumed trapped and emulated by a
+/// hypervisor or sandbox; a native read takes tens of cycles
+pub const RDTSC_EMULATION_THRESHOLD_NS: f64 = 100.0;
+
+/// Whether a measured per-read cost points at an emulated rdtsc
+pub fn looks_emulated(ns_per_read: f64) -> bool {
+    ns_per_read > RDTSC_EMULATION_THRESHOLD_NS
+}
+
+/// Average cost of one raw rdtsc in ns, timed against the monotonic
+/// clock. None where the timer does not read the TSC directly.
+pub fn rdtsc_read_cost_ns() -> Option<f64> {
+    #[cfg(all(not(windows), any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        const RDTSC_PROBE_READS: u32 = 10_000;
+
+        let start = monotonic_raw_ns();
+        for _ in 0..RDTSC_PROBE_READS {
+            core::hint::black_box(unsafe { _rdtsc() });
+        }
+        let elapsed = monotonic_raw_ns().saturating_sub(start);
+        Some(elapsed as f64 / RDTSC_PROBE_READS as f64)
+    }
+
+    #[cfg(not(all(not(windows), any(target_arch = "x86", target_arch = "x86_64"))))]
+    {
+        None
+    }
+}
+
+/// Heuristic: rdtsc is probably trapped and emulated (always false
+/// where it is not used)
+pub fn rdtsc_looks_emulated() -> bool {
+    rdtsc_read_cost_ns().is_some_and(looks_emulated)
+}
+
+// ============================================================
+// rdtscp TSC_AUX (core / node id)
+// ============================================================
+
+/// One rdtscp reading: the TSC and the TSC_AUX value of the core that
+/// executed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TscAuxReading {
+    pub ticks: u64,
+    pub aux: u32,
+}
+
+/// Whether the CPU implements rdtscp (CPUID 0x8000_0001 EDX bit 27).
+///
+/// Read once: migration tracking asks before every batch, and CPUID
+/// is slow and traps under some hypervisors.
+pub fn rdtscp_supported() -> bool {
+    static SUPPORTED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *SUPPORTED.get_or_init(|| safe_cpuid(0x8000_0001, 0).is_some_and(|r| r.edx & (1 << 27) != 0))
+}
+
+/// TSC plus TSC_AUX via rdtscp.
+///
+/// Linux (and Android) only, since only there does the kernel load
+/// TSC_AUX with a known layout; None elsewhere or without rdtscp.
+pub fn read_tsc_aux() -> Option<TscAuxReading> {
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "x86", target_arch = "x86_64")
+    ))]
+    {
+        if !rdtscp_supported() {
+            return None;
+        }
+        let mut aux = 0u32;
+        let ticks = unsafe { __rdtscp(&mut aux) };
+        Some(TscAuxReading { ticks, aux })
+    }
+
+    #[cfg(not(all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "x86", target_arch = "x86_64")
+    )))]
+    {
+        None
+    }
+}
+
+/// NUMA node in a Linux TSC_AUX value, laid out as (node << 12) | cpu
+pub fn aux_node(aux: u32) -> u32 {
+    aux >> 12
+}
+
+/// CPU number in a Linux TSC_AUX value
+pub fn aux_cpu(aux: u32) -> u32 {
+    aux & 0xfff
+}
+
+/// Whether two readings ran on different NUMA nodes. Firmware or a VM
+/// may offset the TSC per socket (IA32_TSC_ADJUST), so a delta across
+/// nodes is unreliable.
+pub fn crossed_numa_node(first: &TscAuxReading, second: &TscAuxReading) -> bool {
+    aux_node(first.aux) != aux_node(second.aux)
+}
+
+// --------------------------
+// x86 TSC calibration (integer)
+// --------------------------
+
+#[cfg(all(
+    not(windows),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+fn calibrate_tsc_with_monotonic(window: Duration) -> Calibration {
+    let segment_ns = (window.as_nanos() as u64 / CALIBRATION_SEGMENTS as u64).max(1);
+    let mut samples = Vec::with_capacity(CALIBRATION_SEGMENTS);
+
+    let (first_ns, first_tsc) = read_monotonic_and_tsc();
+    let (mut prev_ns, mut prev_tsc) = (first_ns, first_tsc);
+    for _ in 0..CALIBRATION_SEGMENTS {
+        spin_wait_ns(segment_ns);
+        let (ns, tsc) = read_monotonic_and_tsc();
+        samples.push(CalibrationSample {
+            // wraps if the TSC went backwards; check_calibration rejects it
+            delta_ticks: tsc.wrapping_sub(prev_tsc),
+            delta_ns: ns.saturating_sub(prev_ns) as u64,
+        });
+        (prev_ns, prev_tsc) = (ns, tsc);
+    }
+
+    let delta_tsc = prev_tsc.wrapping_sub(first_tsc);
+    let delta_ns = prev_ns.saturating_sub(first_ns).max(1);
+
+    Calibration {
+        tick_hz: (delta_tsc as u128 * 1_000_000_000u128 / delta_ns) as u64,
+        samples,
+    }
+}
+
+/// Monotonic ns and TSC read back to back
+#[cfg(all(
+    not(windows),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[inline(always)]
+fn read_monotonic_and_tsc() -> (u128, u64) {
+    unsafe {
+        let ns = monotonic_raw_ns();
+        _mm_lfence();
+        let tsc = _rdtsc();
+        _mm_lfence();
+        (ns, tsc)
+    }
+}
+
+#[cfg(all(
+    not(windows),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[inline(always)]
+fn spin_wait_ns(ns: u64) {
+    let start = monotonic_raw_ns();
+
+    loop {
+        let now = monotonic_raw_ns();
+        if now.saturating_sub(start) >= ns as u128 {
+            break;
+        }
+        // pause/yield: keeps the spin from tripping turbo mid-calibration
+        core::hint::spin_loop();
+    }
+}
+
+// --------------------------
+// Reference clock (x86 calibration)
+// --------------------------
+
+/// Clock the TSC is calibrated against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceClock {
+    MonotonicRaw,
+    /// CLOCK_MONOTONIC, when the raw clock is rejected (older Android
+    /// kernels, seccomp-filtered musl containers)
+    Monotonic,
+    /// Wall clock, when a sandbox blocks clock_gettime outright:
+    /// microsecond-ish resolution and subject to clock steps
+    SystemTime,
+}
+
+/// First usable reference clock given which clock_gettime clocks work
+pub fn select_reference_clock(raw_ok: bool, monotonic_ok: bool) -> ReferenceClock {
+    if raw_ok {
+        ReferenceClock::MonotonicRaw
+    } else if monotonic_ok {
+        ReferenceClock::Monotonic
+    } else {
+        ReferenceClock::SystemTime
+    }
+}
+
+/// Reference clock for this process, probed once; warns when only the
+/// degraded SystemTime fallback is left
+#[cfg(all(
+    not(windows),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+fn reference_clock() -> ReferenceClock {
+    static CLOCK: OnceLock<ReferenceClock> = OnceLock::new();
+    *CLOCK.get_or_init(|| {
+        let clock = select_reference_clock(
+            clock_gettime_ns(libc::CLOCK_MONOTONIC_RAW).is_some(),
+            clock_gettime_ns(libc::CLOCK_MONOTONIC).is_some(),
+        );
+        if clock == ReferenceClock::SystemTime {
+            eprintln!(
+                "Warning: clock_gettime is blocked (sandbox?); calibrating against SystemTime, resolution is degraded"
+            );
+        }
+        clock
+    })
+}
+
+/// `clock` in nanoseconds, or None if the call fails or a filter
+/// "succeeds" without filling in the time
+#[cfg(all(
+    not(windows),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[inline(always)]
+fn clock_gettime_ns(clock: libc::clockid_t) -> Option<u128> {
+    // zeroed() rather than a struct literal: on musl/time64 targets
+    // timespec carries private padding fields
+    let mut ts: libc::timespec = unsafe { core::mem::zeroed() };
+    if unsafe { libc::clock_gettime(clock, &mut ts) } != 0 || (ts.tv_sec == 0 && ts.tv_nsec == 0) {
+        return None;
+    }
+    Some(ts.tv_sec as u128 * 1_000_000_000u128 + ts.tv_nsec as u128)
+}
+
+/// Reference-clock nanoseconds: CLOCK_MONOTONIC_RAW where available,
+/// falling back as described on `ReferenceClock`
+#[cfg(all(
+    not(windows),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[inline(always)]
+fn monotonic_raw_ns() -> u128 {
+    let ns = match reference_clock() {
+        ReferenceClock::MonotonicRaw => clock_gettime_ns(libc::CLOCK_MONOTONIC_RAW),
+        ReferenceClock::Monotonic => clock_gettime_ns(libc::CLOCK_MONOTONIC),
+        ReferenceClock::SystemTime => None,
+    };
+    ns.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos())
+    })
+}
+
+// --------------------------
+// ARM64 frequency
+// --------------------------
+
+#[cfg(all(not(windows), target_arch = "aarch64"))]
+#[inline(always)]
+fn read_cntfrq_el0() -> u64 {
+    let freq: u64;
+    unsafe {
+        core::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq);
+    }
+    freq
+}
+
+/// Serializes tests that change the global backend or frequency, or
+/// read timers that such a change would break
+#[cfg(test)]
+pub(crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// --------------------------
+// macOS Mach timebase
+// --------------------------
+// mach_absolute_time() ticks are ns only where the timebase is 1/1
+// (Intel Macs); Apple Silicon reports 125/3 (24 MHz ticks). Anything
+// converting Mach ticks must scale by numer/denom, never assume ns.
+
+/// `(numer, denom)` of `mach_timebase_info`, read once: Mach ticks times
+/// numer / denom are nanoseconds. None off macOS or if the call fails.
+pub fn mach_timebase() -> Option<(u32, u32)> {
+    #[cfg(target_os = "macos")]
+    {
+        #[repr(C)]
+        struct MachTimebaseInfo {
+            numer: u32,
+            denom: u32,
+        }
+        unsafe extern "C" {
+            fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+        }
+
+        static TIMEBASE: std::sync::OnceLock<Option<(u32, u32)>> = std::sync::OnceLock::new();
+        *TIMEBASE.get_or_init(|| {
+            let mut info = MachTimebaseInfo { numer: 0, denom: 0 };
+            let ok = unsafe { mach_timebase_info(&mut info) } == 0;
+            (ok && info.numer > 0 && info.denom > 0).then_some((info.numer, info.denom))
+        })
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        None
+    }
+}
+
+/// Mach ticks to ns under timebase `numer / denom`, widened so large
+/// tick counts can't overflow; 0 for a 0 denominator
+pub fn mach_ticks_to_ns(ticks: u64, numer: u32, denom: u32) -> u128 {
+    if denom == 0 {
+        return 0;
+    }
+    ticks as u128 * numer as u128 / denom as u128
+}
+
+/// Tick rate (Hz) a Mach timebase stands for: 1e9 * denom / numer
+pub fn mach_timebase_hz(numer: u32, denom: u32) -> u64 {
+    if numer == 0 {
+        return 0;
+    }
+    (1_000_000_000u128 * denom as u128 / numer as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Waker};
+
+    /// A timer started at raw counter value `start_cycles`
+    fn timer_at(start_cycles: u64) -> HighResolutionTimer {
+        HighResolutionTimer {
+            start_cycles,
+            backend: backend(),
+            generation: CALIBRATION_GENERATION.load(Ordering::Acquire),
+            accumulator: TickAccumulator::new(start_cycles, COUNTER_BITS),
+        }
+    }
+
+    /// Poll `fut` to completion on this thread
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn base_frequency_needs_invariant_tsc() {
+        assert_eq!(base_frequency_mhz(false, TimerBackend::Tsc, 2_100_000_000), None);
+        assert_eq!(base_frequency_mhz(true, TimerBackend::Monotonic, 1_000_000_000), None);
+        assert_eq!(base_frequency_mhz(true, TimerBackend::Tsc, 2_100_000_000), Some(2100));
+    }
+
+    // the libc path shared by glibc, musl and bionic
+    #[cfg(all(not(windows), any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    fn tsc_calibration_is_plausible() {
+        let start = monotonic_raw_ns();
+        let tick_hz = calibrate_tsc_with_monotonic(Duration::from_millis(5)).tick_hz;
+        assert!(monotonic_raw_ns() > start);
+        assert!((100_000_000..=10_000_000_000).contains(&tick_hz), "{} Hz", tick_hz);
+    }
+
+    #[cfg(all(not(windows), any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    fn paused_spin_still_calibrates() {
+        let start = monotonic_raw_ns();
+        spin_wait_ns(1_000_000);
+        assert!(monotonic_raw_ns() - start >= 1_000_000);
+
+        let first = calibrate_tsc_with_monotonic(Duration::from_millis(5)).tick_hz as f64;
+        let second = calibrate_tsc_with_monotonic(Duration::from_millis(5)).tick_hz as f64;
+        assert!((first - second).abs() / first < 0.05, "{} vs {} Hz", first, second);
+    }
+
+    #[test]
+    fn ticks_to_ns_does_not_overflow() {
+        assert_eq!(ticks_to_ns(u64::MAX as u128, 1), u64::MAX as u128 * 1_000_000_000);
+        assert_eq!(ticks_to_ns(u128::MAX, 1), u128::MAX);
+        // (2^128 - 1) / (2^64 - 1) is exactly 2^64 + 1 seconds
+        assert_eq!(ticks_to_ns(u128::MAX, u64::MAX), ((1u128 << 64) + 1) * 1_000_000_000);
+        assert_eq!(ticks_to_ns(u64::MAX as u128, 0), 0);
+    }
+
+    #[cfg(any(windows, target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn raw_counter_pair_is_nonzero() {
+        let (ticks, hz) = raw_counter_and_frequency();
+        assert_ne!(ticks, 0);
+        assert_ne!(hz, 0);
+    }
+
+    #[test]
+    fn since_measures_tick_offset() {
+        let (earlier, later) = (timer_at(1_000), timer_at(4_000));
+        let expected = ticks_to_ns(3_000, tick_hz());
+        assert_eq!(later.since(&earlier), expected);
+        assert_eq!(earlier.since(&later), expected);
+        // across a counter wrap
+        let wrapped = timer_at(u64::MAX - 999);
+        assert_eq!(timer_at(1_000).since(&wrapped), ticks_to_ns(2_000, tick_hz()));
+    }
+
+    #[test]
+    fn trivial_future_is_fast() {
+        let _lock = test_lock();
+        let (output, ns) = block_on(time_future(async { 42 }));
+        assert_eq!(output, 42);
+        assert!(ns < 100_000_000, "{} ns", ns);
+    }
+
+    /// Segments of `segment_ns` at 3 GHz whose reference reads are off
+    /// by a fixed +-100 ns of jitter
+    fn jittered_calibration(segment_ns: u64) -> Calibration {
+        let samples = (0..CALIBRATION_SEGMENTS as u64)
+            .map(|i| CalibrationSample {
+                delta_ticks: segment_ns * 3,
+                delta_ns: if i % 2 == 0 { segment_ns - 100 } else { segment_ns + 100 },
+            })
+            .collect();
+        Calibration { tick_hz: 3_000_000_000, samples }
+    }
+
+    #[test]
+    fn longer_window_is_tighter() {
+        let short = jittered_calibration(200_000).error_ppm().unwrap();
+        let long = jittered_calibration(20_000_000).error_ppm().unwrap();
+        assert!(long < short / 50.0, "{} vs {} ppm", long, short);
+    }
+
+    #[test]
+    fn timers_across_frequencies_are_not_comparable() {
+        let _lock = test_lock();
+        let (first, second) = (timer_at(0), timer_at(0));
+        assert!(first.is_comparable_with(&second));
+
+        let recalibrated = HighResolutionTimer { generation: first.generation + 1, ..timer_at(0) };
+        assert!(!first.is_comparable_with(&recalibrated));
+        let other_backend = HighResolutionTimer { backend: TimerBackend::Fallback, ..timer_at(0) };
+        assert!(!timer_at(0).is_comparable_with(&other_backend));
+    }
+
+    // x86_64 and aarch64 alike: never cntvct or rdtsc on Windows
+    #[cfg(windows)]
+    #[test]
+    fn windows_reads_qpc() {
+        assert_eq!(backend(), TimerBackend::Qpc);
+    }
+
+    #[test]
+    fn emulation_threshold() {
+        // native rdtsc is a few ns, a trapped one microseconds
+        assert!(!looks_emulated(8.0));
+        assert!(!looks_emulated(RDTSC_EMULATION_THRESHOLD_NS));
+        assert!(looks_emulated(RDTSC_EMULATION_THRESHOLD_NS + 0.1));
+        assert!(looks_emulated(1_500.0));
+    }
+
+    #[test]
+    fn measure_returns_output_and_duration() {
+        let _lock = test_lock();
+        let (output, elapsed) = measure(|| vec![1, 2, 3]);
+        assert_eq!(output, [1, 2, 3]);
+        assert!(elapsed < Duration::from_secs(1));
+
+        let ((), slept) = measure(|| std::thread::sleep(Duration::from_millis(2)));
+        assert!(slept >= Duration::from_millis(1), "{:?}", slept);
+    }
+
+    #[test]
+    fn accumulator_absorbs_wraps() {
+        // an 8-bit counter wrapping twice: 200 -> 250 -> 4 -> 180 -> 10
+        let mut narrow = TickAccumulator::new(200, 8);
+        let totals: Vec<u128> = [250, 4, 180, 10].into_iter().map(|raw| narrow.update(raw)).collect();
+        assert_eq!(totals, [50, 60, 236, 322]);
+
+        let mut full = TickAccumulator::new(u64::MAX - 9, 64);
+        assert_eq!(full.update(5), 15);
+        assert_eq!(full.update(u64::MAX / 2), 15 + u64::MAX as u128 / 2 - 5);
+        assert_eq!(full.total(), 15 + u64::MAX as u128 / 2 - 5);
+    }
+
+    #[test]
+    fn calibration_cache_round_trip() {
+        let path = std::env::temp_dir().join(format!("timing-eval-test-{}-tick-hz.cache", std::process::id()));
+        write_calibration_cache(&path, "GenuineIntel-000906ea", 2_904_000_123).unwrap();
+        assert_eq!(read_calibration_cache(&path, "GenuineIntel-000906ea"), Some(2_904_000_123));
+        assert_eq!(read_calibration_cache(&path, "AuthenticAMD-00a20f10"), None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read_calibration_cache(&path, "GenuineIntel-000906ea"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn calibration_cache_never_follows_symlinks() {
+        let dir = std::env::temp_dir().join(format!("timing-eval-test-{}-cache-dir", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (path, victim) = (dir.join("nested").join("tick-hz"), dir.join("victim"));
+        write_calibration_cache(&path, "GenuineIntel-000906ea", 1).unwrap();
+
+        std::fs::write(&victim, "GenuineIntel-000906ea\t7\n").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::os::unix::fs::symlink(&victim, &path).unwrap();
+        assert_eq!(read_calibration_cache(&path, "GenuineIntel-000906ea"), None);
+
+        write_calibration_cache(&path, "GenuineIntel-000906ea", 2_904_000_123).unwrap();
+        assert_eq!(std::fs::read_to_string(&victim).unwrap(), "GenuineIntel-000906ea\t7\n");
+        assert!(!std::fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+        assert_eq!(read_calibration_cache(&path, "GenuineIntel-000906ea"), Some(2_904_000_123));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn env_overrides_fallback_frequency() {
+        assert_eq!(fallback_tick_hz_from(None), DEFAULT_FALLBACK_TICK_HZ);
+        assert_eq!(fallback_tick_hz_from(Some(" 3000000000\n")), 3_000_000_000);
+        assert_eq!(fallback_tick_hz_from(Some("0")), DEFAULT_FALLBACK_TICK_HZ);
+        assert_eq!(fallback_tick_hz_from(Some("3 GHz")), DEFAULT_FALLBACK_TICK_HZ);
+    }
+
+    #[test]
+    fn elapsed_integer_part_matches_fraction() {
+        // 3 ticks at 2.4 GHz are 1.25 ns
+        let elapsed = ElapsedTime::from_ticks(3, 2_400_000_000);
+        assert_eq!((elapsed.ns_int, elapsed.ns_frac), (1, 1.25));
+        for ticks in [0, 1, 7, 1_000, 123_456_789] {
+            let elapsed = ElapsedTime::from_ticks(ticks, 2_400_000_000);
+            assert_eq!(elapsed.ns_int as f64, elapsed.ns_frac.trunc(), "{} ticks", ticks);
+        }
+    }
+
+    #[test]
+    fn ns_to_cycles_at_known_frequency() {
+        let _lock = test_lock();
+        // one second is tick_hz cycles, and the scale is linear
+        let hz = tick_hz() as f64;
+        assert!((ns_to_cycles(1e9) - hz).abs() <= hz * 1e-12);
+        assert!((ns_to_cycles(10.0) - 20.0 * ns_to_cycles(0.5)).abs() <= 1e-9);
+    }
+
+    #[test]
+    fn cpuid_availability() {
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            assert_eq!(safe_cpuid(0, 0), None);
+            assert!(!tsc_is_invariant());
+        }
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            let max_leaf = safe_cpuid(0, 0).expect("leaf 0").eax;
+            // beyond the reported range is refused, not queried
+            assert_eq!(safe_cpuid(max_leaf + 1, 0), None);
+        }
+    }
+
+    #[test]
+    fn stop_reads_like_ns() {
+        let _lock = test_lock();
+        let timer = HighResolutionTimer::start();
+        std::thread::sleep(Duration::from_millis(1));
+        let ns = timer.ns();
+        let stopped = timer.stop();
+        assert!(ns >= 500_000, "{} ns", ns);
+        // the same start point, read a moment later
+        assert!(stopped >= ns && stopped - ns < 1_000_000, "{} then {} ns", ns, stopped);
+    }
+
+    #[test]
+    fn numa_node_change_from_aux() {
+        let reading = |node: u32, cpu: u32| TscAuxReading { ticks: 0, aux: node << 12 | cpu };
+        assert_eq!((aux_node(reading(1, 37).aux), aux_cpu(reading(1, 37).aux)), (1, 37));
+        assert!(!crossed_numa_node(&reading(0, 3), &reading(0, 5)));
+        assert!(crossed_numa_node(&reading(0, 3), &reading(1, 3)));
+    }
+
+    #[test]
+    fn elapsed_tracks_instant() {
+        let _lock = test_lock();
+        let (timer, instant) = (HighResolutionTimer::now(), Instant::now());
+        std::thread::sleep(Duration::from_millis(5));
+        let (hrt, std) = (timer.elapsed(), instant.elapsed());
+        assert!(hrt >= Duration::from_millis(4), "{:?}", hrt);
+        // within 5% plus a scheduling slice of each other
+        let gap = hrt.abs_diff(std);
+        assert!(gap < std / 20 + Duration::from_micros(500), "hrt {:?} vs Instant {:?}", hrt, std);
+    }
+
+    #[test]
+    fn fast_path_within_1ns_of_exact() {
+        for hz in [24_000_000, 1_000_000_000, 2_099_999_999, 3_000_000_000, 4_700_000_007] {
+            let recip = ns_reciprocal(hz);
+            assert_ne!(recip, 0);
+            for ticks in [0, 1, 999, 1_000_000, 123_456_789_012, (1u64 << RECIP_SHIFT) - 1] {
+                let exact = ticks_to_ns(ticks as u128, hz);
+                let fast = ticks_to_ns_fast(ticks, hz, recip);
+                assert!(fast <= exact && exact - fast <= 1, "{} ticks at {} Hz: {} vs {}", ticks, hz, fast, exact);
+            }
+        }
+    }
+
+    #[test]
+    fn fast_path_sweep() {
+        let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        for _ in 0..100_000 {
+            // 1 MHz to 10 GHz, past any real counter
+            let hz = 1_000_000 + next() % 10_000_000_000;
+            let recip = ns_reciprocal(hz);
+            // every magnitude up to the shift, then past it
+            let ticks = next() >> (next() % 64);
+            let exact = ticks_to_ns(ticks as u128, hz);
+            let fast = ticks_to_ns_fast(ticks, hz, recip);
+            if ticks >> RECIP_SHIFT != 0 {
+                assert_eq!(fast, exact);
+            } else {
+                assert!(fast <= exact && exact - fast <= 1, "{} ticks at {} Hz: {} vs {}", ticks, hz, fast, exact);
+            }
+        }
+    }
+
+    #[test]
+    fn cached_rdtscp_support_matches_cpuid() {
+        let fresh = safe_cpuid(0x8000_0001, 0).is_some_and(|r| r.edx & (1 << 27) != 0);
+        assert_eq!(rdtscp_supported(), fresh);
+        assert_eq!(rdtscp_supported(), fresh);
+    }
+
+    #[test]
+    fn reference_clock_falls_back() {
+        assert_eq!(select_reference_clock(true, true), ReferenceClock::MonotonicRaw);
+        assert_eq!(select_reference_clock(false, true), ReferenceClock::Monotonic);
+        // clock_gettime failing for both clocks leaves the wall clock
+        assert_eq!(select_reference_clock(false, false), ReferenceClock::SystemTime);
+        // what an erroring clock_gettime reports to the probe
+        #[cfg(all(not(windows), any(target_arch = "x86", target_arch = "x86_64")))]
+        assert_eq!(clock_gettime_ns(-1), None);
+    }
+
+    #[test]
+    fn chosen_backend_is_available() {
+        let available = available_backends();
+        assert!(available.contains(&backend()));
+        assert_eq!(available[0], backend());
+    }
+
+    #[test]
+    fn forced_backend_must_be_available() {
+        let _lock = test_lock();
+        let unavailable = if backend() == TimerBackend::Qpc { TimerBackend::Cntvct } else { TimerBackend::Qpc };
+        assert!(select_backend(unavailable).is_err());
+        assert_eq!(active_backend(), backend());
+
+        #[cfg(unix)]
+        {
+            select_backend(TimerBackend::Monotonic).unwrap();
+            let timer = HighResolutionTimer::start();
+            assert_eq!((active_backend(), timer.backend), (TimerBackend::Monotonic, TimerBackend::Monotonic));
+            assert_eq!(tick_hz(), 1_000_000_000);
+            std::thread::sleep(Duration::from_millis(1));
+            assert!(timer.ns() >= 1_000_000);
+            select_backend(backend()).unwrap();
+        }
+        assert_eq!(active_backend(), backend());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_monotonic_read_repeats_the_last_one() {
+        let last_good = AtomicU64::new(0);
+        assert_eq!(monotonic_or_last(Some(5_000), &last_good), 5_000);
+        assert_eq!(monotonic_or_last(None, &last_good), 5_000);
+        assert_eq!(monotonic_or_last(Some(7_000), &last_good), 7_000);
+        assert_eq!(monotonic_or_last(None, &last_good), 7_000);
+    }
+
+    #[test]
+    fn discrepancy_against_nominal() {
+        assert_eq!(discrepancy_percent(2_121_000_000.0, 2_100_000_000.0), Some(1.0));
+        assert_eq!(discrepancy_percent(2_079_000_000.0, 2_100_000_000.0), Some(-1.0));
+        assert_eq!(discrepancy_percent(2_100_000_000.0, 0.0), None);
+        assert_eq!(model_name_mhz("Intel(R) Xeon(R) Silver 4310 CPU @ 2.10GHz"), Some(2100.0));
+    }
+
+    // compiled (and run, where an i686 runner exists) only on 32-bit x86
+    #[cfg(all(not(windows), target_arch = "x86"))]
+    #[test]
+    fn i686_reads_the_full_counter() {
+        let _lock = test_lock();
+        if active_backend() != TimerBackend::Tsc {
+            return;
+        }
+        // the timer's read must land between two raw EDX:EAX reads
+        let before = unsafe { _rdtsc() };
+        let ticks = HighResolutionTimer::ticks();
+        let after = unsafe { _rdtsc() };
+        assert!(before <= ticks && ticks <= after, "{} {} {}", before, ticks, after);
+        // the TSC passes 2^32 a couple of seconds after reset, so the
+        // high half (EDX) must be there
+        assert!(ticks > u32::MAX as u64);
+    }
+
+    #[test]
+    fn cycles_is_the_exact_tick_delta() {
+        let mut timer = timer_at(1_000);
+        timer.backend = TimerBackend::Cntvct;
+        assert_eq!(timer.cycles_at(1_250), Some(250));
+        // across a counter wrap
+        timer.start_cycles = u64::MAX - 9;
+        assert_eq!(timer.cycles_at(5), Some(15));
+
+        timer.backend = TimerBackend::Tsc;
+        assert_eq!(timer.cycles_at(5), tsc_is_invariant().then_some(15));
+        for backend in [TimerBackend::Qpc, TimerBackend::Monotonic, TimerBackend::Fallback] {
+            timer.backend = backend;
+            assert_eq!(timer.cycles_at(5), None);
+        }
+    }
+
+    #[test]
+    fn implausible_calibration_is_an_error() {
+        assert_eq!(check_tick_hz(3_000_000_000), Ok(3_000_000_000));
+        assert_eq!(check_tick_hz(32_768), Err(CalibrationError::OutOfRange(32_768)));
+        assert_eq!(check_tick_hz(100_000_000_000), Err(CalibrationError::OutOfRange(100_000_000_000)));
+        assert_eq!(check_tick_hz(0), Err(CalibrationError::ZeroDelta));
+
+        let err = crate::error::TimingError::from(CalibrationError::OutOfRange(32_768));
+        assert!(err.to_string().contains("implausible tick frequency 32768 Hz"), "{}", err);
+    }
+
+    #[test]
+    fn below_one_tick_is_flagged() {
+        assert!(below_resolution(0.3, 0.4));
+        assert!(!below_resolution(0.4, 0.4));
+        assert!(!below_resolution(25.0, 0.4));
+
+        let _lock = test_lock();
+        let resolution = timer_resolution_ns();
+        assert_eq!(resolution, 1e9 / global_tick_hz() as f64);
+        assert!(is_below_resolution(resolution / 2.0));
+        assert!(!is_below_resolution(resolution * 2.0));
+    }
+
+    #[test]
+    fn stepped_window_is_retried() {
+        let _lock = test_lock();
+        let window = Duration::from_millis(20);
+        let segment_ns = window.as_nanos() as u64 / CALIBRATION_SEGMENTS as u64;
+        let mut stepped = jittered_calibration(segment_ns);
+        // the reference clock stepped back mid-segment
+        stepped.samples[3].delta_ns /= 2;
+        assert!(matches!(check_calibration(&stepped), Err(CalibrationError::Unstable(_))));
+
+        let mut windows = vec![stepped.clone(), jittered_calibration(segment_ns)].into_iter();
+        let mut measured = 0;
+        let calibration = retry_calibration(window, |_| {
+            measured += 1;
+            windows.next().expect("no more windows")
+        })
+        .unwrap();
+        assert_eq!(measured, 2);
+        assert_eq!(calibration.tick_hz, 3_000_000_000);
+
+        // every attempt stepped
+        let err = retry_calibration(window, |_| stepped.clone()).unwrap_err();
+        assert!(matches!(err, CalibrationError::Unstable(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn thread_cache_follows_recalibration() {
+        let _lock = test_lock();
+        let (earlier, later) = (timer_at(0), timer_at(3_000_000));
+        let calibrated = global_tick_hz();
+        // prime this thread's cache, then change the frequency twice
+        store_tick_hz(1_000_000_000);
+        assert_eq!(tick_hz(), 1_000_000_000);
+        assert_eq!(later.since(&earlier), 3_000_000);
+        store_tick_hz(3_000_000_000);
+        let (hz, since, duration, cycles, resolution) = (
+            tick_hz(),
+            later.since(&earlier),
+            later.checked_duration_since(&earlier),
+            ns_to_cycles(1_000.0),
+            timer_resolution_ns(),
+        );
+        store_tick_hz(calibrated);
+        assert_eq!(hz, 3_000_000_000);
+        assert_eq!(since, 1_000_000);
+        assert_eq!(duration, Some(Duration::from_millis(1)));
+        assert_eq!(cycles, 3_000.0);
+        assert!((resolution - 1.0 / 3.0).abs() < 1e-9);
+
+        let hz = recalibrate().unwrap_or_else(|_| TICK_HZ.load(Ordering::Acquire));
+        assert_eq!(tick_hz(), hz);
+        let (seen, cached_hz, recip) = LOCAL_TICK_HZ.with(Cell::get);
+        assert_eq!(seen, CALIBRATION_GENERATION.load(Ordering::Acquire));
+        assert_eq!((cached_hz, recip), (hz, TICK_RECIP.load(Ordering::Relaxed)));
+    }
+
+    #[test]
+    fn implausible_recalibration_keeps_the_frequency() {
+        let _lock = test_lock();
+        if active_backend() == TimerBackend::Monotonic {
+            return; // nothing is measured, so nothing to reject
+        }
+        let before = tick_hz();
+        let generation = CALIBRATION_GENERATION.load(Ordering::Acquire);
+        // no real counter runs at 1-2 Hz
+        set_plausible_tick_hz(1..=2).unwrap();
+        let result = recalibrate();
+        set_plausible_tick_hz(MIN_PLAUSIBLE_TICK_HZ..=MAX_PLAUSIBLE_TICK_HZ).unwrap();
+
+        assert!(matches!(result, Err(CalibrationError::OutOfRange(_))), "{:?}", result);
+        assert_eq!(tick_hz(), before);
+        assert_eq!(CALIBRATION_GENERATION.load(Ordering::Acquire), generation);
+    }
+
+    #[test]
+    fn invalid_plausible_band_is_rejected() {
+        let _lock = test_lock();
+        assert!(set_plausible_tick_hz(0..=MAX_PLAUSIBLE_TICK_HZ).is_err());
+        let (min, max) = (2_000, 1_000);
+        assert!(set_plausible_tick_hz(min..=max).is_err());
+        assert_eq!(plausible_tick_hz(), MIN_PLAUSIBLE_TICK_HZ..=MAX_PLAUSIBLE_TICK_HZ);
+        assert_eq!(set_plausible_tick_hz(5..=5), Ok(()));
+        assert_eq!(plausible_tick_hz(), 5..=5);
+        set_plausible_tick_hz(MIN_PLAUSIBLE_TICK_HZ..=MAX_PLAUSIBLE_TICK_HZ).unwrap();
+    }
+
+    #[test]
+    fn tsc_deadline_matches_the_kernel_flag() {
+        let supported: bool = tsc_deadline_supported();
+        if !cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
+            assert!(!supported);
+        }
+        // the kernel may hide the flag (errata) but never invents it
+        if let Ok(cpuinfo) = std::fs::read_to_string("/proc/cpuinfo") {
+            let flagged = cpuinfo
+                .lines()
+                .find(|line| line.starts_with("flags"))
+                .is_some_and(|flags| flags.split_whitespace().any(|f| f == "tsc_deadline_timer"));
+            assert!(!flagged || supported);
+        }
+    }
+
+    #[test]
+    fn capture_pairs_ticks_with_wall_time() {
+        let _lock = test_lock();
+        let first = HighResolutionTimer::capture();
+        std::thread::sleep(Duration::from_millis(5));
+        let second = HighResolutionTimer::capture();
+        let now_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+
+        assert!(first.ticks != 0 && second.ticks != first.ticks);
+        // after 2020 and no later than now
+        assert!((1_577_836_800_000_000_000..=now_ns).contains(&first.wall_ns), "{}", first.wall_ns);
+        assert!(first.skew_ns < 1_000_000, "{}", first.skew_ns);
+
+        // both clocks saw about the same 5 ms
+        let tick_ns = ticks_to_ns(second.ticks.wrapping_sub(first.ticks) as u128, cached_tick_hz().0) as f64;
+        let wall_ns = (second.wall_ns - first.wall_ns) as f64;
+        assert!(wall_ns >= 5e6 && (tick_ns - wall_ns).abs() < 0.1 * wall_ns + 1e6, "{} vs {}", tick_ns, wall_ns);
+    }
+
+    #[test]
+    fn override_scales_ns_and_clearing_restores_calibration() {
+        let _lock = test_lock();
+        let calibrated = tick_hz();
+        let elapsed_ns = |hz| {
+            set_tick_hz_override(Some(hz));
+            // 3e9 ticks ago, plus the few the reads take
+            let ns = timer_at(HighResolutionTimer::ticks().wrapping_sub(3_000_000_000)).ns();
+            assert_eq!((tick_hz_override(), tick_hz()), (Some(hz), hz));
+            ns
+        };
+        let at_1ghz = elapsed_ns(1_000_000_000);
+        let at_3ghz = elapsed_ns(3_000_000_000);
+        set_tick_hz_override(None);
+
+        assert!((3_000_000_000..3_010_000_000).contains(&at_1ghz), "{}", at_1ghz);
+        assert!((1_000_000_000..1_010_000_000).contains(&at_3ghz), "{}", at_3ghz);
+        assert_eq!((tick_hz_override(), tick_hz()), (None, calibrated));
+    }
+
+    #[test]
+    fn calibration_finishing_under_an_override_does_not_replace_it() {
+        let _lock = test_lock();
+        let calibrated = tick_hz();
+        set_tick_hz_override(Some(1_000_000_000));
+        // what a recalibrate that checked for an override before the
+        // override was set installs once its measurement is done
+        assert_eq!(replace_tick_hz(2_000_000_000), 1_000_000_000);
+        assert_eq!(tick_hz(), 1_000_000_000);
+        assert_eq!(recalibrate(), Ok(1_000_000_000));
+        // clearing restores the latest calibration, not the one from
+        // before the override
+        set_tick_hz_override(None);
+        assert_eq!(tick_hz(), 2_000_000_000);
+        store_tick_hz(calibrated);
+    }
+
+    #[test]
+    fn mach_timebase_scales_ticks() {
+        // Apple Silicon: 24 MHz ticks at 125/3 ns each
+        assert_eq!(mach_ticks_to_ns(24_000_000, 125, 3), 1_000_000_000);
+        assert_eq!(mach_ticks_to_ns(3, 125, 3), 125);
+        assert_eq!(mach_timebase_hz(125, 3), 24_000_000);
+        // Intel Macs: 1:1
+        assert_eq!(mach_ticks_to_ns(12_345, 1, 1), 12_345);
+        assert_eq!(mach_timebase_hz(1, 1), 1_000_000_000);
+        // no overflow on a counter near its end
+        assert_eq!(mach_ticks_to_ns(u64::MAX, 125, 3), u64::MAX as u128 * 125 / 3);
+        assert_eq!((mach_ticks_to_ns(5, 1, 0), mach_timebase_hz(0, 1)), (0, 0));
+        if !cfg!(target_os = "macos") {
+            assert_eq!(mach_timebase(), None);
+        }
+    }
+
+    #[test]
+    fn mach_frequency_converts_like_the_timebase() {
+        // the backend converts at mach_timebase_hz; that must agree with
+        // scaling by the timebase itself
+        for (numer, denom) in [(125, 3), (1, 1)] {
+            let hz = mach_timebase_hz(numer, denom);
+            for ticks in [0, 3, 24_000_000, 1 << 40, u64::MAX] {
+                assert_eq!(ticks_to_ns(ticks as u128, hz), mach_ticks_to_ns(ticks, numer, denom), "{}/{}", numer, denom);
+            }
+        }
+        if let Some((numer, denom)) = mach_timebase()
+            && active_backend() == TimerBackend::Cntvct
+        {
+            assert_eq!(calibrate_tick_hz_with_window(calibration_window()), mach_timebase_hz(numer, denom));
+        }
+    }
+
+    #[test]
+    fn preempted_window_is_retried() {
+        let _lock = test_lock();
+        let window = Duration::from_millis(20);
+        let segment_ns = window.as_nanos() as u64 / CALIBRATION_SEGMENTS as u64;
+        let mut preempted = jittered_calibration(segment_ns);
+        // one segment ran 3x its share: the same rate, just descheduled
+        let long = &mut preempted.samples[2];
+        (long.delta_ns, long.delta_ticks) = (long.delta_ns * 3, long.delta_ticks * 3);
+        assert!(check_calibration(&preempted).is_ok());
+        assert!(matches!(check_preemption(&preempted, window), Err(CalibrationError::Preempted(_))));
+        assert!(check_preemption(&jittered_calibration(segment_ns), window).is_ok());
+
+        let mut windows = vec![preempted.clone(), jittered_calibration(segment_ns)].into_iter();
+        let mut measured = 0;
+        let calibration = retry_calibration(window, |_| {
+            measured += 1;
+            windows.next().expect("no more windows")
+        })
+        .unwrap();
+        assert_eq!(measured, 2);
+        assert!(check_preemption(&calibration, window).is_ok());
+
+        // preempted every time: the last attempt is still used
+        let mut measured = 0;
+        retry_calibration(window, |_| {
+            measured += 1;
+            preempted.clone()
+        })
+        .unwrap();
+        assert_eq!(measured, CALIBRATION_ATTEMPTS);
+    }
+
+    #[test]
+    fn start_with_backend_reports_the_backend_it_reads() {
+        let _lock = test_lock();
+        let (timer, started_on) = HighResolutionTimer::start_with_backend();
+        assert_eq!((started_on, timer.backend), (active_backend(), active_backend()));
+
+        #[cfg(unix)]
+        {
+            select_backend(TimerBackend::Monotonic).unwrap();
+            let (monotonic, started_on) = HighResolutionTimer::start_with_backend();
+            let now_active = active_backend();
+            select_backend(backend()).unwrap();
+            assert_eq!((started_on, monotonic.backend), (TimerBackend::Monotonic, TimerBackend::Monotonic));
+            assert_eq!(now_active, TimerBackend::Monotonic);
+            assert!(!monotonic.is_comparable_with(&timer));
+        }
     }
-    freq
 }
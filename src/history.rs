@@ -0,0 +1,136 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::benchmark::BenchmarkResult;
+use crate::date_time_tool::{current_timestamp, iso8601_utc};
+use crate::report::tags_field;
+
+// ============================================================
+// Append-only TSV history
+// ============================================================
+
+/// Header row written when the history file is created
+pub const HISTORY_HEADER: &str = "time\tcommit\tbenchmark\tns_per_call\ttags";
+
+/// Header of history files started before `--tag`: no tags column
+pub const UNTAGGED_HISTORY_HEADER: &str = "time\tcommit\tbenchmark\tns_per_call";
+
+/// Append one row per result to the TSV at `path`.
+///
+/// The file gets a header when it is created. The commit column comes
+/// from the `GIT_COMMIT` environment variable and is empty without it;
+/// the tags column holds `tags` as `key=value;...`. A file with the
+/// `UNTAGGED_HISTORY_HEADER` keeps it: its rows get no tags column, and
+/// tags are refused rather than dropped.
+pub fn append_history(path: &Path, results: &[BenchmarkResult], tags: &[(String, String)]) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).create(true).append(true).open(path)?;
+    let mut header = String::new();
+    BufReader::new(&file).read_line(&mut header)?;
+
+    let mut out = String::new();
+    let tagged = match header.trim_end() {
+        "" => {
+            out.push_str(HISTORY_HEADER);
+            out.push('\n');
+            true
+        }
+        UNTAGGED_HISTORY_HEADER if tags.is_empty() => false,
+        UNTAGGED_HISTORY_HEADER => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the file predates --tag and has no tags column; start a new history file to record tags",
+            ))
+        }
+        _ => true,
+    };
+
+    let time = iso8601_utc(current_timestamp());
+    let commit = std::env::var("GIT_COMMIT").unwrap_or_default();
+    let tags = tsv_field(&tags_field(tags));
+    for r in results {
+        out.push_str(&format!("{}\t{}\t{}\t{:.3}", time, tsv_field(&commit), tsv_field(&r.name), r.ns_per_call()));
+        if tagged {
+            out.push('\t');
+            out.push_str(&tags);
+        }
+        out.push('\n');
+    }
+
+    file.write_all(out.as_bytes())
+}
+
+// ============================================================
+// Per-sample timeline
+// ============================================================
+
+/// Header row of a timeline file
+pub const TIMELINE_HEADER: &str = "wall_ns\tbenchmark\tns_per_call";
+
+/// Write every result's `timeline()` to the TSV at `path`, replacing
+/// it: one row per sample, wall-clock epoch ns first
+pub fn write_timeline(path: &Path, results: &[BenchmarkResult]) -> io::Result<()> {
+    let mut out = String::from(TIMELINE_HEADER);
+    out.push('\n');
+    for r in results {
+        let name = tsv_field(&r.name);
+        for (wall_ns, ns) in r.timeline() {
+            out.push_str(&format!("{}\t{}\t{:.3}\n", wall_ns, name, ns));
+        }
+    }
+    std::fs::write(path, out)
+}
+
+/// Replace characters that would break the TSV layout
+fn tsv_field(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh path in the temp dir, unique per process and `name`
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("timing-eval-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn runs_append_under_one_header() {
+        let path = temp_path("history.tsv");
+        let results = [BenchmarkResult::from_samples("instant", &[20, 22])];
+        append_history(&path, &results, &[]).unwrap();
+        append_history(&path, &results, &[]).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], HISTORY_HEADER);
+        for row in &lines[1..] {
+            let columns: Vec<&str> = row.split('\t').collect();
+            assert_eq!(columns.len(), HISTORY_HEADER.split('\t').count());
+            assert_eq!(columns[2], "instant");
+            assert_eq!(columns[3], "21.000");
+        }
+    }
+
+    #[test]
+    fn untagged_files_keep_four_columns() {
+        let path = temp_path("untagged.tsv");
+        std::fs::write(&path, format!("{}\n", UNTAGGED_HISTORY_HEADER)).unwrap();
+        let results = [BenchmarkResult::from_samples("instant", &[20, 22])];
+        append_history(&path, &results, &[]).unwrap();
+        let tags = [("host".to_string(), "ci-7".to_string())];
+        let err = append_history(&path, &results, &tags).unwrap_err();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2, "the tagged rows were refused");
+        assert_eq!(lines[1].split('\t').count(), 4);
+    }
+}
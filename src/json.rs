@@ -0,0 +1,305 @@
+// ============================================================
+// Minimal JSON reader
+// ============================================================
+// Enough to read back what `report::render_json` writes (and any
+// well-formed JSON); no dependency on serde.
+
+/// A parsed JSON value; objects keep their keys in file order
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Member `key` of an object; None for other values
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// A number, or a string holding one (how `render_json` writes
+    /// integers above 2^53 - 1)
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            JsonValue::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Parse one JSON document; errors name the byte offset
+pub fn parse_json(text: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser { bytes: text.as_bytes(), pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("invalid JSON at byte {}: {}", self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unknown literal"))
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b't') => self.literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.literal("false", JsonValue::Bool(false)),
+            Some(b'n') => self.literal("null", JsonValue::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or("");
+        text.parse().map(JsonValue::Number).map_err(|_| self.error("invalid number"))
+    }
+
+    /// A string at `pos` (which must be the opening quote), unescaped
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escaped) = self.bytes.get(self.pos) else {
+                        return Err(self.error("unterminated escape"));
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'"' | b'\\' | b'/' => out.push(escaped),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'u' => {
+                            let c = self.unicode_escape()?;
+                            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                _ => out.push(byte),
+            }
+        }
+        String::from_utf8(out).map_err(|_| self.error("string is not UTF-8"))
+    }
+
+    /// The char of a `\uXXXX` escape (after the `u`), joining a UTF-16
+    /// surrogate pair; lone surrogates become U+FFFD, and an escape
+    /// after a high surrogate that isn't a low one is left to be read
+    /// on its own
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if (0xD800..0xDC00).contains(&high) && self.bytes[self.pos..].starts_with(b"\\u") {
+            let start = self.pos;
+            self.pos += 2;
+            match self.hex4() {
+                Ok(low) if (0xDC00..0xE000).contains(&low) => {
+                    let combined = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                    return Ok(char::from_u32(combined).unwrap_or('\u{FFFD}'));
+                }
+                _ => self.pos = start,
+            }
+        }
+        Ok(char::from_u32(high).unwrap_or('\u{FFFD}'))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.bytes.get(self.pos..self.pos + 4).ok_or_else(|| self.error("short \\u escape"))?;
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err(self.error("invalid \\u escape"));
+        }
+        let value = u32::from_str_radix(std::str::from_utf8(digits).unwrap_or(""), 16)
+            .map_err(|_| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> String {
+        parse_json(text).unwrap().as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn escapes_unescape() {
+        assert_eq!(string(r#""a\"b\\c\/d""#), "a\"b\\c/d");
+        assert_eq!(string(r#""\b\f\n\r\t""#), "\u{8}\u{c}\n\r\t");
+        assert_eq!(string(r#""\u00e9 \u20AC""#), "é €");
+        // a surrogate pair joins into one char
+        assert_eq!(string(r#""\ud83d\ude00""#), "😀");
+        // lone surrogates become U+FFFD; the escape after an unpaired
+        // high surrogate is still read
+        assert_eq!(string(r#""\ud83d""#), "\u{FFFD}");
+        assert_eq!(string(r#""\ude00x""#), "\u{FFFD}x");
+        assert_eq!(string(r#""\ud83dA""#), "\u{FFFD}A");
+        assert_eq!(string(r#""\ud83d\u0041""#), "\u{FFFD}A");
+        assert_eq!(string(r#""\ud83d\ud83d\ude00""#), "\u{FFFD}😀");
+        assert_eq!(string("\"raw é\""), "raw é");
+    }
+
+    #[test]
+    fn nested_values_keep_their_order() {
+        let value = parse_json(r#" { "b": [1, {"c": null}, []], "a": {"t": true, "f": false}, "e": {} } "#).unwrap();
+        let JsonValue::Object(members) = &value else { panic!("{:?}", value) };
+        let keys: Vec<&str> = members.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["b", "a", "e"]);
+        let b = value.get("b").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(b[0], JsonValue::Number(1.0));
+        assert_eq!(b[1].get("c"), Some(&JsonValue::Null));
+        assert_eq!(b[2], JsonValue::Array(vec![]));
+        assert_eq!(value.get("a").and_then(|a| a.get("f")), Some(&JsonValue::Bool(false)));
+        assert_eq!(value.get("e"), Some(&JsonValue::Object(vec![])));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn numbers_parse() {
+        let number = |text: &str| parse_json(text).unwrap().as_f64().unwrap();
+        assert_eq!(number("0"), 0.0);
+        assert_eq!(number("-12.5"), -12.5);
+        assert_eq!(number("1e3"), 1000.0);
+        assert_eq!(number("2.5E-1"), 0.25);
+        // integers above 2^53 - 1 are written as strings
+        assert_eq!(number(r#""18446744073709551615""#), 18446744073709551615.0);
+        assert_eq!(parse_json("\"x\"").unwrap().as_f64(), None);
+    }
+
+    #[test]
+    fn malformed_json_is_rejected() {
+        for text in [
+            "", "[1,", "[1 2]", "{\"a\" 1}", "{1: 2}", "{\"a\":1,}", "tru", "nul", "1 2", "-", "1e", "\"open",
+            r#""\x""#, r#""\u12""#, r#""\u+123""#, r#""\u12g4""#,
+        ] {
+            let error = parse_json(text).expect_err(text);
+            assert!(error.starts_with("invalid JSON at byte "), "{}: {}", text, error);
+        }
+        assert_eq!(parse_json("[1] x").unwrap_err(), "invalid JSON at byte 4: trailing characters");
+    }
+}
@@ -0,0 +1,68 @@
+// always built: the timer and timestamp primitives
+pub mod date_time_tool;
+pub mod error;
+pub mod high_resolution_timer;
+pub mod stopwatch;
+pub mod trace;
+
+// the one-call entry point
+pub use date_time_tool::now_ns;
+
+// benchmarks, stats, reporting and the command line
+#[cfg(feature = "cli")]
+pub mod affinity;
+#[cfg(feature = "cli")]
+pub mod benchmark;
+#[cfg(feature = "cli")]
+pub mod catalog;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+pub mod diff;
+#[cfg(feature = "cli")]
+pub mod history;
+#[cfg(feature = "cli")]
+pub mod json;
+#[cfg(feature = "cli")]
+pub mod noise;
+#[cfg(feature = "cli")]
+pub mod plot;
+#[cfg(feature = "cli")]
+pub mod recommend;
+#[cfg(feature = "cli")]
+pub mod report;
+#[cfg(feature = "cli")]
+pub mod runner;
+#[cfg(feature = "cli")]
+pub mod signal;
+#[cfg(feature = "cli")]
+pub mod sink;
+#[cfg(feature = "cli")]
+pub mod stats;
+#[cfg(feature = "cli")]
+pub mod system_info;
+#[cfg(feature = "cli")]
+pub mod warning;
+
+// with --no-default-features only the core modules exist; this is the
+// surface an embedder gets
+#[cfg(all(test, not(feature = "cli")))]
+mod tests {
+    #[test]
+    fn core_types_without_cli() {
+        use crate::error::{CalibrationError, TimingError};
+        use crate::high_resolution_timer::HighResolutionTimer;
+        use crate::stopwatch::{PhaseTimer, StopWatch};
+
+        let timer = HighResolutionTimer::start();
+        let mut watch = StopWatch::start();
+        watch.pause();
+        let mut phases = PhaseTimer::with_capacity(2);
+        phases.mark("a");
+        phases.mark("b");
+        assert_eq!(phases.phases().len(), 1);
+        assert!(timer.ns() >= watch.elapsed_ns());
+        assert!(HighResolutionTimer::ticks() <= HighResolutionTimer::ticks());
+        assert_eq!(TimingError::from(CalibrationError::ZeroDelta).exit_code(), 3);
+    }
+}
@@ -1,74 +1,511 @@
-mod date_time_tool;
-mod system_info;
-mod high_resolution_timer;
-use crate::date_time_tool::current_timestamp;
-use crate::system_info::report_sys_info;
-use crate::high_resolution_timer::HighResolutionTimer;
-
-pub fn print_performance_stats(start_ns: u64, end_ns: u64, loop_count: u64) {
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use timing_test::affinity::{
+    current_cpu, current_numa_node, is_current_cpu_isolated, isolated_cpus, logical_cpus, online_cpus, pin_to_cpu,
+    raise_priority, raise_timer_resolution, set_realtime_priority, Priority,
+};
+use timing_test::benchmark::{detect_warmup, measure_scaling, thread_levels, DEFAULT_LOOPS};
+use timing_test::cli::parse_args_with_env;
+use timing_test::date_time_tool::{current_timestamp, monotonic_timestamp, system_time_resolution_ns};
+use timing_test::diff::{diff_results, read_results_json, render_diff};
+use timing_test::error::TimingError;
+use timing_test::system_info::{report_sys_info, version_banner};
+use timing_test::high_resolution_timer::{
+    active_backend, available_backends, aux_node, crossed_numa_node, default_calibration_cache_path, last_calibration, measure,
+    ns_to_cycles, read_tsc_aux, select_backend, set_calibration_cache, set_calibration_window, tick_hz,
+    HighResolutionTimer, TscAuxReading,
+};
+use timing_test::history::{append_history, write_timeline};
+use timing_test::noise::ResourceUsage;
+use timing_test::plot::write_plot;
+use timing_test::report::{label, print_calibration, print_clock_drift, print_cpu_sweep, print_freq_sample, print_freq_summary, print_scaling, print_validation, print_sweep, set_label_width};
+use timing_test::recommend::{clock_candidates, render_recommendation, RECOMMEND_CANDIDATES, RECOMMEND_MAX_RESOLUTION_NS};
+use timing_test::runner::{assertion_failure, monitor_frequency, run, on_measurement_thread, run_into, sweep, sweep_cpus, validate_timer, measure_clock_drift, Format, RunConfig, FREQ_MONITOR_INTERVAL};
+use timing_test::signal::{install_stop_handler, stop_requested};
+use timing_test::sink::{ResultSink, StdoutSink, VecSink};
+use timing_test::trace::{enable_trace, render_trace, take_trace};
+use timing_test::warning::{run_warnings, Warning, WarningKind};
+
+pub fn print_performance_stats(start_ns: u64, end_ns: u64, loop_count: u64) -> Result<(), TimingError> {
     if end_ns < start_ns {
-        println!("Error: end time must be after start time");
-        return;
+        return Err(TimingError::EndBeforeStart { start_ns, end_ns });
     }
     
-    let elapsed_ns = end_ns - start_ns;
+    print_performance_stats_duration(Duration::from_nanos(end_ns - start_ns), loop_count);
+    Ok(())
+}
+
+/// Same report from an elapsed `Duration` (e.g. `Instant::elapsed()`),
+/// kept in u128 ns so long runs don't truncate
+pub fn print_performance_stats_duration(elapsed: Duration, loop_count: u64) {
+    let elapsed_ns = elapsed.as_nanos();
     
-    println!("Time consumed: \t\t{} ns", elapsed_ns);
-    println!("Loop count: \t\t{}", loop_count);
+    println!("{}{} ns", label("Time consumed"), elapsed_ns);
+    println!("{}{}", label("Loop count"), loop_count);
     
-    if loop_count > 0 {
-        let ns_per_call = elapsed_ns / loop_count;
-        if ns_per_call==0 {
-             let ns_per_call = (elapsed_ns as f64 )/(loop_count as f64) ;
-             println!("Time per call: \t\t{} ns", ns_per_call);
-
-        }else{
-            println!("Time per call: \t\t{} ns", ns_per_call);
-        }
+    match time_per_call(elapsed_ns, loop_count) {
+        Some(ns_per_call) => println!("{}{} ns", label("Time per call"), ns_per_call),
+        None => println!("{}N/A (loop count is 0)", label("Time per call")),
+    }
+}
 
+/// ns per call as printed: whole ns, or fractional below 1 ns; None
+/// for a loop count of 0
+fn time_per_call(elapsed_ns: u128, loop_count: u64) -> Option<String> {
+    let ns_per_call = elapsed_ns.checked_div(loop_count as u128)?;
+    if ns_per_call == 0 {
+        Some(((elapsed_ns as f64) / (loop_count as f64)).to_string())
     } else {
-        println!("Time per call: \t\tN/A (loop count is 0)");
+        Some(ns_per_call.to_string())
     }
 }
 
-fn main() {
-    
+pub fn print_warmup(steady_after: Option<u64>) {
+    match steady_after {
+        Some(iterations) => println!("{}steady state reached after ~{} iterations", label("Warmup"), iterations),
+        None => println!("{}no steady state detected", label("Warmup")),
+    }
+}
+
+/// Run `f` `loop_count` times bracketed by `clock`.
+///
+/// Returns start, end and the last value. If the wall clock steps
+/// backwards mid-loop (NTP), the loop is retried once bracketed by the
+/// monotonic clock instead of discarding the run.
+fn bracketed_loop(clock: fn() -> u64, loop_count: u64, mut f: impl FnMut() -> u128) -> (u64, u64, u128) {
+    fn run(clock: fn() -> u64, loop_count: u64, f: &mut impl FnMut() -> u128) -> (u64, u64, u128) {
+        let start = clock();
+        let mut last = 0;
+        for _ in 0..loop_count {
+            last = f();
+        }
+        (start, clock(), last)
+    }
+
+    let (start, end, last) = run(clock, loop_count, &mut f);
+    if end >= start {
+        return (start, end, last);
+    }
+    println!("{}clock went backwards during the loop, retrying with the monotonic clock", label("Note"));
+    run(monotonic_timestamp, loop_count, &mut f)
+}
+
+/// What the measuring thread went through during `run_into`
+struct Measured {
+    elapsed: Duration,
+    /// getrusage delta
+    noise: Option<ResourceUsage>,
+    aux_before: Option<TscAuxReading>,
+    aux_after: Option<TscAuxReading>,
+}
+
+/// `run_into` on the calling thread, bracketed by the noise counters
+fn measure_all(config: &RunConfig, sink: &mut dyn ResultSink) -> Measured {
+    let before = ResourceUsage::now();
+    let aux_before = read_tsc_aux();
+    let ((), elapsed) = measure(|| run_into(config, sink));
+    let aux_after = read_tsc_aux();
+    let noise = before.zip(ResourceUsage::now()).map(|(before, after)| before.delta(&after));
+    Measured { elapsed, noise, aux_before, aux_after }
+}
+
+/// Run the configured benchmarks, print them, append to the history log
+/// and write the plot and timeline files; fails on the first result
+/// over its `--assert` limit, after reporting everything
+fn run_and_report(config: &RunConfig) -> Result<(), TimingError> {
+    let mut sink = StdoutSink::new(config.clone());
+    let Measured { elapsed, noise, aux_before, aux_after } = match config.thread_stack_mb {
+        None => measure_all(config, &mut sink),
+        Some(stack_mb) => {
+            let (results, measured) = on_measurement_thread(config, stack_mb, || {
+                let mut collected = VecSink::default();
+                let measured = measure_all(config, &mut collected);
+                (collected.results, measured)
+            })
+            .map_err(|e| TimingError::BadArguments(format!("cannot start a thread with a {} MB stack: {}", stack_mb, e)))?;
+            for result in &results {
+                sink.record(result);
+            }
+            measured
+        }
+    };
+
+    // run-wide warnings go to stderr now and into every result's output
+    let mut warnings = run_warnings();
+    if let Some((first, last)) = aux_before.zip(aux_after)
+        && crossed_numa_node(&first, &last)
+    {
+        warnings.push(Warning::new(
+            WarningKind::NumaCrossing,
+            format!(
+                "measurement moved from NUMA node {} to {} (TSC_AUX); cross-node TSC deltas are unreliable",
+                aux_node(first.aux),
+                aux_node(last.aux)
+            ),
+        ));
+    }
+    if let Some(message) = noise.as_ref().and_then(|noise| noise.noise_warning(elapsed)) {
+        warnings.push(Warning::new(WarningKind::SystemNoise, message));
+    }
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    sink.add_run_warnings(&warnings);
+    sink.finish();
+    let results = sink.results();
+
+    if let Some(noise) = noise
+        && config.format == Format::Text
+    {
+        println!("\nDuring measurement:");
+        println!(
+            "{}{} involuntary, {} voluntary",
+            label("Context switches"),
+            noise.involuntary_switches,
+            noise.voluntary_switches
+        );
+        println!("{}{} minor, {} major", label("Page faults"), noise.minor_faults, noise.major_faults);
+        let interrupts: Option<u64> = sink.results().iter().map(|result| result.interrupts).sum();
+        let iterations: u64 = sink.results().iter().map(|result| result.loops).sum();
+        if let Some(interrupts) = interrupts {
+            println!(
+                "{}{} on the measuring CPU over {} iterations, timer ticks aside",
+                label("Interrupts"),
+                interrupts,
+                iterations
+            );
+        }
+    }
+
+    if let Some(path) = &config.history
+        && let Err(err) = append_history(path, results, &config.tags)
+    {
+        eprintln!("Warning: could not append to history {}: {}", path.display(), err);
+    }
+
+    if let Some(path) = &config.timeline
+        && let Err(err) = write_timeline(path, results)
+    {
+        eprintln!("Warning: could not write timeline {}: {}", path.display(), err);
+    }
+
+    if let Some(prefix) = &config.plot {
+        match write_plot(prefix, results) {
+            Ok((_, script)) => eprintln!("Plot: run `gnuplot {}`", script.display()),
+            Err(err) => eprintln!("Warning: could not write plot {}: {}", prefix.display(), err),
+        }
+    }
+
+    if stop_requested() {
+        eprintln!("Interrupted: results above cover only what finished before the signal");
+    } else if config.past_deadline()
+        && let Some(budget) = config.max_runtime
+    {
+        eprintln!(
+            "Max runtime of {:?} reached: results above cover only what finished before it",
+            budget
+        );
+    }
+
+    match results.iter().find_map(|result| assertion_failure(config, result)) {
+        Some(failure) => Err(failure.into()),
+        None => Ok(()),
+    }
+}
+
+/// Exit code 0 on success, else the error's code (see `TimingError`)
+fn main() -> ExitCode {
+    let result = run_main();
+    // empty unless --trace enabled it
+    let trace = take_trace();
+    if !trace.is_empty() {
+        eprint!("\nTrace:\n{}", render_trace(&trace));
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            println!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// `--diff`: the two JSON files' shared benchmarks, largest regression
+/// first, then any that only one of them has
+fn print_diff(old: &Path, new: &Path, config: &RunConfig) -> Result<(), TimingError> {
+    let old_results = read_results_json(old).map_err(TimingError::BadArguments)?;
+    let new_results = read_results_json(new).map_err(TimingError::BadArguments)?;
+    let (rows, only_old, only_new) = diff_results(&old_results, &new_results);
+
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    print!("{}", render_diff(&rows, config.precision, color));
+    if !only_old.is_empty() {
+        println!("(only in {}: {})", old.display(), only_old.join(", "));
+    }
+    if !only_new.is_empty() {
+        println!("(only in {}: {})", new.display(), only_new.join(", "));
+    }
+    Ok(())
+}
+
+/// `run_warnings` on stderr, for the modes that print no results to
+/// carry them
+fn print_run_warnings() {
+    // calibrate first, so a noisy calibration is flagged too
+    tick_hz();
+    for warning in run_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+fn run_main() -> Result<(), TimingError> {
+    let config =
+        parse_args_with_env(std::env::args().skip(1), |name| std::env::var(name).ok()).map_err(TimingError::BadArguments)?;
+
+    set_label_width(config.label_width);
+    if config.trace {
+        enable_trace();
+    }
+    set_calibration_window(config.calibration_window);
+    if config.calibration_cache {
+        set_calibration_cache(default_calibration_cache_path());
+    }
+    if let Some(selected) = config.backend {
+        select_backend(selected).map_err(TimingError::BadArguments)?;
+    }
+
+    if config.version {
+        print!("{}", version_banner());
+        return Ok(());
+    }
+    if let Some((old, new)) = &config.diff {
+        return print_diff(old, new, &config);
+    }
+    if config.list_backends {
+        for available in available_backends() {
+            let selected = if available == active_backend() { " (selected)" } else { "" };
+            println!("{}\t{}{}", available.name(), available.description(), selected);
+        }
+        return Ok(());
+    }
+
+    if let Err(message) = install_stop_handler() {
+        eprintln!("Warning: {}", message);
+    }
+
+    // pin before anything calibrates so both happen on the same core
+    let node_before_pin = current_numa_node();
+    let pinned = config.cpu.and_then(|cpu| match pin_to_cpu(cpu) {
+        Ok(()) => Some(cpu),
+        Err(message) => {
+            eprintln!("Warning: CPU pinning not applied: {}", message);
+            None
+        }
+    });
+    // first-touch memory so far lives on the node we started on
+    if pinned.is_some()
+        && let (Some(before), Some(after)) = (node_before_pin, current_numa_node())
+        && before != after
+    {
+        eprintln!(
+            "Warning: pinned CPU is on NUMA node {} but startup memory may be on node {}",
+            after, before
+        );
+    }
+    if config.realtime
+        && let Err(message) = set_realtime_priority()
+    {
+        eprintln!("Warning: realtime scheduling not applied: {}", message);
+    }
+    if config.priority == Some(Priority::Realtime) {
+        eprintln!("Warning: realtime priority can starve input, disk and network threads until the run ends");
+    }
+    // restored when these drop at the end of run_main, early returns too
+    let _priority = config.priority.map(raise_priority).and_then(|raised| match raised {
+        Ok(guard) => Some(guard),
+        Err(message) => {
+            eprintln!("Warning: priority not raised: {}", message);
+            None
+        }
+    });
+    let _timer_resolution = config.high_timer_res.then(raise_timer_resolution).and_then(|raised| match raised {
+        Ok(guard) => Some(guard),
+        Err(message) => {
+            eprintln!("Warning: high timer resolution not applied: {}", message);
+            None
+        }
+    });
+    if is_current_cpu_isolated() == Some(false)
+        && let Some(isolated) = isolated_cpus().filter(|cpus| !cpus.is_empty())
+    {
+        let list: Vec<String> = isolated.iter().map(usize::to_string).collect();
+        eprintln!(
+            "Warning: running on a non-isolated CPU while CPUs {} are isolated; consider --cpu",
+            list.join(",")
+        );
+    }
+
+    if config.dump_calibration {
+        match last_calibration() {
+            Some(calibration) => print_calibration(&calibration),
+            None => println!("(no calibration was measured: cached frequency or monotonic backend)"),
+        }
+        return Ok(());
+    }
+    if let Some(duration) = config.freq_monitor {
+        print_run_warnings();
+        let mut first = None;
+        let samples = monitor_frequency(duration, FREQ_MONITOR_INTERVAL, |elapsed, outcome| match outcome {
+            Ok(hz) => print_freq_sample(elapsed, hz, *first.get_or_insert(hz)),
+            Err(err) => println!("{:>8.1} s  rejected, kept the previous frequency: {}", elapsed.as_secs_f64(), err),
+        });
+        print_freq_summary(&samples);
+        return Ok(());
+    }
+    if let Some(duration) = config.validate {
+        print_run_warnings();
+        println!("Timing {:.1} s with the timer and SystemTime...", duration.as_secs_f64());
+        let validation = validate_timer(duration);
+        print_validation(&validation);
+        if !validation.is_healthy() {
+            return Err(TimingError::Calibration(format!(
+                "timer and SystemTime disagree by {:+.4}% over {:.1} s",
+                validation.error_percent(),
+                validation.wall_ns as f64 / 1e9
+            )));
+        }
+        return Ok(());
+    }
+    if let Some(duration) = config.clock_drift {
+        print_run_warnings();
+        println!("Timing {:.1} s with CLOCK_MONOTONIC and CLOCK_MONOTONIC_RAW...", duration.as_secs_f64());
+        let drift = measure_clock_drift(duration).ok_or_else(|| {
+            TimingError::BadArguments("--clock-drift needs CLOCK_MONOTONIC and CLOCK_MONOTONIC_RAW (Linux)".to_string())
+        })?;
+        print_clock_drift(&drift);
+        return Ok(());
+    }
+    if config.recommend {
+        print_run_warnings();
+        let mut sources = config.clone();
+        sources.benchmarks = RECOMMEND_CANDIDATES.iter().map(|name| name.to_string()).collect();
+        let results = run(&sources);
+        print!("{}", render_recommendation(&clock_candidates(&results), RECOMMEND_MAX_RESOLUTION_NS));
+        return Ok(());
+    }
+    if config.sweep {
+        print_run_warnings();
+        print_sweep(&sweep(&config), &config);
+        return Ok(());
+    }
+    if config.scaling {
+        print_run_warnings();
+        let cores = logical_cpus();
+        let loops = config.loops.unwrap_or(DEFAULT_LOOPS / 10);
+        print_scaling(&measure_scaling(&thread_levels(cores), loops), &config);
+        return Ok(());
+    }
+    if config.sweep_cpus {
+        print_run_warnings();
+        print_cpu_sweep(&sweep_cpus(&config, &online_cpus()), &config);
+        return Ok(());
+    }
+
+    if config.format != Format::Text {
+        return run_and_report(&config);
+    }
+
     report_sys_info();
+    match pinned {
+        Some(cpu) => println!("{}{} (calibration and measurement)", label("Pinned CPU"), cpu),
+        None => println!("{}none (calibration and measurement may run on different cores)", label("Pinned CPU")),
+    }
     
     println!("\n---------- System call SystemTime::now() -------------\n" );
-    
-    let  start = current_timestamp();
-    let  loop_count = 10_000_000;
-    let mut last = 0;
-    for _ in 0..loop_count {
-        last = current_timestamp();
+    println!("{}{} ns", label("SystemTime resolution"), system_time_resolution_ns());
+
+    if config.detect_warmup {
+        print_warmup(detect_warmup(current_timestamp));
     }
-    let end=current_timestamp();
+    
+    let  loop_count = config.loops.unwrap_or(DEFAULT_LOOPS);
+    let (start, end, last) = bracketed_loop(current_timestamp, loop_count, || current_timestamp() as u128);
 
-    print_performance_stats(start,end,loop_count);
+    print_performance_stats(start,end,loop_count)?;
     println!("show last to prevent optimized by compiler {} \n",last);
 
     
     println!("\n---------- High Resolution Time with CPU tick-------------\n" );
 
 
-    let start = current_timestamp();
-    let loop_count = 10_000_000;
-    let tenth_of_giga = 100_000_000;
-    
     let timer = HighResolutionTimer::start();
-    let mut last = 0;
-    for _ in 0..loop_count {       
-        last = timer.ns();
+    if config.detect_warmup {
+        print_warmup(detect_warmup(|| timer.ns()));
     }
+    let (start, end, last) = bracketed_loop(current_timestamp, loop_count, || timer.ns());
     println!("show last to prevent optimized by compiler {} \n",last);
-    let end = current_timestamp();
-    print_performance_stats(start,end,loop_count);
+    print_performance_stats(start,end,loop_count)?;
+    let ns_per_call = end.saturating_sub(start) as f64 / loop_count as f64;
+    println!(
+        "{}~{:.1} (at {} MHz tick rate)",
+        label("Cycles per call"),
+        ns_to_cycles(ns_per_call),
+        tick_hz() / 1_000_000
+    );
 
 
-    println!("\n====================================================\n" );
+    println!("\n---------- Clock source comparison -------------\n" );
 
-    
+    run_and_report(&config)?;
 
+    if let Some(cpu) = pinned
+        && current_cpu().is_some_and(|now| now != cpu)
+    {
+        eprintln!("Warning: measurement finished off the pinned CPU {}", cpu);
+    }
+
+
+    println!("\n====================================================\n" );
+
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wall clock that steps back 500 ns between its first two reads
+    fn stepping_back_clock() -> u64 {
+        static READS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        match READS.fetch_add(1, std::sync::atomic::Ordering::Relaxed) {
+            0 => 1_000,
+            _ => 500,
+        }
+    }
+
+    #[test]
+    fn backward_step_retries_on_the_monotonic_clock() {
+        let mut calls = 0;
+        let (start, end, last) = bracketed_loop(stepping_back_clock, 3, || {
+            calls += 1;
+            calls
+        });
+        // the whole loop ran again, bracketed by the monotonic clock
+        assert_eq!(calls, 6);
+        assert_eq!(last, 6);
+        assert!(end >= start);
+    }
+
+    #[test]
+    fn ns_per_call_from_duration() {
+        let per_call = |elapsed: Duration, loops| time_per_call(elapsed.as_nanos(), loops);
+        assert_eq!(per_call(Duration::from_millis(1), 1_000).as_deref(), Some("1000"));
+        assert_eq!(per_call(Duration::from_nanos(500), 1_000).as_deref(), Some("0.5"));
+        // past u64 ns the u128 path still divides
+        assert_eq!(per_call(Duration::from_secs(u64::MAX), 1_000_000_000).as_deref(), Some(u64::MAX.to_string().as_str()));
+        assert_eq!(per_call(Duration::from_secs(1), 0), None);
+    }
+}
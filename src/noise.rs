@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+// ============================================================
+// OS noise during a run (getrusage)
+// ============================================================
+
+/// Involuntary context switches per second of measurement above which
+/// results are flagged as untrustworthy (at a 250 Hz tick, 50/s means
+/// one tick in five went to another task)
+pub const INVOLUNTARY_SWITCHES_PER_SEC_THRESHOLD: f64 = 50.0;
+
+/// Below this many involuntary switches a run is never flagged, so a
+/// stray preemption in a millisecond-long run doesn't look like a storm
+pub const MIN_FLAGGED_INVOLUNTARY_SWITCHES: u64 = 10;
+
+/// Major page faults (disk I/O) during a run above which its results
+/// are flagged
+pub const MAJOR_FAULT_THRESHOLD: u64 = 0;
+
+/// Scheduler and page-fault counters of this process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+impl ResourceUsage {
+    /// Current counters of the calling thread (the whole process
+    /// outside Linux); None where getrusage is unavailable (Windows)
+    pub fn now() -> Option<Self> {
+        #[cfg(unix)]
+        {
+            // per thread where possible, so helper threads (context_switch)
+            // don't count against the measuring one
+            #[cfg(target_os = "linux")]
+            let who = libc::RUSAGE_THREAD;
+            #[cfg(not(target_os = "linux"))]
+            let who = libc::RUSAGE_SELF;
+
+            let mut usage: libc::rusage = unsafe { core::mem::zeroed() };
+            if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+                return None;
+            }
+            Some(Self {
+                voluntary_switches: usage.ru_nvcsw as u64,
+                involuntary_switches: usage.ru_nivcsw as u64,
+                minor_faults: usage.ru_minflt as u64,
+                major_faults: usage.ru_majflt as u64,
+            })
+        }
+
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Counters accumulated between `self` and the `later` snapshot
+    pub fn delta(&self, later: &ResourceUsage) -> ResourceUsage {
+        ResourceUsage {
+            voluntary_switches: later.voluntary_switches.saturating_sub(self.voluntary_switches),
+            involuntary_switches: later.involuntary_switches.saturating_sub(self.involuntary_switches),
+            minor_faults: later.minor_faults.saturating_sub(self.minor_faults),
+            major_faults: later.major_faults.saturating_sub(self.major_faults),
+        }
+    }
+
+    /// Why a run that took `elapsed` with these counters shouldn't be
+    /// trusted, if it shouldn't
+    pub fn noise_warning(&self, elapsed: Duration) -> Option<String> {
+        let switches_per_sec = self.involuntary_switches as f64 / elapsed.as_secs_f64().max(1e-9);
+        if self.involuntary_switches >= MIN_FLAGGED_INVOLUNTARY_SWITCHES
+            && switches_per_sec > INVOLUNTARY_SWITCHES_PER_SEC_THRESHOLD
+        {
+            Some(format!(
+                "{} involuntary context switches during measurement ({:.0}/s > {}/s); results are noisy",
+                self.involuntary_switches, switches_per_sec, INVOLUNTARY_SWITCHES_PER_SEC_THRESHOLD
+            ))
+        } else if self.major_faults > MAJOR_FAULT_THRESHOLD {
+            Some(format!(
+                "{} major page faults during measurement; results include disk I/O",
+                self.major_faults
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+// ============================================================
+// Interrupts (/proc/interrupts)
+// ============================================================
+// An interrupt lands in whichever sample is being timed, so a NIC or
+// IPI storm can be all that p99 measures. Timer ticks are left out:
+// every run gets HZ of them a second, quiet host or not.
+
+/// Below this many interrupts a run is never flagged, however few
+/// samples it has
+pub const MIN_FLAGGED_INTERRUPTS: u64 = 20;
+
+/// Interrupts on `cpu` since boot, from `/proc/interrupts` contents:
+/// its column (found by the `CPUn` header, as offline CPUs have none)
+/// summed over every row but the timer ones (`LOC`, `arch_timer`, the
+/// legacy `timer` line). None when `cpu` has no column
+pub fn parse_cpu_interrupts(proc_interrupts: &str, cpu: usize) -> Option<u64> {
+    let mut lines = proc_interrupts.lines();
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let column = header.iter().position(|&name| name == format!("CPU{}", cpu))?;
+    let mut total = 0;
+    for line in lines {
+        let mut fields = line.split_whitespace().skip(1);
+        // rows like ERR: and MIS: have a single system-wide count
+        let counts: Vec<u64> = fields.by_ref().take(header.len()).map_while(|field| field.parse().ok()).collect();
+        if counts.len() < header.len() {
+            continue;
+        }
+        let description = fields.collect::<Vec<_>>().join(" ").to_ascii_lowercase();
+        if !description.contains("timer") {
+            total += counts[column];
+        }
+    }
+    Some(total)
+}
+
+/// Non-timer interrupts on `cpu` since boot (see
+/// `parse_cpu_interrupts`); None outside Linux or without /proc
+pub fn cpu_interrupts(cpu: usize) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        parse_cpu_interrupts(&std::fs::read_to_string("/proc/interrupts").ok()?, cpu)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = cpu;
+        None
+    }
+}
+
+/// Why `interrupts` on the measuring CPU over `iterations` timed calls
+/// may explain the tail, if they may: when there were at least as many
+/// as the `tail_samples` above p99 (each spoils at most the one sample
+/// it lands in) and at least MIN_FLAGGED_INTERRUPTS
+pub fn interrupt_warning(interrupts: u64, iterations: u64, tail_samples: usize) -> Option<String> {
+    (interrupts >= MIN_FLAGGED_INTERRUPTS && interrupts >= tail_samples as u64).then(|| {
+        format!(
+            "{} non-timer interrupts on the measuring CPU over {} iterations (1 per {:.0}), enough for all {} \
+             samples above p99; the tail may be IRQs, not the code",
+            interrupts,
+            iterations,
+            iterations as f64 / interrupts as f64,
+            tail_samples
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rusage_delta() {
+        let before = ResourceUsage { voluntary_switches: 10, involuntary_switches: 4, minor_faults: 100, major_faults: 1 };
+        let after = ResourceUsage { voluntary_switches: 12, involuntary_switches: 40, minor_faults: 150, major_faults: 1 };
+        let delta = before.delta(&after);
+        assert_eq!(
+            delta,
+            ResourceUsage { voluntary_switches: 2, involuntary_switches: 36, minor_faults: 50, major_faults: 0 }
+        );
+        // counters never run backwards into a huge delta
+        assert_eq!(after.delta(&before), ResourceUsage::default());
+
+        assert!(delta.noise_warning(Duration::from_millis(100)).is_some());
+        assert!(delta.noise_warning(Duration::from_secs(10)).is_none());
+    }
+
+    #[test]
+    fn stray_preemption_in_a_short_run_is_not_flagged() {
+        let stray = ResourceUsage { involuntary_switches: MIN_FLAGGED_INVOLUNTARY_SWITCHES - 1, ..Default::default() };
+        // far above the rate threshold, but too few to mean anything
+        assert!(stray.noise_warning(Duration::from_millis(1)).is_none());
+        let storm = ResourceUsage { involuntary_switches: MIN_FLAGGED_INVOLUNTARY_SWITCHES, ..stray };
+        assert!(storm.noise_warning(Duration::from_millis(1)).is_some());
+    }
+
+    #[test]
+    fn interrupts_from_the_cpu_column_without_timer_rows() {
+        let interrupts = |nic: u64| {
+            format!(
+                "           CPU0       CPU2\n\
+                 \x20 0:         36          0   IO-APIC   2-edge      timer\n\
+                 \x2036:       {}         7   PCI-MSIX-0000:00:02.0   1-edge      eth0-rx\n\
+                 \x2011:      9000       9000     GICv3  27 Level     arch_timer\n\
+                 LOC:    2413396    2413000   Local timer interrupts\n\
+                 RES:          5          3   Rescheduling interrupts\n\
+                 ERR:          0\n",
+                nic
+            )
+        };
+        let before = parse_cpu_interrupts(&interrupts(100), 0).unwrap();
+        let after = parse_cpu_interrupts(&interrupts(500), 0).unwrap();
+        assert_eq!((before, after), (105, 505));
+        // CPU2 is the second column; CPU1 is offline
+        assert_eq!(parse_cpu_interrupts(&interrupts(100), 2), Some(10));
+        assert_eq!(parse_cpu_interrupts(&interrupts(100), 1), None);
+
+        // 400 interrupts over 1M iterations: one per 2500
+        let warning = interrupt_warning(after - before, 1_000_000, 10).unwrap();
+        assert!(warning.starts_with("400 non-timer interrupts") && warning.contains("(1 per 2500)"), "{}", warning);
+        // fewer interrupts than tail samples, or too few to mean anything
+        assert_eq!(interrupt_warning(400, 1_000_000, 1_000), None);
+        assert_eq!(interrupt_warning(MIN_FLAGGED_INTERRUPTS - 1, 1_000_000, 0), None);
+    }
+}
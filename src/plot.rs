@@ -0,0 +1,99 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::benchmark::BenchmarkResult;
+
+// ============================================================
+// gnuplot export
+// ============================================================
+
+/// Write `<prefix>.dat` with every result's per-sample ns/call and a
+/// `<prefix>.gnuplot` script that renders it to `<prefix>.png`.
+///
+/// Each result is one data block (sample index, ns), so warmup ramps
+/// and periodic spikes show up as the curve over the run. Returns the
+/// data and script paths.
+pub fn write_plot(prefix: &Path, results: &[BenchmarkResult]) -> io::Result<(PathBuf, PathBuf)> {
+    let data_path = with_suffix(prefix, ".dat");
+    let script_path = with_suffix(prefix, ".gnuplot");
+
+    let mut data = String::new();
+    for (block, r) in results.iter().enumerate() {
+        // gnuplot separates `index` blocks with two blank lines
+        if block > 0 {
+            data.push_str("\n\n");
+        }
+        data.push_str(&format!("# {}: {}\n# sample\tns_per_call\n", r.name, r.label.replace('\n', " ")));
+        for (i, ns) in r.samples.iter().enumerate() {
+            data.push_str(&format!("{}\t{:.3}\n", i, ns));
+        }
+    }
+
+    let data_name = gnuplot_string(&data_path.display().to_string());
+    let mut script = format!(
+        "set terminal png size 1200,600\n\
+         set output {}\n\
+         set title \"Per-call latency by sample\"\n\
+         set xlabel \"sample (batch)\"\n\
+         set ylabel \"ns per call\"\n\
+         set key outside right\n",
+        gnuplot_string(&with_suffix(prefix, ".png").display().to_string())
+    );
+    let plots: Vec<String> = results
+        .iter()
+        .enumerate()
+        .map(|(block, r)| {
+            format!("{} index {} with lines title {} noenhanced", data_name, block, gnuplot_string(&r.name))
+        })
+        .collect();
+    if !plots.is_empty() {
+        script.push_str(&format!("plot {}\n", plots.join(", \\\n     ")));
+    }
+
+    std::fs::write(&data_path, data)?;
+    std::fs::write(&script_path, script)?;
+    Ok((data_path, script_path))
+}
+
+/// `prefix` with `suffix` appended (not an extension swap, so dots in
+/// the prefix survive)
+fn with_suffix(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(prefix.as_os_str());
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Double-quoted gnuplot string literal
+fn gnuplot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_data_and_script() {
+        let prefix = std::env::temp_dir().join(format!("timing-eval-test-{}-plot.v1", std::process::id()));
+        let results = vec![
+            BenchmarkResult::from_samples("instant", &[20, 22, 24]),
+            BenchmarkResult::from_samples("say \"ticks\"", &[8, 9]),
+        ];
+        let (data_path, script_path) = write_plot(&prefix, &results).unwrap();
+        assert!(data_path.to_string_lossy().ends_with("plot.v1.dat"));
+        let data = std::fs::read_to_string(&data_path).unwrap();
+        let script = std::fs::read_to_string(&script_path).unwrap();
+        let _ = std::fs::remove_file(&data_path);
+        let _ = std::fs::remove_file(&script_path);
+
+        // two header lines and one line per sample per block, plus the
+        // two blank lines between the blocks
+        assert_eq!(data.lines().count(), (2 + 3) + 2 + (2 + 2));
+        assert_eq!(data.lines().filter(|l| !l.is_empty() && !l.starts_with('#')).count(), 5);
+        assert!(data.contains("\n\n\n# say \"ticks\""));
+        // six settings lines and the plot command, one line per result
+        assert_eq!(script.lines().count(), 6 + 2);
+        assert!(script.contains("index 1 with lines title \"say \\\"ticks\\\"\""));
+    }
+}
@@ -0,0 +1,175 @@
+use std::time::Instant;
+
+use crate::benchmark::BenchmarkResult;
+use crate::date_time_tool::{clock_step_ns, system_time_resolution_ns};
+use crate::high_resolution_timer::{timer_resolution_ns, HighResolutionTimer};
+use crate::report::{format_ns_compact, render_table};
+
+// ============================================================
+// Clock recommendation (--recommend)
+// ============================================================
+// The cheapest clock that can still tell apart nanosecond-scale
+// intervals: a fast but coarse clock (SystemTime on a 15.6 ms tick) is
+// useless for fine timing however cheap it is to read.
+
+/// Clock sources that can time an interval on their own, by benchmark
+/// name (`ticks` needs the conversion `hrt` includes)
+pub const RECOMMEND_CANDIDATES: &[&str] = &[
+    "systemtime",
+    "instant",
+    "hrt",
+    #[cfg(unix)]
+    "clock_gettime",
+];
+
+/// The wall clock: measured as the comparison but never recommended,
+/// as NTP and manual changes can step it mid-interval
+pub const REFERENCE_CLOCK: &str = "systemtime";
+
+/// A clock must resolve steps finer than this (ns) to be recommended.
+/// The observed step of a fine clock is really its read cost (tens of
+/// ns), so this only rules out microsecond and timer-tick clocks.
+pub const RECOMMEND_MAX_RESOLUTION_NS: f64 = 1_000.0;
+
+/// One clock source's read cost and resolution
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockCandidate {
+    pub name: String,
+    pub label: String,
+    pub ns_per_call: f64,
+    pub resolution_ns: f64,
+}
+
+/// Resolution of a `RECOMMEND_CANDIDATES` clock: the tick for `hrt`,
+/// the smallest observed step for the others (takes up to a second each
+/// for coarse clocks)
+pub fn clock_resolution_ns(name: &str) -> Option<f64> {
+    match name {
+        "hrt" => Some(timer_resolution_ns()),
+        "systemtime" => Some(system_time_resolution_ns() as f64),
+        "instant" => {
+            let base = Instant::now();
+            Some(clock_step_ns(|| base.elapsed().as_nanos() as u64) as f64)
+        }
+        #[cfg(unix)]
+        "clock_gettime" => Some(clock_step_ns(|| {
+            let ts = crate::benchmark::clock_gettime_monotonic();
+            ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+        }) as f64),
+        _ => None,
+    }
+}
+
+/// Candidates for the `RECOMMEND_CANDIDATES` among `results`, with
+/// their resolutions measured now
+pub fn clock_candidates(results: &[BenchmarkResult]) -> Vec<ClockCandidate> {
+    // calibrate before any step measurement, not during one
+    let _ = HighResolutionTimer::start();
+    results
+        .iter()
+        .filter_map(|result| {
+            Some(ClockCandidate {
+                resolution_ns: clock_resolution_ns(&result.name)?,
+                name: result.name.clone(),
+                label: result.label.clone(),
+                ns_per_call: result.ns_per_call(),
+            })
+        })
+        .collect()
+}
+
+/// The cheapest monotonic candidate (not `REFERENCE_CLOCK`) with a
+/// resolution finer than `max_resolution_ns`, or the finest one if
+/// none is that fine
+pub fn recommend(candidates: &[ClockCandidate], max_resolution_ns: f64) -> Option<&ClockCandidate> {
+    let monotonic = || candidates.iter().filter(|c| c.name != REFERENCE_CLOCK);
+    monotonic()
+        .filter(|c| c.resolution_ns < max_resolution_ns)
+        .min_by(|a, b| a.ns_per_call.total_cmp(&b.ns_per_call))
+        .or_else(|| monotonic().min_by(|a, b| a.resolution_ns.total_cmp(&b.resolution_ns)))
+}
+
+/// The candidates table and the advice line, comparing the pick with
+/// `REFERENCE_CLOCK` (or the slowest other candidate)
+pub fn render_recommendation(candidates: &[ClockCandidate], max_resolution_ns: f64) -> String {
+    let rows: Vec<Vec<String>> = candidates
+        .iter()
+        .map(|c| {
+            let fine = if c.resolution_ns < max_resolution_ns { "yes" } else { "no" };
+            vec![
+                c.label.clone(),
+                format_ns_compact(c.ns_per_call),
+                format_ns_compact(c.resolution_ns),
+                fine.to_string(),
+            ]
+        })
+        .collect();
+    let mut out = render_table(&["Clock", "cost", "resolution", "fine"], &rows);
+
+    let Some(pick) = recommend(candidates, max_resolution_ns) else {
+        out.push_str("(no clock source results to recommend from)\n");
+        return out;
+    };
+    out.push_str(&format!("\nFor timing on this machine, use: {} ({})", pick.label, format_ns_compact(pick.ns_per_call)));
+    let reference = candidates
+        .iter()
+        .find(|c| c.name == REFERENCE_CLOCK)
+        .or_else(|| {
+            candidates
+                .iter()
+                .filter(|c| c.name != pick.name)
+                .max_by(|a, b| a.ns_per_call.total_cmp(&b.ns_per_call))
+        });
+    if let Some(reference) = reference
+        && pick.ns_per_call > 0.0
+    {
+        let ratio = reference.ns_per_call / pick.ns_per_call;
+        if ratio >= 1.0 {
+            out.push_str(&format!(" \u{2014} {:.1}x faster than {}", ratio, reference.label));
+        } else {
+            out.push_str(&format!(" \u{2014} {:.1}x the cost of {}", 1.0 / ratio, reference.label));
+        }
+        if reference.resolution_ns >= max_resolution_ns {
+            out.push_str(&format!(", which only resolves {}", format_ns_compact(reference.resolution_ns)));
+        }
+    }
+    if pick.resolution_ns >= max_resolution_ns {
+        out.push_str(&format!("\n(no clock here resolves below {} ns; this is the finest)", max_resolution_ns));
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, ns_per_call: f64, resolution_ns: f64) -> ClockCandidate {
+        ClockCandidate { name: name.to_string(), label: name.to_string(), ns_per_call, resolution_ns }
+    }
+
+    #[test]
+    fn recommends_the_cheapest_fine_clock() {
+        let candidates = [
+            // cheapest overall, but the wall clock is never picked
+            candidate("systemtime", 5.0, 20.0),
+            // cheap but coarse
+            candidate("coarse", 8.0, 4_000_000.0),
+            candidate("instant", 25.0, 25.0),
+            candidate("hrt", 12.0, 0.4),
+        ];
+        let pick = |max| recommend(&candidates, max).map(|c| c.name.as_str());
+        assert_eq!(pick(RECOMMEND_MAX_RESOLUTION_NS), Some("hrt"));
+        // hrt stays the pick at the tightest bound it meets...
+        assert_eq!(pick(1.0), Some("hrt"));
+        // ...and coarse wins once anything goes
+        assert_eq!(pick(f64::INFINITY), Some("coarse"));
+        // nothing fine enough: the finest instead
+        assert_eq!(pick(0.1), Some("hrt"));
+        assert_eq!(recommend(&candidates[..2], 1.0).map(|c| c.name.as_str()), Some("coarse"));
+        assert_eq!(recommend(&[], 1.0), None);
+
+        let text = render_recommendation(&candidates, RECOMMEND_MAX_RESOLUTION_NS);
+        assert!(text.contains("use: hrt"), "{}", text);
+    }
+}
@@ -0,0 +1,1129 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::affinity::logical_cpus;
+use crate::benchmark::{clock_read_backend, BenchmarkResult, ScalingPoint, SweepResult, SCALING_CONTENTION_RATIO};
+use crate::high_resolution_timer::{
+    cpu_brand, is_below_resolution, is_suspicious_clock_read, timer_resolution_ns, typical_clock_read_ns, Calibration,
+};
+use crate::runner::{
+    ClockDrift, CpuRun, Format, RunConfig, TimerValidation, Units, CLOCK_DRIFT_NEGLIGIBLE_PPM, VALIDATE_TOLERANCE_PERCENT,
+};
+use crate::stats::{confidence_interval_95, histogram, median_confidence_interval, significant_digits, HistogramScale};
+use crate::system_info::{environment_snapshot, identity, EnvironmentSnapshot, Identity};
+use crate::warning::Warning;
+
+/// Print results in the format chosen by `config`
+pub fn print_results(results: &[BenchmarkResult], config: &RunConfig) {
+    match config.format {
+        Format::Auto | Format::Text => print_comparison_table(results, config),
+        Format::Json => println!("{}", render_json(results, config)),
+        Format::Csv => print!("{}", render_csv(results, config)),
+        Format::Prometheus => print!("{}", render_prometheus(results, config)),
+        Format::GoBench => print!("{}", render_gobench(results, config)),
+        Format::Bmf => println!("{}", render_bmf(results, config)),
+        Format::Oneline => println!("{}", render_oneline(results, cpu_brand().as_deref(), config)),
+    }
+}
+
+/// Print results as a ranked table (expects results sorted by cost)
+pub fn print_comparison_table(results: &[BenchmarkResult], config: &RunConfig) {
+    let p = config.precision;
+    let relative = config
+        .relative_to
+        .as_deref()
+        .and_then(|baseline| Some((baseline, normalize_to(results, baseline)?)));
+
+    let units = config.units.unwrap_or(Units::Ns);
+    let cost_header = match (&relative, units) {
+        (Some((baseline, _)), _) => format!("vs {}", baseline),
+        (None, Units::Ns) => "ns/call".to_string(),
+        (None, Units::Us) => "us/call".to_string(),
+        (None, Units::Auto) => "cost/call".to_string(),
+    };
+    let headers = ["Rank", &cost_header, "ops/s", "trimmed", "p50", "p99", "first call", "Benchmark"];
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .enumerate()
+        .map(|(rank, result)| {
+            let cost = match &relative {
+                Some((_, ratios)) => format!("{:.2}x", ratios[rank]),
+                None => format_cost(result.ns_per_call(), units, headline_precision(result, units, p)),
+            };
+            vec![
+                (rank + 1).to_string(),
+                cost,
+                format_ops(result.ops_per_sec()),
+                format_cost(result.trimmed_mean(config.trim_percent), units, p),
+                format_cost(result.stats.p50, units, p),
+                if result.percentiles_reliable(config.min_samples) {
+                    format_cost(result.stats.p99, units, p)
+                } else {
+                    format!("{}?", format_cost(result.stats.p99, units, p))
+                },
+                result.first_call_ns.map_or("-".to_string(), |ns| ns.to_string()),
+                annotated_label(result),
+            ]
+        })
+        .collect();
+    print!("{}", render_table(&headers, &rows));
+    let migrated: Vec<String> = results
+        .iter()
+        .filter(|r| r.migrations > 0)
+        .map(|r| format!("{} {}", r.name, r.migrations))
+        .collect();
+    if !migrated.is_empty() {
+        println!("(core migrations during measurement: {}; consider --cpu)", migrated.join(", "));
+    }
+    if let Some(baseline) = &config.relative_to
+        && relative.is_none()
+    {
+        println!("(no result for {} to compare against; showing ns/call)", baseline);
+    }
+    println!("(trimmed: mean without the slowest {}% of samples)", config.trim_percent);
+    println!("(first call: one cold call before warmup, ns, includes one timer read)");
+    if results.iter().any(|r| !r.percentiles_reliable(config.min_samples)) {
+        println!("(p99?: fewer than {} samples, too few for a meaningful p99)", config.min_samples);
+    }
+
+    // what most Rust code uses vs the raw tick read this crate is about
+    if let Some(ratio) = relative_cost(results, "instant", "hrt") {
+        println!("\n{}{:.2}x the cost per call", label("Instant::now() vs HRT"), ratio);
+    }
+    if let Some(line) = tags_line(&config.tags) {
+        println!("\n{}", line);
+    }
+
+    let spec = &config.histogram;
+    let scale = match spec.scale {
+        HistogramScale::Log => "log-scale",
+        HistogramScale::Linear => "linear",
+    };
+    match spec.max {
+        Some(max) => println!("\nDistribution ({}, last bucket >= {} ns; min..max ns):", scale, max),
+        None => println!("\nDistribution ({}, min..max ns):", scale),
+    }
+    for result in results {
+        println!(
+            "{}\t{:.p$}..{:.p$}\t{}",
+            sparkline(&histogram(&result.samples, spec)),
+            result.stats.min,
+            result.stats.max,
+            result.label
+        );
+    }
+}
+
+/// Label plus a note when a run's clocks disagreed, the cost is below
+/// one tick or far outside the usual band for the clock it reads
+fn annotated_label(result: &BenchmarkResult) -> String {
+    let ns = result.ns_per_call();
+    if result.clock_anomaly {
+        return format!("{} (clock anomaly: timer and wall clock disagree)", result.label);
+    }
+    if is_below_resolution(ns) {
+        return format!("{} (below timer resolution)", result.label);
+    }
+    match clock_read_backend(&result.name) {
+        Some(backend) if is_suspicious_clock_read(backend, ns) => {
+            let (low, high) = typical_clock_read_ns(backend);
+            format!("{} (suspicious: {} reads usually take {}-{} ns)", result.label, backend.name(), low, high)
+        }
+        _ => result.label.clone(),
+    }
+}
+
+/// `precision`, lowered to the decimals `significant_digits` allows for
+/// this result's timer resolution, batch size and sample count (in the
+/// unit `format_cost` will print it in). Only the text table uses it;
+/// the other formats print `precision` decimals as given
+pub fn headline_precision(result: &BenchmarkResult, units: Units, precision: usize) -> usize {
+    let ns = result.ns_per_call();
+    let decimals = significant_digits(timer_resolution_ns(), result.batch_size(), result.samples.len()) as usize;
+    let unit_exponent = match units {
+        Units::Ns => 0,
+        Units::Us => 3,
+        Units::Auto => match ns.abs() {
+            n if n >= 1e9 => 9,
+            n if n >= 1e6 => 6,
+            n if n >= 1e3 => 3,
+            _ => 0,
+        },
+    };
+    precision.min(decimals + unit_exponent)
+}
+
+/// A per-call cost in `units` with `precision` decimals: a bare number
+/// for `Ns` and `Us` (the column header names the unit), a suffixed
+/// one for `Auto`, e.g. `4.230ns`, `1.250us`, `3.100ms`
+pub fn format_cost(ns: f64, units: Units, precision: usize) -> String {
+    let p = precision;
+    match units {
+        Units::Ns => format!("{:.p$}", ns),
+        Units::Us => format!("{:.p$}", ns / 1e3),
+        Units::Auto => match ns.abs() {
+            n if n >= 1e9 => format!("{:.p$}s", ns / 1e9),
+            n if n >= 1e6 => format!("{:.p$}ms", ns / 1e6),
+            n if n >= 1e3 => format!("{:.p$}us", ns / 1e3),
+            _ => format!("{:.p$}ns", ns),
+        },
+    }
+}
+
+/// Calls per second with a K/M/G suffix, e.g. `35.2M`; `inf` at 0 ns
+pub fn format_ops(ops: f64) -> String {
+    if !ops.is_finite() {
+        return "inf".to_string();
+    }
+    match ops {
+        o if o >= 1e9 => format!("{:.1}G", o / 1e9),
+        o if o >= 1e6 => format!("{:.1}M", o / 1e6),
+        o if o >= 1e3 => format!("{:.1}K", o / 1e3),
+        o => format!("{:.1}", o),
+    }
+}
+
+/// Print each calibration segment's counts and rate, then the chosen
+/// frequency and its error, to audit a surprising calibration
+pub fn print_calibration(calibration: &Calibration) {
+    print!("{}", render_calibration(calibration));
+}
+
+/// `print_calibration` output
+pub fn render_calibration(calibration: &Calibration) -> String {
+    let rows: Vec<Vec<String>> = calibration
+        .samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            vec![
+                (i + 1).to_string(),
+                s.delta_ticks.to_string(),
+                s.delta_ns.to_string(),
+                format!("{:.0}", s.hz()),
+            ]
+        })
+        .collect();
+    let mut out = if rows.is_empty() {
+        "(no segments: the frequency is reported by hardware, not measured)\n".to_string()
+    } else {
+        render_table(&["Segment", "delta_ticks", "delta_ns", "Hz"], &rows)
+    };
+    out.push_str(&format!("{}{} Hz\n", label("Tick frequency"), calibration.tick_hz));
+    match (calibration.rse(), calibration.error_ppm()) {
+        (Some(rse), Some(ppm)) => out.push_str(&format!("{}{:.3e} ({:.1} ppm)\n", label("RSE"), rse, ppm)),
+        _ => out.push_str(&format!("{}N/A (fewer than two segments)\n", label("RSE"))),
+    }
+    out
+}
+
+/// One `--freq-monitor` line: when, the frequency and its offset from
+/// the first sample
+pub fn print_freq_sample(elapsed: Duration, hz: u64, first_hz: u64) {
+    let ppm = (hz as f64 - first_hz as f64) / first_hz.max(1) as f64 * 1e6;
+    println!("{:>8.1} s  {:>12} Hz  {:+10.1} ppm", elapsed.as_secs_f64(), hz, ppm);
+}
+
+/// Min, max and spread of `--freq-monitor` frequencies
+pub fn print_freq_summary(samples: &[u64]) {
+    let (Some(&min), Some(&max)) = (samples.iter().min(), samples.iter().max()) else {
+        println!("(no frequency samples)");
+        return;
+    };
+    println!("{}{}", label("Samples"), samples.len());
+    println!("{}{} Hz", label("Min"), min);
+    println!("{}{} Hz", label("Max"), max);
+    println!("{}{} Hz ({:.1} ppm)", label("Spread"), max - min, (max - min) as f64 / min.max(1) as f64 * 1e6);
+}
+
+/// Both elapsed times of a `--validate` interval, their error and
+/// whether it is within `VALIDATE_TOLERANCE_PERCENT`
+pub fn print_validation(validation: &TimerValidation) {
+    println!("{}{} ns", label("Timer elapsed"), validation.timer_ns);
+    println!("{}{} ns", label("SystemTime elapsed"), validation.wall_ns);
+    println!("{}{:+.4}%", label("Error"), validation.error_percent());
+    let verdict = if validation.is_healthy() { "ok" } else { "calibration looks wrong" };
+    println!("{}{} (tolerance {}%)", label("Verdict"), verdict, VALIDATE_TOLERANCE_PERCENT);
+}
+
+/// Both elapsed times of a `--clock-drift` interval, their drift and
+/// whether calibrating against the raw clock makes a difference
+pub fn print_clock_drift(drift: &ClockDrift) {
+    println!("{}{} ns", label("MONOTONIC elapsed"), drift.monotonic_ns);
+    println!("{}{} ns", label("MONOTONIC_RAW elapsed"), drift.raw_ns);
+    println!("{}{:+.3} ppm", label("Drift"), drift.drift_ppm());
+    let verdict = if drift.is_negligible() {
+        "negligible: CLOCK_MONOTONIC would calibrate as well here"
+    } else {
+        "NTP is slewing CLOCK_MONOTONIC; calibrating against _RAW avoids that error"
+    };
+    println!("{}{} (below {} ppm is negligible)", label("Verdict"), verdict, CLOCK_DRIFT_NEGLIGIBLE_PPM);
+}
+
+/// Print sweep fits: slope as the per-call cost, intercept as the fixed
+/// cost of one timed block
+pub fn print_sweep(results: &[SweepResult], config: &RunConfig) {
+    let p = config.precision;
+    let headers = ["ns/call (slope)", "fixed ns", "counts", "Benchmark"];
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|r| {
+            let (slope, intercept) = match r.fit {
+                Some(fit) => (format!("{:.p$}", fit.slope), format!("{:.p$}", fit.intercept)),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            let counts: Vec<String> = r.points.iter().map(|(n, _)| n.to_string()).collect();
+            vec![slope, intercept, counts.join(","), r.label.clone()]
+        })
+        .collect();
+    print!("{}", render_table(&headers, &rows));
+    println!("(least-squares fit of total ns = fixed + slope * iterations)");
+}
+
+/// Print clock-read cost per thread count, flagging contended levels
+pub fn print_scaling(points: &[ScalingPoint], config: &RunConfig) {
+    let p = config.precision;
+    let baseline = points.first().map_or(0.0, |point| point.ns_per_call);
+    let rows: Vec<Vec<String>> = points
+        .iter()
+        .map(|point| {
+            let relative = if baseline > 0.0 { point.ns_per_call / baseline } else { 0.0 };
+            vec![
+                point.threads.to_string(),
+                format!("{:.p$}", point.ns_per_call),
+                format!("{:.2}x", relative),
+                if point.contended { "contention".to_string() } else { String::new() },
+            ]
+        })
+        .collect();
+    print!("{}", render_table(&["Threads", "ns/call", "vs 1", ""], &rows));
+    println!(
+        "(HighResolutionTimer::ns() read concurrently; contention = over {}x the single-thread cost)",
+        SCALING_CONTENTION_RATIO
+    );
+}
+
+/// Print ns/call per CPU (rows) and benchmark (columns)
+pub fn print_cpu_sweep(runs: &[CpuRun], config: &RunConfig) {
+    let p = config.precision;
+    // results are ranked per run, so fix the column order once
+    let names: Vec<&str> = runs
+        .iter()
+        .find_map(|(_, results)| results.as_ref().ok())
+        .map_or(Vec::new(), |results| results.iter().map(|r| r.name.as_str()).collect());
+
+    let mut headers = vec!["CPU"];
+    headers.extend(&names);
+    let rows: Vec<Vec<String>> = runs
+        .iter()
+        .map(|(cpu, results)| {
+            let mut row = vec![cpu.to_string()];
+            match results {
+                Ok(results) => row.extend(names.iter().map(|name| {
+                    results
+                        .iter()
+                        .find(|r| r.name == *name)
+                        .map_or("-".to_string(), |r| format!("{:.p$}", r.ns_per_call()))
+                })),
+                Err(message) => row.push(format!("skipped: {}", message)),
+            }
+            row
+        })
+        .collect();
+    print!("{}", render_table(&headers, &rows));
+    println!("(ns/call with the thread pinned to each CPU)");
+}
+
+// --------------------------
+// Label alignment
+// --------------------------
+
+/// Column where values start in `Label: value` lines
+pub const DEFAULT_LABEL_WIDTH: usize = 24;
+
+static LABEL_WIDTH: AtomicUsize = AtomicUsize::new(DEFAULT_LABEL_WIDTH);
+
+/// Set the value column used by `label()`
+pub fn set_label_width(width: usize) {
+    LABEL_WIDTH.store(width, Ordering::Relaxed);
+}
+
+/// `name:` padded to the configured label width, for `Label: value`
+/// lines whose values line up whatever the label length
+pub fn label(name: &str) -> String {
+    pad_label(name, LABEL_WIDTH.load(Ordering::Relaxed))
+}
+
+/// `name:` left-aligned in `width` columns, always followed by at
+/// least one space
+pub fn pad_label(name: &str, width: usize) -> String {
+    let name = format!("{}:", name);
+    let width = width.max(name.chars().count() + 1);
+    format!("{:<width$}", name, width = width)
+}
+
+/// Space-padded table with every column as wide as its widest cell.
+///
+/// Columns are separated by two spaces; the last one is not padded.
+/// Rows shorter than `headers` leave the missing cells blank.
+pub fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let render_row = |cells: Vec<&str>| {
+        let last = widths.len().saturating_sub(1);
+        let mut line = String::new();
+        for (i, width) in widths.iter().enumerate() {
+            let cell = cells.get(i).copied().unwrap_or("");
+            if i == last {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+        }
+        line.truncate(line.trim_end().len());
+        line.push('\n');
+        line
+    };
+
+    let mut out = render_row(headers.to_vec());
+    for row in rows {
+        out.push_str(&render_row(row.iter().map(String::as_str).collect()));
+    }
+    out
+}
+
+/// Every result's ns/call divided by that of `baseline` (in result
+/// order), or None if `baseline` has no nonzero result
+pub fn normalize_to(results: &[BenchmarkResult], baseline: &str) -> Option<Vec<f64>> {
+    let base = results
+        .iter()
+        .find(|r| r.name == baseline)
+        .map(|r| r.ns_per_call())
+        .filter(|ns| *ns > 0.0)?;
+    Some(results.iter().map(|r| r.ns_per_call() / base).collect())
+}
+
+/// ns/call of benchmark `name` divided by that of `baseline`, when
+/// both were run
+pub fn relative_cost(results: &[BenchmarkResult], name: &str, baseline: &str) -> Option<f64> {
+    let cost = |n: &str| results.iter().find(|r| r.name == n).map(|r| r.ns_per_call());
+    let baseline = cost(baseline).filter(|ns| *ns > 0.0)?;
+    Some(cost(name)? / baseline)
+}
+
+/// One-line Unicode sparkline of histogram bucket counts.
+///
+/// Heights are relative to the tallest bucket; empty buckets render
+/// as a space.
+pub fn sparkline(buckets: &[u64]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let tallest = buckets.iter().copied().max().unwrap_or(0);
+    buckets
+        .iter()
+        .map(|&count| {
+            if count == 0 {
+                ' '
+            } else {
+                let level = (count * BLOCKS.len() as u64).div_ceil(tallest) as usize;
+                BLOCKS[level.clamp(1, BLOCKS.len()) - 1]
+            }
+        })
+        .collect()
+}
+
+/// Results as a JSON array, one object per benchmark, each carrying
+/// the run's `identity()` so rows can be stored on their own
+pub fn render_json(results: &[BenchmarkResult], config: &RunConfig) -> String {
+    let identity = identity_json(&identity());
+    let environment = environment_json(&environment_snapshot());
+    let tags = tags_json(&config.tags);
+    let objects: Vec<String> = results
+        .iter()
+        .map(|r| {
+            let mean_ci = confidence_interval_95(&r.stats);
+            let p50_ci = median_confidence_interval(&r.samples);
+            format!(
+                "{{\"name\":\"{}\",\"label\":\"{}\",\"loops\":{},\"total_ns\":{},\
+                 \"ns_per_call\":{:.p$},\"ops_per_sec\":{},\"trimmed_mean\":{:.p$},\"trim_percent\":{},\
+                 \"min\":{:.p$},\"max\":{:.p$},\"mean\":{:.p$},\
+                 \"stddev\":{:.p$},\"p50\":{:.p$},\"p90\":{:.p$},\"p99\":{:.p$},\
+                 \"iqr\":{:.p$},\"peak_to_peak\":{:.p$},\"mean_ci95\":[{:.p$},{:.p$}],\"p50_ci95\":[{:.p$},{:.p$}],\
+                 \"samples\":{},\"percentiles_reliable\":{},\
+                 \"first_call_ns\":{},\"migrations\":{},\"warnings\":{},\"identity\":{},\"environment\":{},\"tags\":{}}}",
+                json_escape(&r.name),
+                json_escape(&r.label),
+                json_integer(r.loops as u128),
+                json_integer(r.total_ns),
+                r.ns_per_call(),
+                json_ops(r.ops_per_sec()),
+                r.trimmed_mean(config.trim_percent),
+                config.trim_percent,
+                r.stats.min,
+                r.stats.max,
+                r.stats.mean,
+                r.stats.stddev,
+                r.stats.p50,
+                r.stats.p90,
+                r.stats.p99,
+                r.stats.iqr(),
+                r.stats.peak_to_peak(),
+                mean_ci.0,
+                mean_ci.1,
+                p50_ci.0,
+                p50_ci.1,
+                r.samples.len(),
+                r.percentiles_reliable(config.min_samples),
+                r.first_call_ns.map_or("null".to_string(), |ns| json_integer(ns as u128)),
+                json_integer(r.migrations as u128),
+                warnings_json(&r.warnings),
+                identity,
+                environment,
+                tags,
+                p = config.precision
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+/// Warnings as a JSON array of `{"code": ..., "message": ...}`
+fn warnings_json(warnings: &[Warning]) -> String {
+    let objects: Vec<String> = warnings
+        .iter()
+        .map(|w| format!("{{\"code\":\"{}\",\"message\":\"{}\"}}", w.kind.code(), json_escape(&w.message)))
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+/// Tags as a JSON object of strings, in the order given
+fn tags_json(tags: &[(String, String)]) -> String {
+    let members: Vec<String> = tags
+        .iter()
+        .map(|(key, value)| format!("\"{}\":\"{}\"", json_escape(key), json_escape(value)))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+/// The text report's `Tags` line; None without tags
+fn tags_line(tags: &[(String, String)]) -> Option<String> {
+    (!tags.is_empty()).then(|| format!("{}{}", label("Tags"), tags_field(tags).replace(';', ", ")))
+}
+
+/// Tags as `key=value` pairs joined by `;`, the CSV and history field
+pub fn tags_field(tags: &[(String, String)]) -> String {
+    let pairs: Vec<String> = tags.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+    pairs.join(";")
+}
+
+/// Whole calls per second as a JSON number; null when infinite, which
+/// JSON can't represent
+fn json_ops(ops: f64) -> String {
+    if ops.is_finite() { format!("{:.0}", ops) } else { "null".to_string() }
+}
+
+/// Largest integer every JSON parser holds exactly (2^53 - 1, an
+/// f64 mantissa; JavaScript's `Number.MAX_SAFE_INTEGER`)
+pub const JSON_MAX_SAFE_INTEGER: u128 = (1 << 53) - 1;
+
+/// `value` as a JSON number, or as a quoted decimal string above
+/// `JSON_MAX_SAFE_INTEGER` so f64-based parsers can't round it
+pub fn json_integer(value: u128) -> String {
+    if value > JSON_MAX_SAFE_INTEGER {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// `identity` as a JSON object
+pub fn identity_json(identity: &Identity) -> String {
+    format!(
+        "{{\"hostname\":\"{}\",\"cpu_brand\":\"{}\",\"commit\":\"{}\",\"timestamp\":\"{}\",\
+         \"rustc\":\"{}\",\"opt_level\":\"{}\",\"debug_assertions\":{}}}",
+        json_escape(&identity.hostname),
+        json_escape(&identity.cpu_brand),
+        json_escape(&identity.commit),
+        json_escape(&identity.timestamp),
+        json_escape(&identity.rustc),
+        json_escape(&identity.opt_level),
+        identity.debug_assertions
+    )
+}
+
+/// `env` as a JSON object, null for settings that couldn't be read
+pub fn environment_json(env: &EnvironmentSnapshot) -> String {
+    let flag = |state: Option<bool>| state.map_or("null".to_string(), |on| on.to_string());
+    format!(
+        "{{\"governor\":{},\"turbo\":{},\"smt\":{}}}",
+        env.governor
+            .as_deref()
+            .map_or("null".to_string(), |g| format!("\"{}\"", json_escape(g))),
+        flag(env.turbo),
+        flag(env.smt)
+    )
+}
+
+/// Results as CSV with a header row
+pub fn render_csv(results: &[BenchmarkResult], config: &RunConfig) -> String {
+    let mut out = String::from(
+        "name,loops,total_ns,ns_per_call,ops_per_sec,trimmed_mean,min,max,mean,stddev,p50,p90,p99,iqr,peak_to_peak,mean_ci95_low,mean_ci95_high,first_call_ns,warnings,tags\n",
+    );
+    let tags = tags_field(&config.tags);
+    for r in results {
+        let codes: Vec<&str> = r.warnings.iter().map(|w| w.kind.code()).collect();
+        let mean_ci = confidence_interval_95(&r.stats);
+        out.push_str(&format!(
+            "{},{},{},{:.p$},{:.0},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{},{},{}\n",
+            r.name,
+            r.loops,
+            r.total_ns,
+            r.ns_per_call(),
+            r.ops_per_sec(),
+            r.trimmed_mean(config.trim_percent),
+            r.stats.min,
+            r.stats.max,
+            r.stats.mean,
+            r.stats.stddev,
+            r.stats.p50,
+            r.stats.p90,
+            r.stats.p99,
+            r.stats.iqr(),
+            r.stats.peak_to_peak(),
+            mean_ci.0,
+            mean_ci.1,
+            r.first_call_ns.map_or(String::new(), |ns| ns.to_string()),
+            codes.join(";"),
+            tags,
+            p = config.precision
+        ));
+    }
+    out
+}
+
+/// Metric name, HELP text and value accessor for one Prometheus gauge
+type Gauge = (&'static str, &'static str, fn(&BenchmarkResult) -> f64);
+
+/// Results in the Prometheus text exposition format
+pub fn render_prometheus(results: &[BenchmarkResult], config: &RunConfig) -> String {
+    let gauges: [Gauge; 3] = [
+        ("timing_eval_ns_per_call", "Mean cost per call in nanoseconds.", |r| r.ns_per_call()),
+        ("timing_eval_p50_ns", "Median per-call cost in nanoseconds.", |r| r.stats.p50),
+        ("timing_eval_p99_ns", "99th percentile per-call cost in nanoseconds.", |r| r.stats.p99),
+    ];
+
+    let tags: String = config
+        .tags
+        .iter()
+        .map(|(key, value)| format!(",{}=\"{}\"", key, prometheus_escape(value)))
+        .collect();
+    let mut out = String::new();
+    for (metric, help, value) in gauges {
+        out.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", metric, help, metric));
+        for r in results {
+            out.push_str(&format!(
+                "{}{{benchmark=\"{}\"{}}} {:.p$}\n",
+                metric,
+                prometheus_escape(&r.name),
+                tags,
+                value(r),
+                p = config.precision
+            ));
+        }
+    }
+
+    out.push_str("# HELP timing_eval_loops Measured iterations per benchmark.\n");
+    out.push_str("# TYPE timing_eval_loops gauge\n");
+    for r in results {
+        out.push_str(&format!(
+            "timing_eval_loops{{benchmark=\"{}\"{}}} {}\n",
+            prometheus_escape(&r.name),
+            tags,
+            r.loops
+        ));
+    }
+    out
+}
+
+/// Results as Go benchmark lines (`BenchmarkName-<cores> <N> <ns> ns/op`),
+/// the input format of benchstat.
+///
+/// Names are CamelCased (`clock_gettime` -> `ClockGettime`); the suffix
+/// is the logical core count, like Go's GOMAXPROCS default. Tags come
+/// first as `key: value` configuration lines, which benchstat groups by.
+pub fn render_gobench(results: &[BenchmarkResult], config: &RunConfig) -> String {
+    let cores = logical_cpus();
+    let mut out = String::new();
+    for (key, value) in &config.tags {
+        out.push_str(&format!("{}: {}\n", key, value));
+    }
+    for r in results {
+        out.push_str(&format!(
+            "Benchmark{}-{}\t{}\t{:.p$} ns/op\n",
+            camel_case(&r.name),
+            cores,
+            r.loops,
+            r.ns_per_call(),
+            p = config.precision
+        ));
+    }
+    out
+}
+
+/// Results in the Bencher Metric Format: one object per benchmark name
+/// with a `latency` measure in ns, the median as `value` and its ~95%
+/// confidence interval (see `median_confidence_interval`) as
+/// `lower_value` and `upper_value`. The format has nowhere for tags.
+pub fn render_bmf(results: &[BenchmarkResult], config: &RunConfig) -> String {
+    let entries: Vec<String> = results
+        .iter()
+        .map(|r| {
+            let (lower, upper) = median_confidence_interval(&r.samples);
+            format!(
+                "\"{}\":{{\"latency\":{{\"value\":{:.p$},\"lower_value\":{:.p$},\"upper_value\":{:.p$}}}}}",
+                json_escape(&r.name),
+                r.stats.p50,
+                lower,
+                upper,
+                p = config.precision
+            )
+        })
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+/// Longest line `render_oneline` produces
+pub const ONELINE_MAX_CHARS: usize = 120;
+
+/// Longest CPU name `render_oneline` keeps, so a long brand string
+/// can't take the whole line
+pub const ONELINE_CPU_MAX_CHARS: usize = 40;
+
+/// Results (in the given order) on one line for a commit status or chat
+/// message, e.g. `HRT 4.2ns | Instant 9.1ns | SystemTime 38ns @ Ryzen 9 7950X`.
+///
+/// Costs are `format_ns_compact` unless `config.units` is set, then
+/// `format_cost` with `config.precision`. The CPU name is cut to
+/// `ONELINE_CPU_MAX_CHARS` and counts against the line; benchmarks that
+/// would push the line past `ONELINE_MAX_CHARS` are left off and
+/// counted as `+N more`.
+pub fn render_oneline(results: &[BenchmarkResult], cpu_brand: Option<&str>, config: &RunConfig) -> String {
+    let mut cpu = cpu_brand
+        .map(|brand| format!(" @ {}", truncate_chars(&short_cpu_name(brand), ONELINE_CPU_MAX_CHARS)))
+        .unwrap_or_default();
+    if !config.tags.is_empty() {
+        cpu.push_str(&format!(" [{}]", tags_field(&config.tags).replace(';', " ")));
+    }
+    let cost = |ns: f64| match config.units {
+        None => format_ns_compact(ns),
+        Some(Units::Ns) => format!("{}ns", format_cost(ns, Units::Ns, config.precision)),
+        Some(Units::Us) => format!("{}us", format_cost(ns, Units::Us, config.precision)),
+        Some(Units::Auto) => format_cost(ns, Units::Auto, config.precision),
+    };
+    let parts: Vec<String> = results
+        .iter()
+        .map(|r| format!("{} {}", short_bench_name(&r.name), cost(r.ns_per_call())))
+        .collect();
+
+    for shown in (0..=parts.len()).rev() {
+        let mut line = parts[..shown].join(" | ");
+        if shown < parts.len() {
+            let more = format!("+{} more", parts.len() - shown);
+            line = if line.is_empty() { more } else { format!("{} | {}", line, more) };
+        }
+        line.push_str(&cpu);
+        if line.chars().count() <= ONELINE_MAX_CHARS || shown == 0 {
+            return line;
+        }
+    }
+    unreachable!("shown == 0 always returns")
+}
+
+/// `s` cut to `max` chars, the last one replaced by `…` when cut
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut cut: String = s.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Display names for the clock sources; catalog names stay as they are
+fn short_bench_name(name: &str) -> &str {
+    match name {
+        "hrt" => "HRT",
+        "hrt_start_stop" => "HRT(start+stop)",
+        "instant" => "Instant",
+        "systemtime" => "SystemTime",
+        "ticks_noinline" => "ticks(noinline)",
+        other => other,
+    }
+}
+
+/// ns with two or three significant digits and a unit, e.g. `4.2ns`,
+/// `38ns`, `1.25us`
+pub fn format_ns_compact(ns: f64) -> String {
+    match ns {
+        n if n >= 1e9 => format!("{:.2}s", n / 1e9),
+        n if n >= 1e6 => format!("{:.2}ms", n / 1e6),
+        n if n >= 1e3 => format!("{:.2}us", n / 1e3),
+        n if n >= 10.0 => format!("{:.0}ns", n),
+        n => format!("{:.1}ns", n),
+    }
+}
+
+/// CPU brand string without vendor, trademarks, core count, "Processor"
+/// and clock speed: "AMD Ryzen 9 7950X 16-Core Processor" gives
+/// "Ryzen 9 7950X", "Intel(R) Core(TM) i7-9700K CPU @ 3.60GHz" gives
+/// "Core i7-9700K"
+pub fn short_cpu_name(brand: &str) -> String {
+    let brand = brand.split('@').next().unwrap_or(brand);
+    let words: Vec<&str> = brand
+        .split_whitespace()
+        .map(|word| word.trim_end_matches("(R)").trim_end_matches("(TM)").trim_end_matches("(tm)"))
+        .filter(|word| {
+            !word.is_empty()
+                && !matches!(*word, "AMD" | "Intel" | "Processor" | "CPU")
+                && !word.ends_with("-Core")
+                && !word.ends_with("-core")
+        })
+        .collect();
+    if words.is_empty() { brand.trim().to_string() } else { words.join(" ") }
+}
+
+/// `snake_case` to `CamelCase`, dropping other non-alphanumerics
+fn camel_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (first, rest) = part.split_at(1);
+            first.to_ascii_uppercase() + rest
+        })
+        .collect()
+}
+
+/// Escape a Prometheus label value (backslash, quote, newline)
+pub fn prometheus_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Escape a string for use inside JSON quotes
+pub fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_results() -> Vec<BenchmarkResult> {
+        vec![
+            BenchmarkResult::from_samples("instant", &[20, 22, 24]),
+            BenchmarkResult::from_samples("ticks", &[8, 9, 10]),
+        ]
+    }
+
+    fn is_metric_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    }
+
+    #[test]
+    fn sparkline_heights() {
+        assert_eq!(sparkline(&[5, 5, 5, 5]), "████");
+        assert_eq!(sparkline(&[1, 1, 100, 1, 0]), "▁▁█▁ ");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn prometheus_follows_exposition_grammar() {
+        let out = render_prometheus(&sample_results(), &RunConfig::new());
+        let mut typed = Vec::new();
+        let mut samples = 0;
+        for line in out.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                assert!(is_metric_name(help.split(' ').next().unwrap()), "{}", line);
+            } else if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                assert!(is_metric_name(name) && kind == "gauge", "{}", line);
+                typed.push(name);
+            } else {
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                let (name, labels) = series.split_once('{').unwrap();
+                assert!(typed.contains(&name), "untyped sample: {}", line);
+                assert!(labels.starts_with("benchmark=\"") && labels.ends_with("\"}"), "{}", line);
+                value.parse::<f64>().unwrap();
+                samples += 1;
+            }
+        }
+        assert_eq!(typed.len(), 4);
+        assert_eq!(samples, 4 * 2);
+    }
+
+    #[test]
+    fn instant_cost_relative_to_hrt() {
+        let results = [
+            BenchmarkResult::from_samples("instant", &[30, 30]),
+            BenchmarkResult::from_samples("hrt", &[10, 10]),
+        ];
+        assert_eq!(relative_cost(&results, "instant", "hrt"), Some(3.0));
+        assert_eq!(relative_cost(&results, "instant", "ticks"), None);
+    }
+
+    #[test]
+    fn table_columns_align() {
+        let rows = vec![
+            vec!["1".to_string(), "8.1".to_string(), "ticks".to_string()],
+            vec!["2".to_string(), "1234.5".to_string(), "systemtime".to_string()],
+            vec!["10".to_string()],
+        ];
+        let out = render_table(&["Rank", "ns", "Benchmark"], &rows);
+        assert_eq!(
+            out,
+            "Rank  ns      Benchmark\n\
+             1     8.1     ticks\n\
+             2     1234.5  systemtime\n\
+             10\n"
+        );
+    }
+
+    #[test]
+    fn normalizes_to_baseline() {
+        let results = [
+            BenchmarkResult::from_samples("ticks", &[5, 5]),
+            BenchmarkResult::from_samples("hrt", &[10, 10]),
+            BenchmarkResult::from_samples("systemtime", &[25, 25]),
+        ];
+        assert_eq!(normalize_to(&results, "hrt"), Some(vec![0.5, 1.0, 2.5]));
+        assert_eq!(normalize_to(&results, "instant"), None);
+        let zero = [BenchmarkResult::from_samples("hrt", &[0])];
+        assert_eq!(normalize_to(&zero, "hrt"), None);
+    }
+
+    #[test]
+    fn gobench_lines_match_go_format() {
+        let out = render_gobench(&[BenchmarkResult::from_samples("clock_gettime", &[20, 22])], &RunConfig::new());
+        // ^Benchmark[A-Z]\w*-\d+\s+\d+\s+\d+(\.\d+)? ns/op$
+        let fields: Vec<&str> = out.split_whitespace().collect();
+        let [name, iterations, value, unit] = fields[..] else { panic!("{}", out) };
+        let (name, cores) = name.rsplit_once('-').unwrap();
+        assert_eq!(name, "BenchmarkClockGettime");
+        assert_eq!(cores.parse::<usize>().unwrap(), std::thread::available_parallelism().map_or(1, |n| n.get()));
+        assert_eq!(iterations, "2");
+        assert_eq!(value, "21.000");
+        assert_eq!(unit, "ns/op");
+    }
+
+    #[test]
+    fn large_integers_are_quoted() {
+        assert_eq!(json_integer(JSON_MAX_SAFE_INTEGER), "9007199254740991");
+        assert_eq!(json_integer(1 << 53), "\"9007199254740992\"");
+        assert_eq!(json_integer(u128::MAX), format!("\"{}\"", u128::MAX));
+
+        let mut results = sample_results();
+        results[0].total_ns = 1 << 60;
+        let json = render_json(&results, &RunConfig::new());
+        assert!(json.contains("\"total_ns\":\"1152921504606846976\""), "{}", json);
+        assert!(json.contains(&format!("\"total_ns\":{},", results[1].total_ns)), "{}", json);
+    }
+
+    #[test]
+    fn labels_align_values() {
+        let short = format!("{}{}", pad_label("CPU", 20), "3.0 GHz");
+        let long = format!("{}{}", pad_label("Timer resolution", 20), "1 ns");
+        assert_eq!(short, "CPU:                3.0 GHz");
+        assert_eq!(long, "Timer resolution:   1 ns");
+        // a label longer than the width still gets a space
+        assert_eq!(pad_label("Timer resolution", 8), "Timer resolution: ");
+
+        assert_eq!(label("CPU").len(), DEFAULT_LABEL_WIDTH);
+        assert_eq!(label("CPU").len(), label("Invariant TSC").len());
+    }
+
+    #[test]
+    fn absurd_clock_read_is_flagged() {
+        use crate::high_resolution_timer::{active_backend, TimerBackend};
+
+        let _lock = crate::high_resolution_timer::test_lock();
+        assert!(is_suspicious_clock_read(TimerBackend::Tsc, 5_000.0));
+        assert!(is_suspicious_clock_read(TimerBackend::Monotonic, 1.0));
+        assert!(!is_suspicious_clock_read(TimerBackend::Tsc, 8.0));
+
+        let absurd = BenchmarkResult::from_samples("ticks", &[5_000, 5_000, 5_000]);
+        let label = annotated_label(&absurd);
+        assert!(label.starts_with("ticks (suspicious: "), "{}", label);
+        let (low, high) = typical_clock_read_ns(active_backend());
+        assert!(label.ends_with(&format!("usually take {}-{} ns)", low, high)), "{}", label);
+        // only clock reads have a band
+        assert_eq!(annotated_label(&BenchmarkResult::from_samples("hrt", &[5_000])), "hrt");
+    }
+
+    #[test]
+    fn clock_anomaly_is_flagged_in_the_label() {
+        let mut result = BenchmarkResult::from_samples("hrt", &[5_000]);
+        result.clock_anomaly = true;
+        assert_eq!(annotated_label(&result), "hrt (clock anomaly: timer and wall clock disagree)");
+    }
+
+    #[test]
+    fn calibration_dump_has_a_row_per_segment() {
+        use crate::high_resolution_timer::CalibrationSample;
+
+        let samples = (0..4).map(|i| CalibrationSample { delta_ticks: 3_000_000 + i, delta_ns: 1_000_000 }).collect();
+        let out = render_calibration(&Calibration { tick_hz: 3_000_001, samples });
+        let lines: Vec<&str> = out.lines().collect();
+        // header, four segments, frequency and RSE
+        assert_eq!(lines.len(), 1 + 4 + 2, "{}", out);
+        assert!(lines[1].starts_with("1 ") && lines[4].starts_with("4 "), "{}", out);
+        assert!(lines[4].contains("3000003"), "{}", out);
+        assert!(lines[5].ends_with("3000001 Hz"), "{}", out);
+
+        let hardware = render_calibration(&Calibration { tick_hz: 24_000_000, samples: Vec::new() });
+        assert!(hardware.starts_with("(no segments"), "{}", hardware);
+        assert!(hardware.contains("N/A"), "{}", hardware);
+    }
+
+    #[test]
+    fn oneline_names_costs_and_cpu() {
+        let brand = "Intel(R) Core(TM) i7-9700K CPU @ 3.60GHz";
+        let line = render_oneline(&sample_results(), Some(brand), &RunConfig::default());
+        assert_eq!(line, "Instant 22ns | ticks 9.0ns @ Core i7-9700K");
+        assert_eq!(short_cpu_name("AMD Ryzen 9 7950X 16-Core Processor"), "Ryzen 9 7950X");
+        assert_eq!(render_oneline(&sample_results(), None, &RunConfig::default()), "Instant 22ns | ticks 9.0ns");
+    }
+
+    #[test]
+    fn oneline_cuts_a_long_cpu_name() {
+        let brand = "Contoso Hypothetical Server Silicon Model 9000 With A Very Long Marketing Name Indeed";
+        let line = render_oneline(&sample_results(), Some(brand), &RunConfig::default());
+        let cpu = line.split(" @ ").nth(1).unwrap();
+        assert_eq!(cpu.chars().count(), ONELINE_CPU_MAX_CHARS);
+        assert!(cpu.ends_with('…'), "{}", cpu);
+        assert!(line.starts_with("Instant 22ns | ticks 9.0ns @ Contoso"), "{}", line);
+
+        // the name counts against the line: many results still fit
+        let many: Vec<BenchmarkResult> =
+            (0..40).map(|i| BenchmarkResult::from_samples(&format!("bench{}", i), &[5])).collect();
+        let line = render_oneline(&many, Some(brand), &RunConfig::default());
+        assert!(line.chars().count() <= ONELINE_MAX_CHARS, "{}", line);
+        assert!(line.contains(" more @ Contoso"), "{}", line);
+    }
+
+    #[test]
+    fn few_samples_are_flagged_unreliable() {
+        let results = sample_results();
+        assert!(!results[0].percentiles_reliable(crate::benchmark::DEFAULT_MIN_SAMPLES));
+        assert!(results[0].percentiles_reliable(3));
+        assert!(!results[0].percentiles_reliable(4));
+
+        let json = render_json(&results, &RunConfig::new());
+        assert_eq!(json.matches("\"percentiles_reliable\":false").count(), 2, "{}", json);
+        let json = render_json(&results, &RunConfig::new().min_samples(3));
+        assert_eq!(json.matches("\"percentiles_reliable\":true").count(), 2, "{}", json);
+    }
+
+    #[test]
+    fn format_cost_in_each_unit() {
+        assert_eq!(format_cost(1234.5, Units::Ns, 1), "1234.5");
+        assert_eq!(format_cost(1234.5, Units::Us, 3), "1.234");
+        assert_eq!(format_cost(1234.5, Units::Auto, 2), "1.23us");
+        assert_eq!(format_cost(12.0, Units::Auto, 1), "12.0ns");
+        assert_eq!(format_cost(2.5e7, Units::Auto, 1), "25.0ms");
+        assert_eq!(format_cost(3e9, Units::Auto, 0), "3s");
+        assert_eq!("auto".parse::<Units>(), Ok(Units::Auto));
+        assert!("ms".parse::<Units>().is_err());
+    }
+
+    #[test]
+    fn warnings_reach_json_and_csv() {
+        use crate::warning::WarningKind;
+
+        let mut results = sample_results();
+        results[1].warnings.push(Warning::new(WarningKind::CoreMigrations, "moved \"twice\""));
+
+        let json = crate::json::parse_json(&render_json(&results, &RunConfig::new())).unwrap();
+        let warnings: Vec<&[crate::json::JsonValue]> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r.get("warnings").and_then(|w| w.as_array()).unwrap())
+            .collect();
+        assert!(warnings[0].is_empty());
+        assert_eq!(warnings[1][0].get("code").and_then(|c| c.as_str()), Some("core_migrations"));
+        assert_eq!(warnings[1][0].get("message").and_then(|m| m.as_str()), Some("moved \"twice\""));
+
+        let csv = render_csv(&results, &RunConfig::new());
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        assert!(!rows[0].contains("core_migrations"));
+        // warnings, then an empty tags column
+        assert!(rows[1].ends_with(",core_migrations,"), "{}", rows[1]);
+    }
+
+    #[test]
+    fn bmf_has_latency_and_bounds_per_benchmark() {
+        let results = sample_results();
+        let bmf = crate::json::parse_json(&render_bmf(&results, &RunConfig::new().precision(3))).unwrap();
+        for result in &results {
+            let latency = bmf.get(&result.name).and_then(|b| b.get("latency")).expect("a latency measure");
+            let field = |key| latency.get(key).and_then(|v| v.as_f64()).unwrap();
+            let (lower, upper) = median_confidence_interval(&result.samples);
+
+            assert_eq!(field("value"), result.stats.p50);
+            assert!((field("lower_value") - lower).abs() < 1e-3 && (field("upper_value") - upper).abs() < 1e-3);
+            assert!(field("lower_value") <= field("value") && field("value") <= field("upper_value"));
+        }
+    }
+
+    #[test]
+    fn tags_reach_every_format_that_has_room() {
+        let args = ["--tag", "host=ci-7", "--tag", "commit=abc123"].map(String::from);
+        let config = crate::cli::parse_args(args).unwrap();
+        assert_eq!(config.tags, [("host".to_string(), "ci-7".to_string()), ("commit".to_string(), "abc123".to_string())]);
+        assert!(crate::cli::parse_args(["--tag", "novalue"].map(String::from)).unwrap_err().contains("KEY=VALUE"));
+        let results = [BenchmarkResult::from_samples("hrt", &[10, 12])];
+
+        let json = crate::json::parse_json(&render_json(&results, &config)).unwrap();
+        let tags = json.as_array().unwrap()[0].get("tags").unwrap();
+        assert_eq!(tags.get("host").and_then(|v| v.as_str()), Some("ci-7"));
+        assert_eq!(tags.get("commit").and_then(|v| v.as_str()), Some("abc123"));
+        let csv = render_csv(&results, &config);
+        assert!(csv.lines().next().unwrap().ends_with(",tags"));
+        assert!(csv.lines().nth(1).unwrap().ends_with(",host=ci-7;commit=abc123"), "{}", csv);
+        let prometheus = render_prometheus(&results, &config);
+        assert!(prometheus.contains("timing_eval_p50_ns{benchmark=\"hrt\",host=\"ci-7\",commit=\"abc123\"} "), "{}", prometheus);
+        assert!(prometheus.contains("timing_eval_loops{benchmark=\"hrt\",host=\"ci-7\",commit=\"abc123\"} 2"));
+        assert!(render_gobench(&results, &config).starts_with("host: ci-7\ncommit: abc123\nBenchmarkHrt-"));
+        assert!(render_oneline(&results, None, &config).ends_with(" [host=ci-7 commit=abc123]"));
+        assert!(tags_line(&config.tags).unwrap().ends_with("host=ci-7, commit=abc123"));
+        assert_eq!(tags_line(&[]), None);
+
+        let path = std::env::temp_dir().join(format!("timing-eval-test-{}-tags.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        crate::history::append_history(&path, &results, &config.tags).unwrap();
+        let history = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let row: Vec<&str> = history.lines().nth(1).unwrap().split('\t').collect();
+        assert_eq!((row[2], row[4]), ("hrt", "host=ci-7;commit=abc123"));
+    }
+}
@@ -0,0 +1,1192 @@
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::affinity::{current_cpu, pin_to_cpu, raise_priority, set_realtime_priority, Priority};
+use crate::benchmark::{
+    clock_sources, default_batch_size, measure_batched, measure_first_call, measure_until_precise, merge_warm_runs,
+    precision_batch_size, rank_by_cost, registry, sweep_benchmark, Benchmark, BenchmarkResult, DEFAULT_MIN_SAMPLES,
+    MemoryPressure, SweepResult,
+};
+use crate::error::{AssertionFailure, CalibrationError};
+use crate::noise::{cpu_interrupts, interrupt_warning};
+use crate::high_resolution_timer::{recalibrate, HighResolutionTimer, TimerBackend, DEFAULT_CALIBRATION_WINDOW};
+use crate::report::DEFAULT_LABEL_WIDTH;
+use crate::signal::stop_requested;
+use crate::sink::{ResultSink, VecSink};
+use crate::warning::{result_warnings, Warning, WarningKind};
+use crate::trace::{trace_event, TraceKind};
+use crate::stats::{precision_reached, HistogramSpec, RunningMoments};
+
+// ============================================================
+// Run configuration
+// ============================================================
+
+/// Output format for results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Text on a terminal, JSON when piped; resolved by `RunConfig::build`
+    Auto,
+    Text,
+    Json,
+    Csv,
+    /// Prometheus text exposition (node_exporter textfile collector)
+    Prometheus,
+    /// Go `testing` benchmark lines, for benchstat
+    GoBench,
+    /// One short line for a commit status or chat message
+    Oneline,
+    /// Bencher Metric Format JSON, for bencher.dev
+    Bmf,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Format::Auto),
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "prometheus" => Ok(Format::Prometheus),
+            "gobench" => Ok(Format::GoBench),
+            "oneline" => Ok(Format::Oneline),
+            "bmf" => Ok(Format::Bmf),
+            other => Err(format!("unknown format: {}", other)),
+        }
+    }
+}
+
+impl Format {
+    /// Concrete format for `Auto` given whether stdout is a terminal;
+    /// other formats are returned unchanged
+    pub fn resolve(self, is_terminal: bool) -> Format {
+        match self {
+            Format::Auto if is_terminal => Format::Text,
+            Format::Auto => Format::Json,
+            other => other,
+        }
+    }
+}
+
+/// Unit for the per-call costs in the text table and the oneline summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Units {
+    Ns,
+    Us,
+    /// ns, us, ms or s per value, whichever keeps it readable
+    Auto,
+}
+
+impl std::str::FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ns" => Ok(Units::Ns),
+            "us" => Ok(Units::Us),
+            "auto" => Ok(Units::Auto),
+            other => Err(format!("unknown units: {}", other)),
+        }
+    }
+}
+
+/// Everything that controls a benchmark run
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    /// Measured iterations per run; None uses each benchmark's default
+    pub loops: Option<u64>,
+    /// Untimed iterations before measuring
+    pub warmup: u64,
+    /// Repeated runs merged into one result
+    pub runs: u32,
+    /// Leading runs left out of the merge as cold (still in the timeline)
+    pub discard_runs: u32,
+    pub format: Format,
+    /// Decimal places for ns values
+    pub precision: usize,
+    /// Units for human-facing costs; None is each format's default (ns
+    /// in the table, `Auto` in the oneline summary)
+    pub units: Option<Units>,
+    /// Core to pin calibration and measurement to
+    pub cpu: Option<usize>,
+    /// Benchmark names to run; empty means every clock source (catalog
+    /// benchmarks only run when named)
+    pub benchmarks: Vec<String>,
+    /// Report iterations until steady state
+    pub detect_warmup: bool,
+    /// MB of memory to touch between batches (experimental)
+    pub memory_pressure_mb: Option<usize>,
+    /// Percent of slowest samples dropped for the trimmed mean
+    pub trim_percent: f64,
+    /// Samples below which percentiles are reported as unreliable
+    pub min_samples: usize,
+    /// Window for the TSC frequency calibration
+    pub calibration_window: Duration,
+    /// TSV file that each run appends its results to
+    pub history: Option<PathBuf>,
+    /// Try to run under SCHED_FIFO
+    pub realtime: bool,
+    /// Reuse the tick frequency across launches
+    pub calibration_cache: bool,
+    /// Buckets of the text report's distribution lines
+    pub histogram: HistogramSpec,
+    /// Benchmark whose ns/call the text report treats as 1.0x
+    pub relative_to: Option<String>,
+    /// Print the timer backends and exit
+    pub list_backends: bool,
+    /// Timer backend to use instead of the compiled-in one
+    pub backend: Option<TimerBackend>,
+    /// Path prefix for the gnuplot data and script
+    pub plot: Option<PathBuf>,
+    /// Fit per-call cost over several iteration counts instead of a
+    /// normal run
+    pub sweep: bool,
+    /// Repeat the run pinned to each online CPU
+    pub sweep_cpus: bool,
+    /// TSV file for (wall time, latency) per sample
+    pub timeline: Option<PathBuf>,
+    /// Print the version banner and exit
+    pub version: bool,
+    /// Time the clock sources, print which one to use, and exit
+    pub recommend: bool,
+    /// Column where values start in `Label: value` text lines
+    pub label_width: usize,
+    /// Measure clock-read cost at 1, 2, 4, ... threads instead of a
+    /// normal run
+    pub scaling: bool,
+    /// Record calibration/warmup/run events and print them at the end
+    pub trace: bool,
+    /// (benchmark, max median ns/call) limits; a result above its limit
+    /// fails the run with exit code 4
+    pub assertions: Vec<(String, f64)>,
+    /// Stop after the first result that fails its assertion
+    pub fail_fast: bool,
+    /// (key, value) labels carried into every result's output
+    pub tags: Vec<(String, String)>,
+    /// Recalibrate every second for this long, printing each frequency
+    pub freq_monitor: Option<Duration>,
+    /// Time this long with both the timer and SystemTime, print how far
+    /// apart they end up, then exit
+    pub validate: Option<Duration>,
+    /// Time this long with CLOCK_MONOTONIC and CLOCK_MONOTONIC_RAW,
+    /// print their drift in ppm, then exit (Linux)
+    pub clock_drift: Option<Duration>,
+    /// Print the calibration's segments, frequency and error, then exit
+    pub dump_calibration: bool,
+    /// Hold the Windows timer at 1 ms (timeBeginPeriod) during the run
+    pub high_timer_res: bool,
+    /// Raise the Windows process/thread priority during the run
+    pub priority: Option<Priority>,
+    /// Wall-clock budget; no benchmark or run starts once it's used up
+    pub max_runtime: Option<Duration>,
+    /// When `max_runtime` runs out, counted from `build()`
+    pub deadline: Option<Instant>,
+    /// Compare two `--format json` files (old, new) instead of running
+    pub diff: Option<(PathBuf, PathBuf)>,
+    /// Calls timed together per sample; None picks one from the loops
+    pub batch_size: Option<u64>,
+    /// Sample each run until the mean's 95% confidence interval is
+    /// within this fraction of it, the loops becoming the cap
+    pub target_precision: Option<f64>,
+    /// Measure on a spawned thread with this many MiB of stack instead
+    /// of the main thread
+    pub thread_stack_mb: Option<usize>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            loops: None,
+            warmup: 0,
+            runs: 1,
+            discard_runs: 0,
+            format: Format::Auto,
+            precision: 3,
+            units: None,
+            cpu: None,
+            benchmarks: Vec::new(),
+            detect_warmup: false,
+            memory_pressure_mb: None,
+            trim_percent: 1.0,
+            min_samples: DEFAULT_MIN_SAMPLES,
+            calibration_window: DEFAULT_CALIBRATION_WINDOW,
+            history: None,
+            realtime: false,
+            calibration_cache: false,
+            histogram: HistogramSpec::default(),
+            relative_to: None,
+            list_backends: false,
+            backend: None,
+            plot: None,
+            sweep: false,
+            sweep_cpus: false,
+            timeline: None,
+            version: false,
+            recommend: false,
+            label_width: DEFAULT_LABEL_WIDTH,
+            scaling: false,
+            trace: false,
+            assertions: Vec::new(),
+            tags: Vec::new(),
+            fail_fast: false,
+            freq_monitor: None,
+            validate: None,
+            clock_drift: None,
+            dump_calibration: false,
+            high_timer_res: false,
+            priority: None,
+            max_runtime: None,
+            deadline: None,
+            diff: None,
+            batch_size: None,
+            target_precision: None,
+            thread_stack_mb: None,
+        }
+    }
+}
+
+/// Builder-style setters; finish with `build()` to validate:
+///
+/// `RunConfig::new().loops(1_000_000).runs(3).format(Format::Json).build()`
+impl RunConfig {
+    /// Start from the defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn loops(mut self, loops: u64) -> Self {
+        self.loops = Some(loops);
+        self
+    }
+
+    pub fn warmup(mut self, warmup: u64) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    pub fn runs(mut self, runs: u32) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    pub fn discard_runs(mut self, discard_runs: u32) -> Self {
+        self.discard_runs = discard_runs;
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = Some(units);
+        self
+    }
+
+    pub fn cpu(mut self, cpu: usize) -> Self {
+        self.cpu = Some(cpu);
+        self
+    }
+
+    /// Add a benchmark to run (by name); may be called repeatedly
+    pub fn benchmark(mut self, name: impl Into<String>) -> Self {
+        self.benchmarks.push(name.into());
+        self
+    }
+
+    pub fn detect_warmup(mut self, detect: bool) -> Self {
+        self.detect_warmup = detect;
+        self
+    }
+
+    /// Evict caches between batches by touching `megabytes` of memory
+    pub fn memory_pressure(mut self, megabytes: usize) -> Self {
+        self.memory_pressure_mb = Some(megabytes);
+        self
+    }
+
+    /// Percent of slowest samples to drop for the trimmed mean
+    pub fn trim(mut self, percent: f64) -> Self {
+        self.trim_percent = percent;
+        self
+    }
+
+    pub fn min_samples(mut self, samples: usize) -> Self {
+        self.min_samples = samples;
+        self
+    }
+
+    pub fn calibration_window(mut self, window: Duration) -> Self {
+        self.calibration_window = window;
+        self
+    }
+
+    pub fn thread_stack(mut self, megabytes: usize) -> Self {
+        self.thread_stack_mb = Some(megabytes);
+        self
+    }
+
+    pub fn batch_size(mut self, calls: u64) -> Self {
+        self.batch_size = Some(calls);
+        self
+    }
+
+    /// Stop each run once the mean is known to within `fraction` (0.01
+    /// is ±1%), or at the loops
+    pub fn target_precision(mut self, fraction: f64) -> Self {
+        self.target_precision = Some(fraction);
+        self
+    }
+
+    pub fn diff(mut self, old: impl Into<PathBuf>, new: impl Into<PathBuf>) -> Self {
+        self.diff = Some((old.into(), new.into()));
+        self
+    }
+
+    pub fn history(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history = Some(path.into());
+        self
+    }
+
+    pub fn realtime(mut self, enabled: bool) -> Self {
+        self.realtime = enabled;
+        self
+    }
+
+    pub fn calibration_cache(mut self, enabled: bool) -> Self {
+        self.calibration_cache = enabled;
+        self
+    }
+
+    pub fn histogram(mut self, spec: HistogramSpec) -> Self {
+        self.histogram = spec;
+        self
+    }
+
+    pub fn relative_to(mut self, name: impl Into<String>) -> Self {
+        self.relative_to = Some(name.into());
+        self
+    }
+
+    pub fn list_backends(mut self, enabled: bool) -> Self {
+        self.list_backends = enabled;
+        self
+    }
+
+    pub fn backend(mut self, backend: TimerBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn plot(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.plot = Some(prefix.into());
+        self
+    }
+
+    pub fn sweep(mut self, enabled: bool) -> Self {
+        self.sweep = enabled;
+        self
+    }
+
+    pub fn sweep_cpus(mut self, enabled: bool) -> Self {
+        self.sweep_cpus = enabled;
+        self
+    }
+
+    pub fn timeline(mut self, path: impl Into<PathBuf>) -> Self {
+        self.timeline = Some(path.into());
+        self
+    }
+
+    pub fn version(mut self, enabled: bool) -> Self {
+        self.version = enabled;
+        self
+    }
+
+    pub fn recommend(mut self, enabled: bool) -> Self {
+        self.recommend = enabled;
+        self
+    }
+
+    pub fn label_width(mut self, width: usize) -> Self {
+        self.label_width = width;
+        self
+    }
+
+    pub fn scaling(mut self, enabled: bool) -> Self {
+        self.scaling = enabled;
+        self
+    }
+
+    pub fn trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    /// Fail if `name`'s median ns/call exceeds `max_ns` (repeatable)
+    pub fn assertion(mut self, name: impl Into<String>, max_ns: f64) -> Self {
+        self.assertions.push((name.into(), max_ns));
+        self
+    }
+
+    /// Label every result with `key=value` (repeatable); see
+    /// `validate_tag` for what `build()` accepts
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn fail_fast(mut self, enabled: bool) -> Self {
+        self.fail_fast = enabled;
+        self
+    }
+
+    pub fn freq_monitor(mut self, duration: Duration) -> Self {
+        self.freq_monitor = Some(duration);
+        self
+    }
+
+    pub fn validate(mut self, duration: Duration) -> Self {
+        self.validate = Some(duration);
+        self
+    }
+
+    pub fn clock_drift(mut self, duration: Duration) -> Self {
+        self.clock_drift = Some(duration);
+        self
+    }
+
+    pub fn dump_calibration(mut self, enabled: bool) -> Self {
+        self.dump_calibration = enabled;
+        self
+    }
+
+    pub fn high_timer_res(mut self, enabled: bool) -> Self {
+        self.high_timer_res = enabled;
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn max_runtime(mut self, budget: Duration) -> Self {
+        self.max_runtime = Some(budget);
+        self
+    }
+
+    /// Whether the `max_runtime` deadline has passed
+    pub fn past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Validate and finish the config, resolving `Format::Auto`
+    pub fn build(mut self) -> Result<RunConfig, String> {
+        if self.loops == Some(0) {
+            return Err("loops must be greater than 0".to_string());
+        }
+        if self.runs == 0 {
+            return Err("runs must be greater than 0".to_string());
+        }
+        if self.discard_runs >= self.runs {
+            return Err(format!("discard runs ({}) must be less than runs ({})", self.discard_runs, self.runs));
+        }
+        if self.calibration_window.is_zero() {
+            return Err("calibration window must be greater than 0".to_string());
+        }
+        if self.histogram.buckets == 0 {
+            return Err("histogram buckets must be greater than 0".to_string());
+        }
+        if self.histogram.max.is_some_and(|max| max.is_nan() || max <= 0.0) {
+            return Err("histogram max must be greater than 0".to_string());
+        }
+        if !(0.0..100.0).contains(&self.trim_percent) {
+            return Err("trim must be in [0, 100)".to_string());
+        }
+        if self.sweep && !matches!(self.format, Format::Auto | Format::Text) {
+            return Err("--sweep only prints a text table; drop --format".to_string());
+        }
+
+        if self.assertions.iter().any(|(_, max_ns)| max_ns.is_nan() || *max_ns <= 0.0) {
+            return Err("assertion limits must be greater than 0".to_string());
+        }
+        for (i, (key, value)) in self.tags.iter().enumerate() {
+            validate_tag(key, value)?;
+            if self.tags[..i].iter().any(|(earlier, _)| earlier == key) {
+                return Err(format!("tag {} given twice", key));
+            }
+        }
+        if self.freq_monitor.is_some_and(|duration| duration.is_zero()) {
+            return Err("frequency monitor duration must be greater than 0".to_string());
+        }
+        if self.validate.is_some_and(|duration| duration.is_zero()) {
+            return Err("validate duration must be greater than 0".to_string());
+        }
+        if self.clock_drift.is_some_and(|duration| duration.is_zero()) {
+            return Err("clock drift duration must be greater than 0".to_string());
+        }
+        if self.max_runtime.is_some_and(|budget| budget.is_zero()) {
+            return Err("max runtime must be greater than 0".to_string());
+        }
+        if self.batch_size == Some(0) {
+            return Err("batch size must be greater than 0".to_string());
+        }
+        if self.target_precision.is_some_and(|target| target.is_nan() || target <= 0.0 || target >= 1.0) {
+            return Err("target precision must be between 0 and 1 (0.01 is 1%)".to_string());
+        }
+        if self.thread_stack_mb == Some(0) {
+            return Err("thread stack must be greater than 0 MB".to_string());
+        }
+
+        let known: Vec<&str> = registry().iter().map(|b| b.name).collect();
+        if let Some(unknown) = self
+            .benchmarks
+            .iter()
+            .chain(&self.relative_to)
+            .chain(self.assertions.iter().map(|(name, _)| name))
+            .find(|b| !known.contains(&b.as_str()))
+        {
+            return Err(format!("unknown benchmark: {} (known: {})", unknown, known.join(", ")));
+        }
+        self.format = if self.sweep {
+            Format::Text
+        } else {
+            self.format.resolve(std::io::stdout().is_terminal())
+        };
+        // started here so calibration counts against the budget too
+        self.deadline = self.max_runtime.map(|budget| Instant::now() + budget);
+        Ok(self)
+    }
+}
+
+/// Why `key=value` can't be a tag, if it can't. Keys are lowercase
+/// `[a-z][a-z0-9_]*`, valid as both a Prometheus label and a benchstat
+/// config key, and not `benchmark` (the Prometheus label every result
+/// has); values may not hold `,` or `;` (the CSV and TSV separators)
+/// or control characters.
+pub fn validate_tag(key: &str, value: &str) -> Result<(), String> {
+    let mut chars = key.chars();
+    let valid_key = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_key {
+        return Err(format!("invalid tag key: {:?} (expected [a-z][a-z0-9_]*)", key));
+    }
+    if key == "benchmark" {
+        return Err("tag key benchmark is reserved".to_string());
+    }
+    if value.chars().any(|c| c == ',' || c == ';' || c.is_control()) {
+        return Err(format!("invalid value for tag {}: {:?} (no ',', ';' or control characters)", key, value));
+    }
+    Ok(())
+}
+
+// ============================================================
+// Driver
+// ============================================================
+
+/// Run the selected benchmarks and return results ranked by cost.
+///
+/// After a stop signal (see `signal::install_stop_handler`) or past the
+/// `max_runtime` deadline no further runs start and whatever was
+/// measured so far is returned. Neither interrupts a run in progress.
+pub fn run(config: &RunConfig) -> Vec<BenchmarkResult> {
+    let mut sink = VecSink::default();
+    run_into(config, &mut sink);
+    rank_by_cost(&mut sink.results);
+    sink.results
+}
+
+/// Run `f` on a new thread with a `stack_mb` MiB stack and return its
+/// result, for benchmarks that recurse deeply (`--thread-stack`).
+///
+/// The thread gets the config's CPU pin, SCHED_FIFO and Windows priority
+/// itself, as Windows doesn't pass affinity or thread priority on to new
+/// threads; failures were already reported when the main thread tried
+/// the same. A panic in `f` resumes on the caller.
+pub fn on_measurement_thread<T: Send>(
+    config: &RunConfig,
+    stack_mb: usize,
+    f: impl FnOnce() -> T + Send,
+) -> std::io::Result<T> {
+    std::thread::scope(|scope| {
+        let worker = std::thread::Builder::new()
+            .name("measure".to_string())
+            .stack_size(stack_mb.saturating_mul(1024 * 1024))
+            .spawn_scoped(scope, || {
+                if let Some(cpu) = config.cpu {
+                    let _ = pin_to_cpu(cpu);
+                }
+                if config.realtime {
+                    let _ = set_realtime_priority();
+                }
+                let _priority = config.priority.map(raise_priority);
+                f()
+            })?;
+        Ok(worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+    })
+}
+
+/// Like `run`, but hands each benchmark's result to `sink` as soon as
+/// it finishes, in selection order. Call `sink.finish()` afterwards.
+///
+/// With `fail_fast`, stops after the first result that fails its
+/// assertion (see `assertion_failure`); that result is still recorded.
+pub fn run_into(config: &RunConfig, sink: &mut dyn ResultSink) {
+    let mut pressure = config.memory_pressure_mb.map(MemoryPressure::new);
+
+    let selected = selected_benchmarks(config);
+
+    for mut bench in selected {
+        if should_stop(config) {
+            break;
+        }
+        let loops = config.loops.unwrap_or(bench.default_loops);
+        let first_call_ns = measure_first_call(&mut bench);
+        bench.run(config.warmup);
+        trace_event(TraceKind::WarmupDone, || format!("{} x{}", bench.name, config.warmup));
+
+        let mut runs = Vec::new();
+        let mut warnings = Vec::new();
+        let mut interrupts = Some(0);
+        for run in 1..=config.runs {
+            if should_stop(config) {
+                break;
+            }
+            trace_event(TraceKind::RunStart, || format!("{} run {}", bench.name, run));
+            let batch_size = config.batch_size.or(bench.batch).unwrap_or_else(|| match config.target_precision {
+                Some(_) => precision_batch_size(loops),
+                None => default_batch_size(loops),
+            });
+            let between_batches = || {
+                if let Some(pressure) = pressure.as_mut() {
+                    pressure.apply();
+                }
+            };
+            // only the timed batches: calibration, warmup and the other
+            // runs' checks stay out of the count
+            let cpu = current_cpu();
+            let interrupts_before = cpu.and_then(cpu_interrupts);
+            let result = match config.target_precision {
+                Some(target) => measure_until_precise(&mut bench, loops, batch_size, target, between_batches),
+                None => measure_batched(&mut bench, loops, batch_size, between_batches),
+            };
+            let run_interrupts = cpu
+                .filter(|&cpu| current_cpu() == Some(cpu))
+                .and_then(cpu_interrupts)
+                .zip(interrupts_before)
+                .map(|(after, before)| after.saturating_sub(before));
+            if run > config.discard_runs {
+                interrupts = interrupts.zip(run_interrupts).map(|(total, run)| total + run);
+            }
+            trace_event(TraceKind::RunEnd, || format!("{} run {}", bench.name, run));
+            if result.clock_anomaly {
+                let warning = Warning::new(
+                    WarningKind::ClockAnomaly,
+                    format!(
+                        "{} run {}: timer and wall clock disagree on its length (suspend or clock step?); \
+                         kept and flagged",
+                        bench.name, run
+                    ),
+                );
+                eprintln!("Warning: {}", warning);
+                warnings.push(warning);
+            }
+            if let Some(target) = config.target_precision {
+                let moments = RunningMoments::from_samples(&result.samples);
+                let reached = moments.relative_half_width() * 100.0;
+                if precision_reached(&moments, target) {
+                    eprintln!(
+                        "Precision: {} run {} converged to ±{:.2}% after {} samples",
+                        bench.name, run, reached, moments.len()
+                    );
+                } else {
+                    let warning = Warning::new(
+                        WarningKind::PrecisionNotReached,
+                        format!(
+                            "{} run {}: ±{:.2}% after {} samples ({} loops), short of the ±{}% target",
+                            bench.name, run, reached, moments.len(), result.loops, target * 100.0
+                        ),
+                    );
+                    eprintln!("Warning: {}", warning);
+                    warnings.push(warning);
+                }
+            }
+            runs.push(result);
+        }
+
+        if let Some(mut result) = merge_warm_runs(runs, config.discard_runs as usize).filter(|r| r.loops > 0) {
+            result.first_call_ns = Some(first_call_ns);
+            result.interrupts = interrupts;
+            let tail_samples = result.samples.iter().filter(|&&ns| ns > result.stats.p99).count();
+            if let Some(message) = interrupts.and_then(|count| interrupt_warning(count, result.loops, tail_samples)) {
+                let warning = Warning::new(WarningKind::Interrupts, format!("{}: {}", result.name, message));
+                eprintln!("Warning: {}", warning);
+                warnings.push(warning);
+            }
+            warnings.extend(result_warnings(&result, config));
+            result.warnings = warnings;
+            sink.record(&result);
+            if config.fail_fast && assertion_failure(config, &result).is_some() {
+                break;
+            }
+        }
+    }
+}
+
+/// How `result` broke its `--assert` limit, if it has one and its
+/// median ns/call is above it
+pub fn assertion_failure(config: &RunConfig, result: &BenchmarkResult) -> Option<AssertionFailure> {
+    let &(_, max_ns) = config.assertions.iter().find(|(name, _)| *name == result.name)?;
+    (result.stats.p50 > max_ns).then(|| AssertionFailure {
+        name: result.name.clone(),
+        median_ns: result.stats.p50,
+        max_ns,
+    })
+}
+
+/// Sweep the selected benchmarks (see `sweep_benchmark`), in
+/// selection order
+pub fn sweep(config: &RunConfig) -> Vec<SweepResult> {
+    let mut results = Vec::new();
+    for mut bench in selected_benchmarks(config) {
+        if should_stop(config) {
+            break;
+        }
+        bench.run(config.warmup);
+        results.push(sweep_benchmark(&mut bench));
+    }
+    results
+}
+
+/// Time between `monitor_frequency` recalibrations
+pub const FREQ_MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `recalibrate()` once per `interval` until `duration` is covered
+/// (at least once), passing each outcome and when it was taken to
+/// `each`; returns the accepted frequencies in order.
+///
+/// Replaces the global frequency each time, so timers started before
+/// aren't comparable with later ones. Stops early on a stop signal.
+pub fn monitor_frequency(
+    duration: Duration,
+    interval: Duration,
+    mut each: impl FnMut(Duration, Result<u64, &CalibrationError>),
+) -> Vec<u64> {
+    let count = (duration.as_nanos() / interval.as_nanos().max(1)).max(1);
+    let start = Instant::now();
+    let mut samples = Vec::with_capacity(count as usize);
+    for i in 0..count as u32 {
+        if stop_requested() {
+            break;
+        }
+        // scheduled from the start, so calibration time doesn't add up
+        if let Some(wait) = (start + interval * i).checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+        match recalibrate() {
+            Ok(hz) => {
+                each(start.elapsed(), Ok(hz));
+                samples.push(hz);
+            }
+            Err(err) => each(start.elapsed(), Err(&err)),
+        }
+    }
+    samples
+}
+
+/// Largest |error| (percent) `--validate` accepts from a healthy
+/// calibration
+pub const VALIDATE_TOLERANCE_PERCENT: f64 = 0.1;
+
+/// How long one interval took by the timer and by SystemTime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerValidation {
+    pub timer_ns: u128,
+    pub wall_ns: u128,
+}
+
+impl TimerValidation {
+    /// Timer minus wall clock, percent of the wall clock: positive when
+    /// the calibrated frequency is too low (the timer runs fast); 0
+    /// when the wall clock saw no time pass
+    pub fn error_percent(&self) -> f64 {
+        if self.wall_ns == 0 {
+            return 0.0;
+        }
+        (self.timer_ns as f64 - self.wall_ns as f64) / self.wall_ns as f64 * 100.0
+    }
+
+    /// Within `VALIDATE_TOLERANCE_PERCENT`
+    pub fn is_healthy(&self) -> bool {
+        self.error_percent().abs() < VALIDATE_TOLERANCE_PERCENT
+    }
+}
+
+/// Sleep for `duration` (cut short by a stop signal) measured both by a
+/// `HighResolutionTimer` and by SystemTime.
+///
+/// Over seconds the ~µs skew between the two start and end reads is
+/// far below the tolerance; an NTP step of the wall clock mid-interval
+/// shows up as error.
+pub fn validate_timer(duration: Duration) -> TimerValidation {
+    // calibrates first if needed, so that is not in the interval
+    let timer = HighResolutionTimer::start();
+    let wall_start = SystemTime::now();
+    let deadline = Instant::now() + duration;
+    while let Some(left) = deadline.checked_duration_since(Instant::now())
+        && !left.is_zero()
+        && !stop_requested()
+    {
+        std::thread::sleep(left.min(Duration::from_millis(100)));
+    }
+    let timer_ns = timer.ns();
+    let wall_ns = wall_start.elapsed().map_or(0, |elapsed| elapsed.as_nanos());
+    TimerValidation { timer_ns, wall_ns }
+}
+
+/// Drift (ppm) below which `--clock-drift` calls the two clocks
+/// equivalent: NTP slews by up to 500 ppm, a settled host by far less
+pub const CLOCK_DRIFT_NEGLIGIBLE_PPM: f64 = 1.0;
+
+/// How long one interval took by CLOCK_MONOTONIC and by
+/// CLOCK_MONOTONIC_RAW
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockDrift {
+    pub monotonic_ns: u128,
+    pub raw_ns: u128,
+}
+
+impl ClockDrift {
+    /// MONOTONIC minus RAW, ppm of RAW: the NTP slew, positive when NTP
+    /// runs the clock fast; 0 when the raw clock saw no time pass
+    pub fn drift_ppm(&self) -> f64 {
+        if self.raw_ns == 0 {
+            return 0.0;
+        }
+        (self.monotonic_ns as f64 - self.raw_ns as f64) / self.raw_ns as f64 * 1e6
+    }
+
+    /// Within `CLOCK_DRIFT_NEGLIGIBLE_PPM`: calibrating against RAW
+    /// buys nothing on this host right now
+    pub fn is_negligible(&self) -> bool {
+        self.drift_ppm().abs() < CLOCK_DRIFT_NEGLIGIBLE_PPM
+    }
+}
+
+/// Sleep for `duration` (cut short by a stop signal) measured by both
+/// CLOCK_MONOTONIC and CLOCK_MONOTONIC_RAW, read in the same order at
+/// both ends so the offset between the reads cancels. None outside
+/// Linux or when either clock is unavailable.
+pub fn measure_clock_drift(duration: Duration) -> Option<ClockDrift> {
+    #[cfg(target_os = "linux")]
+    {
+        let read = |clock| {
+            let mut ts: libc::timespec = unsafe { core::mem::zeroed() };
+            (unsafe { libc::clock_gettime(clock, &mut ts) } == 0)
+                .then(|| ts.tv_sec as u128 * 1_000_000_000 + ts.tv_nsec as u128)
+        };
+        let monotonic_start = read(libc::CLOCK_MONOTONIC)?;
+        let raw_start = read(libc::CLOCK_MONOTONIC_RAW)?;
+        let deadline = Instant::now() + duration;
+        while let Some(left) = deadline.checked_duration_since(Instant::now())
+            && !left.is_zero()
+            && !stop_requested()
+        {
+            std::thread::sleep(left.min(Duration::from_millis(100)));
+        }
+        let monotonic_end = read(libc::CLOCK_MONOTONIC)?;
+        let raw_end = read(libc::CLOCK_MONOTONIC_RAW)?;
+        Some(ClockDrift {
+            monotonic_ns: monotonic_end.saturating_sub(monotonic_start),
+            raw_ns: raw_end.saturating_sub(raw_start),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = duration;
+        None
+    }
+}
+
+/// Results of one `sweep_cpus` run, or why the CPU was skipped
+pub type CpuRun = (usize, Result<Vec<BenchmarkResult>, String>);
+
+/// `run(config)` pinned to each of `cpus` in turn, to compare cores
+/// (P vs E cores, or one misbehaving core).
+///
+/// The calling thread stays pinned to the last CPU afterwards.
+pub fn sweep_cpus(config: &RunConfig, cpus: &[usize]) -> Vec<CpuRun> {
+    let pin = |cpu| {
+        if config.past_deadline() {
+            return Err("skipped, max runtime reached".to_string());
+        }
+        pin_to_cpu(cpu)
+    };
+    for_each_cpu(cpus, pin, |_| run(config))
+}
+
+/// Call `pin` then, if it succeeded, `f` for each of `cpus`; stops at a
+/// stop signal
+pub fn for_each_cpu<T>(
+    cpus: &[usize],
+    mut pin: impl FnMut(usize) -> Result<(), String>,
+    mut f: impl FnMut(usize) -> T,
+) -> Vec<(usize, Result<T, String>)> {
+    let mut runs = Vec::with_capacity(cpus.len());
+    for &cpu in cpus {
+        if stop_requested() {
+            break;
+        }
+        runs.push((cpu, pin(cpu).map(|()| f(cpu))));
+    }
+    runs
+}
+
+/// Stop signal or deadline: checked between benchmarks and runs
+fn should_stop(config: &RunConfig) -> bool {
+    stop_requested() || config.past_deadline()
+}
+
+/// Benchmarks named in `config` (every clock source if none), plus the
+/// `--relative-to` baseline and asserted benchmarks, which always run
+fn selected_benchmarks(config: &RunConfig) -> Vec<Benchmark> {
+    let mut selected: Vec<Benchmark> = if config.benchmarks.is_empty() {
+        clock_sources()
+    } else {
+        registry()
+            .into_iter()
+            .filter(|b| config.benchmarks.iter().any(|n| n == b.name))
+            .collect()
+    };
+    let always = config.relative_to.iter().chain(config.assertions.iter().map(|(name, _)| name));
+    for name in always {
+        if !selected.iter().any(|b| b.name == name) {
+            selected.extend(registry().into_iter().filter(|b| b.name == name));
+        }
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_defaults_and_overrides() {
+        let config = RunConfig::new();
+        assert_eq!(config.loops, None);
+        assert_eq!(config.warmup, 0);
+        assert_eq!(config.runs, 1);
+        assert_eq!(config.format, Format::Auto);
+        assert_eq!(config.precision, 3);
+        assert_eq!(config.cpu, None);
+        assert!(config.benchmarks.is_empty());
+        // build() settles Auto on a concrete format
+        assert_ne!(config.build().unwrap().format, Format::Auto);
+
+        let config = RunConfig::new()
+            .loops(500)
+            .warmup(10)
+            .runs(3)
+            .format(Format::Json)
+            .precision(1)
+            .benchmark("instant")
+            .build()
+            .unwrap();
+        assert_eq!(config.loops, Some(500));
+        assert_eq!(config.warmup, 10);
+        assert_eq!(config.runs, 3);
+        assert_eq!(config.format, Format::Json);
+        assert_eq!(config.precision, 1);
+        assert_eq!(config.benchmarks, ["instant"]);
+
+        assert!(RunConfig::new().loops(0).build().is_err());
+        assert!(RunConfig::new().runs(0).build().is_err());
+    }
+
+    #[test]
+    fn auto_format_follows_the_terminal() {
+        assert_eq!(Format::Auto.resolve(true), Format::Text);
+        assert_eq!(Format::Auto.resolve(false), Format::Json);
+        // an explicit choice wins either way
+        assert_eq!(Format::Csv.resolve(true), Format::Csv);
+        assert_eq!(Format::Text.resolve(false), Format::Text);
+    }
+
+    #[test]
+    fn sweep_rejects_machine_formats() {
+        assert_eq!(RunConfig::new().sweep(true).build().unwrap().format, Format::Text);
+        assert!(RunConfig::new().sweep(true).format(Format::Text).build().is_ok());
+        assert!(RunConfig::new().sweep(true).format(Format::Json).build().is_err());
+        assert!(RunConfig::new().sweep(true).format(Format::Csv).build().is_err());
+    }
+
+    #[test]
+    fn cpu_sweep_runs_once_per_cpu() {
+        let mut pinned = Vec::new();
+        let mut ran = Vec::new();
+        let runs = for_each_cpu(
+            &[0, 2, 5],
+            |cpu| {
+                pinned.push(cpu);
+                if cpu == 2 { Err("offline".to_string()) } else { Ok(()) }
+            },
+            |cpu| {
+                ran.push(cpu);
+                cpu * 10
+            },
+        );
+        assert_eq!(pinned, [0, 2, 5]);
+        // not run where the pin failed
+        assert_eq!(ran, [0, 5]);
+        assert_eq!(runs, [(0, Ok(0)), (2, Err("offline".to_string())), (5, Ok(50))]);
+    }
+
+    #[test]
+    fn trace_records_warmup_and_runs_in_order() {
+        use crate::trace::{disable_trace, enable_trace, take_trace, TraceKind};
+
+        let _lock = crate::high_resolution_timer::test_lock();
+        let _ = crate::high_resolution_timer::tick_hz(); // calibrate outside the trace
+        enable_trace();
+        take_trace();
+        let config = RunConfig::new().benchmark("ticks").loops(1_000).warmup(10).runs(2);
+        let results = run(&config);
+        disable_trace();
+        assert_eq!(results.len(), 1);
+        let events: Vec<(TraceKind, String)> = take_trace()
+            .into_iter()
+            .filter(|e| e.detail.starts_with("ticks "))
+            .map(|e| (e.kind, e.detail))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (TraceKind::WarmupDone, "ticks x10".to_string()),
+                (TraceKind::RunStart, "ticks run 1".to_string()),
+                (TraceKind::RunEnd, "ticks run 1".to_string()),
+                (TraceKind::RunStart, "ticks run 2".to_string()),
+                (TraceKind::RunEnd, "ticks run 2".to_string()),
+            ]
+        );
+
+        // nothing is recorded once disabled
+        run(&config);
+        assert!(take_trace().is_empty());
+    }
+
+    #[test]
+    fn nothing_starts_past_the_deadline() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let mut config = RunConfig::new()
+            .benchmark("ticks")
+            .benchmark("hrt")
+            .loops(1_000)
+            .max_runtime(Duration::from_micros(1))
+            .build()
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(config.past_deadline());
+        assert!(run(&config).is_empty());
+        let skipped: Vec<(usize, Option<String>)> =
+            sweep_cpus(&config, &[0, 1]).into_iter().map(|(cpu, run)| (cpu, run.err())).collect();
+        let reason = Some("skipped, max runtime reached".to_string());
+        assert_eq!(skipped, [(0, reason.clone()), (1, reason)]);
+
+        config.deadline = Some(Instant::now() + Duration::from_secs(600));
+        assert_eq!(run(&config).len(), 2);
+    }
+
+    #[test]
+    fn monitor_takes_one_sample_per_interval() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        // intervals longer than one calibration window
+        let interval = Duration::from_millis(40);
+        let mut taken = Vec::new();
+        let samples = monitor_frequency(3 * interval, interval, |at, hz| taken.push((at, hz.ok())));
+        let short = monitor_frequency(Duration::from_millis(1), interval, |_, _| {});
+
+        assert_eq!(taken.len(), 3);
+        assert_eq!(taken.iter().filter_map(|&(_, hz)| hz).collect::<Vec<_>>(), samples);
+        for (i, (at, _)) in taken.iter().enumerate() {
+            assert!(*at >= interval * i as u32, "sample {} at {:?}", i, at);
+        }
+        // at least once
+        assert_eq!(short.len(), 1);
+    }
+
+    #[test]
+    fn fail_fast_stops_after_the_first_failure() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let ran = |config: RunConfig| {
+            let mut sink = crate::sink::VecSink::default();
+            run_into(&config.benchmark("ticks").benchmark("hrt").loops(1_000), &mut sink);
+            sink.results.into_iter().map(|r| r.name).collect::<Vec<_>>()
+        };
+        // no clock read takes a femtosecond
+        let failing = RunConfig::new().assertion("ticks", 1e-6).assertion("hrt", 1e-6);
+
+        let all = ran(failing.clone());
+        assert_eq!(all.len(), 2);
+        assert_eq!(ran(failing.fail_fast(true)), all[..1]);
+        let passing = RunConfig::new().assertion("ticks", 1e9).assertion("hrt", 1e9).fail_fast(true);
+        assert_eq!(ran(passing), all);
+    }
+
+    /// Recurse `depth` frames of about 1 KiB each
+    fn deep(depth: u32) -> u32 {
+        let frame = std::hint::black_box([depth as u8; 1024]);
+        if depth == 0 { frame[0] as u32 } else { deep(depth - 1) + (frame[1023] != 0) as u32 }
+    }
+
+    #[test]
+    fn deep_recursion_runs_on_a_large_stack() {
+        // ~16 MiB of frames: past the default 2 MiB test thread stack
+        let config = RunConfig::new();
+        let result = on_measurement_thread(&config, 64, || {
+            let mut bench = Benchmark::new("deep", "deep recursion", || deep(16_000));
+            crate::benchmark::measure(&mut bench, 3).loops
+        })
+        .unwrap();
+        assert_eq!(result, 3);
+    }
+
+    #[test]
+    fn validation_error_is_relative_to_the_wall_clock() {
+        let fast = TimerValidation { timer_ns: 1_005_000_000, wall_ns: 1_000_000_000 };
+        assert!((fast.error_percent() - 0.5).abs() < 1e-9);
+        let slow = TimerValidation { timer_ns: 900_000_000, wall_ns: 1_000_000_000 };
+        assert!((slow.error_percent() + 10.0).abs() < 1e-9);
+        assert_eq!(TimerValidation { timer_ns: 5, wall_ns: 0 }.error_percent(), 0.0);
+
+        // the tolerance is 0.1%
+        assert!(TimerValidation { timer_ns: 1_000_500_000, wall_ns: 1_000_000_000 }.is_healthy());
+        assert!(!fast.is_healthy() && !slow.is_healthy());
+    }
+
+    #[test]
+    fn drift_is_ppm_of_the_raw_clock() {
+        // NTP slewing the clock 50 ppm fast over one second
+        let slewed = ClockDrift { monotonic_ns: 1_000_050_000, raw_ns: 1_000_000_000 };
+        assert!((slewed.drift_ppm() - 50.0).abs() < 1e-6);
+        let slow = ClockDrift { monotonic_ns: 1_999_999_000, raw_ns: 2_000_000_000 };
+        assert!((slow.drift_ppm() + 0.5).abs() < 1e-6);
+        assert_eq!(ClockDrift { monotonic_ns: 7, raw_ns: 0 }.drift_ppm(), 0.0);
+
+        assert!(!slewed.is_negligible() && slow.is_negligible());
+        if cfg!(target_os = "linux") {
+            let drift = measure_clock_drift(Duration::from_millis(20)).unwrap();
+            assert!(drift.raw_ns >= 20_000_000 && drift.monotonic_ns >= 20_000_000, "{:?}", drift);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// ============================================================
+// Graceful stop on SIGINT / SIGTERM
+// ============================================================
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a stop signal has arrived since `install_stop_handler()`
+#[inline(always)]
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Install a SIGINT/SIGTERM (Ctrl-C/close on Windows) handler that asks
+/// the driver to stop so partial results still get printed.
+///
+/// A second signal exits immediately.
+pub fn install_stop_handler() -> Result<(), String> {
+    #[cfg(unix)]
+    unsafe {
+        let mut action: libc::sigaction = core::mem::zeroed();
+        action.sa_sigaction = on_signal as *const () as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            if libc::sigaction(signal, &action, core::ptr::null_mut()) != 0 {
+                return Err(format!("sigaction failed: {}", std::io::Error::last_os_error()));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+        if SetConsoleCtrlHandler(Some(on_console_event), 1) == 0 {
+            return Err(format!("SetConsoleCtrlHandler failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err("signal handling is not supported on this target".to_string())
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    // only async-signal-safe work here
+    if STOP_REQUESTED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::_exit(130) };
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_console_event(_event: u32) -> windows_sys::Win32::Foundation::BOOL {
+    if STOP_REQUESTED.swap(true, Ordering::Relaxed) {
+        std::process::exit(130);
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn installs_on_host() {
+        assert_eq!(install_stop_handler(), Ok(()));
+        // installing must not look like a signal
+        assert!(!stop_requested());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::benchmark::{rank_by_cost, BenchmarkResult};
+use crate::report::print_results;
+use crate::runner::RunConfig;
+use crate::warning::Warning;
+
+// ============================================================
+// Result sinks
+// ============================================================
+
+/// Where `runner::run_into` sends results: stdout, a metrics system,
+/// a file, memory
+pub trait ResultSink {
+    /// One benchmark's merged runs, as soon as it finishes (unranked)
+    fn record(&mut self, result: &BenchmarkResult);
+
+    /// Called once after the last result; for sinks that need the whole
+    /// set, like a ranked table or a JSON array
+    fn finish(&mut self) {}
+}
+
+/// Prints in `config.format` at `finish()`, ranked by cost, exactly as
+/// the binary does
+#[derive(Debug, Clone)]
+pub struct StdoutSink {
+    config: RunConfig,
+    results: Vec<BenchmarkResult>,
+}
+
+impl StdoutSink {
+    pub fn new(config: RunConfig) -> Self {
+        Self { config, results: Vec::new() }
+    }
+
+    /// Results recorded so far; ranked once `finish()` has run
+    pub fn results(&self) -> &[BenchmarkResult] {
+        &self.results
+    }
+
+    /// Add run-wide warnings (setup, system noise) to every result
+    /// recorded so far, before `finish()` prints them
+    pub fn add_run_warnings(&mut self, warnings: &[Warning]) {
+        for result in &mut self.results {
+            result.warnings.extend_from_slice(warnings);
+        }
+    }
+}
+
+impl ResultSink for StdoutSink {
+    fn record(&mut self, result: &BenchmarkResult) {
+        self.results.push(result.clone());
+    }
+
+    fn finish(&mut self) {
+        rank_by_cost(&mut self.results);
+        print_results(&self.results, &self.config);
+    }
+}
+
+/// Collects results in memory, in the order they finished
+#[derive(Debug, Clone, Default)]
+pub struct VecSink {
+    pub results: Vec<BenchmarkResult>,
+}
+
+impl ResultSink for VecSink {
+    fn record(&mut self, result: &BenchmarkResult) {
+        self.results.push(result.clone());
+    }
+}
+
+/// Feed latencies measured elsewhere (ns, one per call) through the
+/// same stats, histogram and output as a benchmark, via `sink.record`.
+///
+/// Call `sink.finish()` after the last one, as `run_into` does.
+pub fn report_external(name: &str, samples: &[u64], sink: &mut dyn ResultSink) -> BenchmarkResult {
+    let result = BenchmarkResult::from_samples(name, samples);
+    sink.record(&result);
+    result
+}
+
+/// Drops every result (e.g. a warmup pass)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullSink;
+
+impl ResultSink for NullSink {
+    fn record(&mut self, _result: &BenchmarkResult) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vec_sink_captures_what_ran() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let config = RunConfig::new().benchmark("ticks").benchmark("hrt").loops(2_000);
+        let mut sink = VecSink::default();
+        crate::runner::run_into(&config, &mut sink);
+        let mut ran: Vec<(&str, u64)> = sink.results.iter().map(|r| (r.name.as_str(), r.loops)).collect();
+        ran.sort();
+        assert_eq!(ran, [("hrt", 2_000), ("ticks", 2_000)]);
+    }
+
+    #[test]
+    fn external_samples_get_benchmark_stats() {
+        let mut sink = VecSink::default();
+        let result = report_external("rpc", &[50, 10, 40, 20, 30], &mut sink);
+        assert_eq!(sink.results.len(), 1);
+        assert_eq!((result.loops, result.total_ns, result.ns_per_call()), (5, 150, 30.0));
+        let stats = &sink.results[0].stats;
+        assert_eq!((stats.n, stats.min, stats.max, stats.mean), (5, 10.0, 50.0, 30.0));
+        assert_eq!((stats.p25, stats.p50, stats.p90), (20.0, 30.0, 50.0));
+        assert!((stats.stddev - 250f64.sqrt()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+// ============================================================
+// Summary statistics
+// ============================================================
+
+/// Summary of per-call nanosecond samples
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stats {
+    pub n: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sample standard deviation
+    pub stddev: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl Stats {
+    /// Summarize samples (ns per call). Empty input yields all zeros.
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let variance = if n > 1 {
+            sorted.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+
+        Self {
+            n,
+            min: sorted[0],
+            max: sorted[n - 1],
+            mean,
+            stddev: variance.sqrt(),
+            p25: percentile(&sorted, 25.0),
+            p50: percentile(&sorted, 50.0),
+            p75: percentile(&sorted, 75.0),
+            p90: percentile(&sorted, 90.0),
+            p99: percentile(&sorted, 99.0),
+        }
+    }
+
+    /// Jitter as max - min: the full range, dominated by the single
+    /// worst outlier
+    pub fn peak_to_peak(&self) -> f64 {
+        self.max - self.min
+    }
+
+    /// Jitter as p75 - p25: the spread of the middle half, robust to
+    /// outliers
+    pub fn iqr(&self) -> f64 {
+        self.p75 - self.p25
+    }
+
+    /// Summary of both runs together, without their samples.
+    ///
+    /// n, min, max, mean and stddev are exact (parallel variance
+    /// combination); percentiles are n-weighted averages of the two,
+    /// an approximation. Recompute with `from_samples` when the samples
+    /// are still around, as `merge_runs` does.
+    pub fn combine(&self, other: &Stats) -> Stats {
+        if self.n == 0 {
+            return *other;
+        }
+        if other.n == 0 {
+            return *self;
+        }
+
+        let (n_a, n_b) = (self.n as f64, other.n as f64);
+        let n = n_a + n_b;
+        let delta = other.mean - self.mean;
+        let mean = self.mean + delta * n_b / n;
+        // sums of squared deviations from each mean, then about the new one
+        let m2 = self.stddev.powi(2) * (n_a - 1.0)
+            + other.stddev.powi(2) * (n_b - 1.0)
+            + delta * delta * n_a * n_b / n;
+        let weighted = |a: f64, b: f64| (a * n_a + b * n_b) / n;
+
+        Stats {
+            n: self.n + other.n,
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            mean,
+            stddev: if n > 1.0 { (m2 / (n - 1.0)).sqrt() } else { 0.0 },
+            p25: weighted(self.p25, other.p25),
+            p50: weighted(self.p50, other.p50),
+            p75: weighted(self.p75, other.p75),
+            p90: weighted(self.p90, other.p90),
+            p99: weighted(self.p99, other.p99),
+        }
+    }
+}
+
+/// One line, in ns: `n=100 min=.. p50=.. p90=.. p99=.. max=.. mean=..±..
+/// iqr=.. p2p=..`.
+/// The format precision (`{:.1}`) sets the decimals, default 3.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let p = f.precision().unwrap_or(3);
+        write!(
+            f,
+            "n={} min={:.p$} p50={:.p$} p90={:.p$} p99={:.p$} max={:.p$} mean={:.p$}±{:.p$} iqr={:.p$} p2p={:.p$} ns",
+            self.n,
+            self.min,
+            self.p50,
+            self.p90,
+            self.p99,
+            self.max,
+            self.mean,
+            self.stddev,
+            self.iqr(),
+            self.peak_to_peak()
+        )
+    }
+}
+
+/// Nearest-rank percentile of an ascending-sorted slice
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// 95% confidence interval for the mean: mean -+ 1.96 * stddev /
+/// sqrt(n), the normal approximation (fine from a few dozen samples;
+/// too narrow below that). Just the mean when n < 2.
+pub fn confidence_interval_95(stats: &Stats) -> (f64, f64) {
+    if stats.n < 2 {
+        return (stats.mean, stats.mean);
+    }
+    let half_width = 1.96 * stats.stddev / (stats.n as f64).sqrt();
+    (stats.mean - half_width, stats.mean + half_width)
+}
+
+/// Distribution-free ~95% confidence interval for the median: the
+/// order statistics at ranks n/2 -+ 1.96 * sqrt(n) / 2 (binomial
+/// normal approximation). (0, 0) for no samples; with few samples it
+/// widens to the whole range.
+pub fn median_confidence_interval(samples: &[f64]) -> (f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let n = sorted.len() as f64;
+    let half_width = 1.96 * n.sqrt() / 2.0;
+    let lower = ((n / 2.0 - half_width).floor() as usize).clamp(1, sorted.len());
+    let upper = ((n / 2.0 + half_width).ceil() as usize).clamp(1, sorted.len());
+    // one-based ranks
+    (sorted[lower - 1], sorted[upper - 1])
+}
+
+/// Mean after dropping the slowest `trim_percent`% of samples.
+///
+/// Noise (interrupts, migrations) only ever adds time, so only the top
+/// tail is trimmed. At least one sample is always kept.
+pub fn trimmed_mean(samples: &[f64], trim_percent: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let drop = ((trim_percent.clamp(0.0, 100.0) / 100.0) * sorted.len() as f64).floor() as usize;
+    let kept = &sorted[..(sorted.len() - drop).max(1)];
+    kept.iter().sum::<f64>() / kept.len() as f64
+}
+
+/// Most decimals `significant_digits` ever allows
+pub const MAX_SIGNIFICANT_DECIMALS: u8 = 9;
+
+/// Decimal places of a mean per-call ns that the timer can back up.
+///
+/// Each sample is off by up to one tick (`resolution_ns`) spread over
+/// `batch_size` calls, and averaging `samples` of them shrinks that by
+/// sqrt(samples); the last digit kept is no finer than what's left.
+/// E.g. a 0.4 ns tick, batches of 1 and 1000 samples give 1 ("4.2").
+pub fn significant_digits(resolution_ns: f64, batch_size: u64, samples: usize) -> u8 {
+    let uncertainty = resolution_ns / (batch_size.max(1) as f64 * (samples.max(1) as f64).sqrt());
+    if !uncertainty.is_finite() || uncertainty <= 0.0 {
+        return MAX_SIGNIFICANT_DECIMALS;
+    }
+    (-uncertainty.log10()).floor().clamp(0.0, MAX_SIGNIFICANT_DECIMALS as f64) as u8
+}
+
+// ============================================================
+// Rolling window
+// ============================================================
+
+/// Keeps only the last `window` samples, for continuous monitoring
+/// with bounded memory
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    samples: VecDeque<f64>,
+    window: usize,
+}
+
+impl RollingStats {
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    /// Add a sample (ns), evicting the oldest once the window is full
+    pub fn record(&mut self, ns: u64) {
+        if self.window == 0 {
+            return;
+        }
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ns as f64);
+    }
+
+    /// Summary over the samples currently in the window
+    pub fn snapshot(&self) -> Stats {
+        let (front, back) = self.samples.as_slices();
+        Stats::from_samples(&[front, back].concat())
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+// ============================================================
+// Running moments
+// ============================================================
+
+/// Count, mean and variance of a stream, updated per sample (Welford),
+/// for stopping rules checked after every batch
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RunningMoments {
+    n: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningMoments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moments of samples already collected
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let mut moments = Self::new();
+        for &x in samples {
+            moments.record(x);
+        }
+        moments
+    }
+
+    /// Add a sample (ns per call)
+    pub fn record(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Sample standard deviation; 0 below two samples
+    pub fn stddev(&self) -> f64 {
+        if self.n > 1 { (self.m2 / (self.n - 1) as f64).sqrt() } else { 0.0 }
+    }
+
+    /// Half-width of the mean's 95% confidence interval (as in
+    /// `confidence_interval_95`) over the mean: 0.01 is ±1%. Infinite
+    /// below two samples or at a zero mean with any spread.
+    pub fn relative_half_width(&self) -> f64 {
+        if self.n < 2 {
+            return f64::INFINITY;
+        }
+        let half_width = 1.96 * self.stddev() / (self.n as f64).sqrt();
+        if half_width == 0.0 { 0.0 } else { half_width / self.mean.abs() }
+    }
+}
+
+/// Samples `precision_reached` waits for whatever the spread: the
+/// normal approximation behind the interval is poor below a few dozen
+pub const MIN_PRECISION_SAMPLES: usize = 30;
+
+/// Whether the mean is known to within `target` (a fraction of it, at
+/// 95% confidence), from at least `MIN_PRECISION_SAMPLES` samples
+pub fn precision_reached(moments: &RunningMoments, target: f64) -> bool {
+    moments.len() >= MIN_PRECISION_SAMPLES && moments.relative_half_width() <= target
+}
+
+// ============================================================
+// Histogram
+// ============================================================
+
+/// Default bucket count for latency histograms
+pub const HISTOGRAM_BUCKETS: usize = 16;
+
+/// Bucket spacing for histograms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistogramScale {
+    /// Log-spaced: keeps the fast body and the slow tail both visible
+    Log,
+    /// Evenly spaced: for zooming into a range, e.g. 0..100 ns
+    Linear,
+}
+
+impl std::str::FromStr for HistogramScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(HistogramScale::Log),
+            "linear" => Ok(HistogramScale::Linear),
+            other => Err(format!("unknown histogram scale: {}", other)),
+        }
+    }
+}
+
+/// Bucket count, upper bound and spacing of a histogram
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistogramSpec {
+    pub buckets: usize,
+    /// Explicit upper bound in ns. Samples at or above it go to one
+    /// extra overflow bucket at the end; None spans the samples' max.
+    pub max: Option<f64>,
+    pub scale: HistogramScale,
+}
+
+impl Default for HistogramSpec {
+    fn default() -> Self {
+        Self {
+            buckets: HISTOGRAM_BUCKETS,
+            max: None,
+            scale: HistogramScale::Log,
+        }
+    }
+}
+
+/// Count samples into buckets as described by `spec`.
+///
+/// Linear buckets start at 0 when `max` is given, else at the smallest
+/// sample; log buckets start at the smallest positive sample, with
+/// non-positive samples in the first bucket. With `max` set the result
+/// has `buckets + 1` entries, the last counting samples >= max.
+pub fn histogram(samples: &[f64], spec: &HistogramSpec) -> Vec<u64> {
+    let buckets = spec.buckets;
+    let mut counts = vec![0u64; buckets + usize::from(spec.max.is_some())];
+    if buckets == 0 || samples.is_empty() {
+        return counts;
+    }
+
+    let in_range: Vec<f64> = match spec.max {
+        Some(max) => {
+            let overflow = samples.iter().filter(|&&x| x >= max).count();
+            counts[buckets] = overflow as u64;
+            samples.iter().copied().filter(|&x| x < max).collect()
+        }
+        None => samples.to_vec(),
+    };
+    if in_range.is_empty() {
+        return counts;
+    }
+
+    let log = spec.scale == HistogramScale::Log;
+    let coord = |x: f64| if log { x.ln() } else { x };
+    // log can only span positive samples
+    let spanned = in_range.iter().copied().filter(|&x| !log || x > 0.0);
+    let min = if !log && spec.max.is_some() {
+        0.0
+    } else {
+        spanned.clone().fold(f64::INFINITY, f64::min)
+    };
+    let max = spec.max.unwrap_or_else(|| spanned.fold(f64::MIN, f64::max));
+    if min >= max {
+        counts[0] += in_range.len() as u64;
+        return counts;
+    }
+
+    let (lo, hi) = (coord(min), coord(max));
+    for x in in_range {
+        let index = if x <= min {
+            0
+        } else {
+            (((coord(x) - lo) / (hi - lo)) * buckets as f64) as usize
+        };
+        counts[index.min(buckets - 1)] += 1;
+    }
+    counts
+}
+
+/// Count samples into `buckets` log-spaced buckets spanning min..=max.
+///
+/// Log spacing keeps the fast body and the slow tail both visible.
+/// Non-positive samples land in the first bucket.
+pub fn log_histogram(samples: &[f64], buckets: usize) -> Vec<u64> {
+    histogram(
+        samples,
+        &HistogramSpec {
+            buckets,
+            max: None,
+            scale: HistogramScale::Log,
+        },
+    )
+}
+
+// ============================================================
+// Linear fit
+// ============================================================
+
+/// `y = intercept + slope * x` fitted by least squares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearFit {
+    pub intercept: f64,
+    pub slope: f64,
+}
+
+/// Least-squares line through `points` (x, y), or None with fewer than
+/// two distinct x values
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<LinearFit> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    // NaN when there are no points
+    if sxx.is_nan() || sxx <= 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some(LinearFit {
+        intercept: mean_y - slope * mean_x,
+        slope,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trimming_drops_injected_outliers() {
+        let mut samples = vec![10.0; 98];
+        samples.extend([1000.0, 1000.0]);
+        assert_eq!(trimmed_mean(&samples, 0.0), 29.8);
+        assert_eq!(trimmed_mean(&samples, 2.0), 10.0);
+        // one outlier left at 1%
+        assert_eq!(trimmed_mean(&samples, 1.0), (980.0 + 1000.0) / 99.0);
+        assert_eq!(trimmed_mean(&[5.0], 99.0), 5.0);
+    }
+
+    #[test]
+    fn rolling_window_keeps_the_last_samples() {
+        let mut rolling = RollingStats::new(4);
+        for ns in 1..=10 {
+            rolling.record(ns * 100);
+        }
+        assert_eq!(rolling.len(), 4);
+        let stats = rolling.snapshot();
+        assert_eq!(stats.n, 4);
+        assert_eq!(stats.min, 700.0);
+        assert_eq!(stats.max, 1000.0);
+        assert_eq!(stats.mean, 850.0);
+    }
+
+    #[test]
+    fn linear_buckets_up_to_max() {
+        let spec = HistogramSpec { buckets: 10, max: Some(100.0), scale: HistogramScale::Linear };
+        let counts = histogram(&[0.0, 5.0, 15.0, 50.0, 99.9, 100.0, 250.0], &spec);
+        // ten 10 ns buckets from 0, then the overflow bucket
+        assert_eq!(counts, [2, 1, 0, 0, 0, 1, 0, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn fit_recovers_a_line() {
+        let points: Vec<(f64, f64)> = [10.0, 100.0, 1_000.0, 10_000.0].iter().map(|&x| (x, 40.0 + 2.5 * x)).collect();
+        let fit = linear_fit(&points).unwrap();
+        assert!((fit.slope - 2.5).abs() < 1e-9 && (fit.intercept - 40.0).abs() < 1e-6, "{:?}", fit);
+
+        // +1, -1, -1, +1 around y = 3 + 2x: uncorrelated with x, so the
+        // line stays put
+        let noisy = [(0.0, 4.0), (1.0, 4.0), (2.0, 6.0), (3.0, 10.0)];
+        let fit = linear_fit(&noisy).unwrap();
+        assert!((fit.slope - 2.0).abs() < 1e-12 && (fit.intercept - 3.0).abs() < 1e-12, "{:?}", fit);
+
+        assert_eq!(linear_fit(&[]), None);
+        assert_eq!(linear_fit(&[(5.0, 1.0), (5.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn combining_identical_stats_doubles_n() {
+        let samples = [1.0, 2.0, 3.0, 4.0, 10.0];
+        let stats = Stats::from_samples(&samples);
+        let combined = stats.combine(&stats);
+        assert_eq!(combined.n, 10);
+        assert_eq!(combined.mean, stats.mean);
+        assert_eq!((combined.min, combined.max, combined.p50), (1.0, 10.0, stats.p50));
+
+        let doubled: Vec<f64> = samples.iter().chain(samples.iter()).copied().collect();
+        let exact = Stats::from_samples(&doubled);
+        assert!((combined.stddev - exact.stddev).abs() < 1e-12, "{} vs {}", combined.stddev, exact.stddev);
+
+        assert_eq!(Stats::default().combine(&stats), stats);
+        assert!(format!("{:.1}", combined).starts_with("n=10 min=1.0 "), "{}", combined);
+    }
+
+    #[test]
+    fn jitter_of_a_known_set() {
+        // nearest rank over 8 samples: p25 is the 2nd, p75 the 6th
+        let stats = Stats::from_samples(&[7.0, 100.0, 1.0, 6.0, 2.0, 5.0, 3.0, 4.0]);
+        assert_eq!((stats.p25, stats.p75), (2.0, 6.0));
+        assert_eq!(stats.iqr(), 4.0);
+        // the outlier sets the range but not the IQR
+        assert_eq!(stats.peak_to_peak(), 99.0);
+        assert_eq!(Stats::from_samples(&[5.0]).iqr(), 0.0);
+    }
+
+    #[test]
+    fn significant_digits_follow_the_uncertainty() {
+        // the doc example: 0.4 ns tick, 1000 single-call samples
+        assert_eq!(significant_digits(0.4, 1, 1_000), 1);
+        // a 24 MHz timer: nothing after the point for one call...
+        assert_eq!(significant_digits(41.67, 1, 1), 0);
+        // ...but 2 decimals over 100 batches of 1000
+        assert_eq!(significant_digits(41.67, 1_000, 100), 2);
+        // an unknown resolution and a very fine one both cap out
+        assert_eq!(significant_digits(0.0, 1, 1), MAX_SIGNIFICANT_DECIMALS);
+        assert_eq!(significant_digits(1e-12, 1, 1), MAX_SIGNIFICANT_DECIMALS);
+        // batch 0 and no samples count as 1
+        assert_eq!(significant_digits(0.4, 0, 0), significant_digits(0.4, 1, 1));
+    }
+
+    #[test]
+    fn mean_interval_is_symmetric_and_grows_with_spread() {
+        let tight = Stats::from_samples(&[9.0, 10.0, 11.0, 9.0, 10.0, 11.0]);
+        let wide = Stats::from_samples(&[0.0, 10.0, 20.0, 0.0, 10.0, 20.0]);
+        let (low, high) = confidence_interval_95(&tight);
+        assert!((tight.mean - low - (high - tight.mean)).abs() < 1e-9);
+        assert!((high - low - 2.0 * 1.96 * tight.stddev / 6f64.sqrt()).abs() < 1e-9);
+
+        let (wide_low, wide_high) = confidence_interval_95(&wide);
+        assert_eq!(wide.mean, tight.mean);
+        assert!((wide_high - wide_low) > 9.0 * (high - low), "{:?} vs {:?}", (wide_low, wide_high), (low, high));
+        // one sample: just the mean
+        assert_eq!(confidence_interval_95(&Stats::from_samples(&[7.0])), (7.0, 7.0));
+    }
+
+    #[test]
+    fn precision_needs_enough_tight_samples() {
+        // alternating 99/101: stddev ~1, so ±1.96/sqrt(n) ns on a mean of 100
+        let alternating = |n: usize| {
+            let samples: Vec<f64> = (0..n).map(|i| 99.0 + 2.0 * (i % 2) as f64).collect();
+            RunningMoments::from_samples(&samples)
+        };
+        let moments = alternating(100);
+        assert!((moments.mean() - 100.0).abs() < 1e-9);
+        assert!((moments.relative_half_width() - 1.96 * moments.stddev() / 10.0 / 100.0).abs() < 1e-12);
+
+        assert!(precision_reached(&moments, 0.01));
+        assert!(!precision_reached(&moments, 0.001));
+        // tight, but too few samples to trust the interval
+        assert!(!precision_reached(&alternating(MIN_PRECISION_SAMPLES - 1), 0.01));
+        assert!(precision_reached(&RunningMoments::from_samples(&[5.0; MIN_PRECISION_SAMPLES]), 0.0));
+        assert_eq!(RunningMoments::from_samples(&[5.0]).relative_half_width(), f64::INFINITY);
+    }
+}
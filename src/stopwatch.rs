@@ -0,0 +1,326 @@
+use std::time::Duration;
+
+use crate::high_resolution_timer::{tick_hz, ticks_to_ns, HighResolutionTimer};
+
+// ============================================================
+// Pausable stopwatch
+// ============================================================
+
+/// Accumulates only the intervals between `start`/`resume` and `pause`,
+/// e.g. to leave out time spent waiting on I/O
+#[derive(Debug, Clone)]
+pub struct StopWatch {
+    /// Ticks of the intervals already closed by `pause`
+    accumulated_ticks: u128,
+    /// Counter value when the current interval began, if running
+    running_since: Option<u64>,
+}
+
+impl StopWatch {
+    /// A running stopwatch
+    pub fn start() -> Self {
+        let _ = tick_hz(); // calibrate before the first interval
+        Self {
+            accumulated_ticks: 0,
+            running_since: Some(HighResolutionTimer::ticks()),
+        }
+    }
+
+    /// A paused stopwatch at zero
+    pub fn paused() -> Self {
+        Self {
+            accumulated_ticks: 0,
+            running_since: None,
+        }
+    }
+
+    /// Stop counting; no-op if already paused
+    pub fn pause(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.accumulated_ticks += HighResolutionTimer::ticks().wrapping_sub(since) as u128;
+        }
+    }
+
+    /// Continue counting; no-op if already running
+    pub fn resume(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(HighResolutionTimer::ticks());
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    /// Active **nanoseconds**, including the running interval if any
+    pub fn elapsed_ns(&self) -> u128 {
+        let running = self
+            .running_since
+            .map_or(0, |since| HighResolutionTimer::ticks().wrapping_sub(since) as u128);
+        ticks_to_ns(self.accumulated_ticks + running, tick_hz())
+    }
+
+    /// Active time, like `elapsed_ns` (saturates beyond u64 ns)
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(u64::try_from(self.elapsed_ns()).unwrap_or(u64::MAX))
+    }
+
+    /// Report `elapsed_ns` under `label` (see `report_elapsed`)
+    pub fn report(&self, label: &str) {
+        report_elapsed(label, self.elapsed_ns());
+    }
+}
+
+/// Print `label: N ns`, or with the `tracing` feature emit an info
+/// event with `label` and `ns` fields on target `timing_test`
+pub fn report_elapsed(label: &str, ns: u128) {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::info!(target: "timing_test", label, ns = u64::try_from(ns).unwrap_or(u64::MAX), "elapsed");
+    }
+
+    #[cfg(all(not(feature = "tracing"), feature = "cli"))]
+    {
+        println!("{}{} ns", crate::report::label(label), ns);
+    }
+
+    #[cfg(not(any(feature = "tracing", feature = "cli")))]
+    {
+        println!("{}: {} ns", label, ns);
+    }
+}
+
+/// Time a block and report it under a label, returning the block's
+/// value: `let sum = time_block!("sum", { v.iter().sum::<u64>() });`
+#[macro_export]
+macro_rules! time_block {
+    ($label:expr, $body:block) => {{
+        let watch = $crate::stopwatch::StopWatch::start();
+        let value = $body;
+        watch.report($label);
+        value
+    }};
+}
+
+// ============================================================
+// Phase timer
+// ============================================================
+
+/// Timestamps a sequence of named marks, e.g. `mark("parse")`,
+/// `mark("solve")`, `mark("done")`, to split one pass into phases.
+///
+/// A mark stores only the counter value and a label index; conversion
+/// to ns happens in `phases()`.
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimer {
+    labels: Vec<&'static str>,
+    /// (counter value, index into `labels`)
+    marks: Vec<(u64, u32)>,
+}
+
+/// Time from one mark to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phase {
+    /// Label of the mark that began the phase
+    pub label: &'static str,
+    pub ns: u128,
+}
+
+impl PhaseTimer {
+    /// An empty timer with room for `marks` marks
+    pub fn with_capacity(marks: usize) -> Self {
+        let _ = tick_hz(); // calibrate before the first mark
+        Self {
+            labels: Vec::new(),
+            marks: Vec::with_capacity(marks),
+        }
+    }
+
+    /// Record the end of the current phase and the start of `label`
+    pub fn mark(&mut self, label: &'static str) {
+        // read first so the label lookup lands in the new phase
+        let ticks = HighResolutionTimer::ticks();
+        let index = match self.labels.iter().position(|&l| l == label) {
+            Some(index) => index,
+            None => {
+                self.labels.push(label);
+                self.labels.len() - 1
+            }
+        };
+        self.marks.push((ticks, index as u32));
+    }
+
+    /// One phase per pair of consecutive marks, in order.
+    ///
+    /// Each mark is converted as an offset from the first, so the
+    /// phases add up to exactly `total_ns()`. A mark whose counter
+    /// value is below the one before it (a counter not synchronized
+    /// across the cores the thread ran on) ends a 0 ns phase instead of
+    /// underflowing; the sum then exceeds `total_ns()`. Marks below the
+    /// first count as offset 0.
+    pub fn phases(&self) -> Vec<Phase> {
+        let hz = tick_hz();
+        let Some(&(first, _)) = self.marks.first() else {
+            return Vec::new();
+        };
+        let offset_ns = |ticks: u64| ticks_to_ns(ticks_since(first, ticks), hz);
+        self.marks
+            .windows(2)
+            .map(|pair| Phase {
+                label: self.labels[pair[0].1 as usize],
+                ns: offset_ns(pair[1].0).saturating_sub(offset_ns(pair[0].0)),
+            })
+            .collect()
+    }
+
+    /// ns from the first mark to the last (0 with fewer than two, or
+    /// when the last is below the first)
+    pub fn total_ns(&self) -> u128 {
+        match (self.marks.first(), self.marks.last()) {
+            (Some(first), Some(last)) => ticks_to_ns(ticks_since(first.0, last.0), tick_hz()),
+            _ => 0,
+        }
+    }
+}
+
+/// Signed `to - from`, clamped at 0
+fn ticks_since(from: u64, to: u64) -> u128 {
+    (to as i128 - from as i128).max(0) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_interval_is_excluded() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let mut watch = StopWatch::start();
+        std::thread::sleep(Duration::from_millis(2));
+        watch.pause();
+        assert!(!watch.is_running());
+        let paused_at = watch.elapsed_ns();
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(watch.elapsed_ns(), paused_at);
+
+        watch.resume();
+        std::thread::sleep(Duration::from_millis(2));
+        watch.pause();
+        let active = watch.elapsed();
+        assert!(active >= Duration::from_millis(3), "{:?}", active);
+        assert!(active < Duration::from_millis(30), "{:?} includes the pause", active);
+    }
+
+    #[test]
+    fn phases_sum_to_total() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let mut timer = PhaseTimer::with_capacity(3);
+        timer.mark("parse");
+        std::thread::sleep(Duration::from_millis(1));
+        timer.mark("solve");
+        std::thread::sleep(Duration::from_millis(1));
+        timer.mark("done");
+        let phases = timer.phases();
+        assert_eq!(phases.iter().map(|p| p.label).collect::<Vec<_>>(), ["parse", "solve"]);
+        assert!(phases.iter().all(|p| p.ns >= 1_000_000), "{:?}", phases);
+        assert_eq!(phases.iter().map(|p| p.ns).sum::<u128>(), timer.total_ns());
+    }
+
+    #[test]
+    fn backward_mark_is_a_zero_phase() {
+        let hz = tick_hz();
+        let offset_ns = |ticks: u128| ticks_to_ns(ticks, hz);
+        let timer = PhaseTimer {
+            labels: vec!["a", "b", "c"],
+            marks: vec![(1_000, 0), (1_600, 1), (1_500, 2), (3_000, 0)],
+        };
+        let ns: Vec<u128> = timer.phases().iter().map(|p| p.ns).collect();
+        assert_eq!(ns, [offset_ns(600), 0, offset_ns(2_000) - offset_ns(500)]);
+        assert_eq!(timer.total_ns(), offset_ns(2_000));
+    }
+
+    #[test]
+    fn mark_below_the_first_is_offset_zero() {
+        let hz = tick_hz();
+        let timer = PhaseTimer {
+            labels: vec!["a", "b", "c"],
+            marks: vec![(1_000, 0), (900, 1), (1_500, 2)],
+        };
+        let ns: Vec<u128> = timer.phases().iter().map(|p| p.ns).collect();
+        assert_eq!(ns, [0, ticks_to_ns(500, hz)]);
+        assert_eq!(timer.total_ns(), ticks_to_ns(500, hz));
+
+        let backward = PhaseTimer {
+            labels: vec!["a", "b"],
+            marks: vec![(1_000, 0), (400, 1)],
+        };
+        assert_eq!(backward.phases()[0].ns, 0);
+        assert_eq!(backward.total_ns(), 0);
+    }
+
+    /// Records the `label` and `ns` fields and target of every event
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct RecordingSubscriber {
+        events: std::sync::Mutex<Vec<(String, String, u64)>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for RecordingSubscriber {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Fields(String, u64);
+            impl tracing::field::Visit for Fields {
+                fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                    if field.name() == "label" {
+                        self.0 = value.to_string();
+                    }
+                }
+                fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+                    if field.name() == "ns" {
+                        self.1 = value;
+                    }
+                }
+                fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+            }
+
+            let mut fields = Fields(String::new(), 0);
+            event.record(&mut fields);
+            let target = event.metadata().target().to_string();
+            self.events.lock().unwrap().push((target, fields.0, fields.1));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn report_emits_a_tracing_event() {
+        let subscriber = std::sync::Arc::new(RecordingSubscriber::default());
+        tracing::subscriber::with_default(std::sync::Arc::clone(&subscriber), || {
+            report_elapsed("parse", 1_234);
+            report_elapsed("huge", u128::MAX);
+        });
+        let events = subscriber.events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [
+                ("timing_test".to_string(), "parse".to_string(), 1_234),
+                ("timing_test".to_string(), "huge".to_string(), u64::MAX),
+            ]
+        );
+    }
+}
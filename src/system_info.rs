@@ -1,12 +1,586 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::affinity::{current_numa_node, is_current_cpu_isolated, scheduling_policy};
+use crate::date_time_tool::{current_timestamp, iso8601_utc};
+use crate::high_resolution_timer::{
+    active_backend, available_backends, calibration_error_ppm, calibration_was_cached, calibration_window,
+    cpu_brand, estimated_base_frequency_mhz, frequency_discrepancy_percent, looks_emulated, mach_timebase,
+    mach_timebase_hz, nominal_base_frequency_mhz, rdtsc_read_cost_ns, rdtscp_supported, timer_resolution_ns,
+    tsc_deadline_supported, tsc_is_invariant,
+};
+use crate::report::label;
 
 pub fn report_sys_info()  {
     println!("\n ---------------OS and CPU info----------------- \n" );
     //time::Instant::now().elapsed().as_nanos() as u64
-    println!("Operation system: \t{}", std::env::consts::OS);
-    println!("OS Family: \t\t{}", std::env::consts::FAMILY);
+    println!("{}{}", label("Operation system"), std::env::consts::OS);
+    println!("{}{}", label("OS Family"), std::env::consts::FAMILY);
     
     // 架构
-    println!("Architecture: \t\t{}", std::env::consts::ARCH);
+    println!("{}{}", label("Architecture"), std::env::consts::ARCH);
+
+    let timer = active_backend();
+    println!("{}{} ({})", label("Timer backend"), timer.name(), timer.description());
+    println!("{}{:.3} ns/tick", label("Timer resolution"), timer_resolution_ns());
+    if let Some((numer, denom)) = mach_timebase() {
+        println!("{}{}/{} ns/tick ({} Hz)", label("Mach timebase"), numer, denom, mach_timebase_hz(numer, denom));
+    }
+
+    // TSC only equals the base clock when it is invariant
+    println!("{}{}", label("Invariant TSC"), tsc_is_invariant());
+    // explains how closely sleeps and timer waits can hit their target
+    println!("{}{}", label("TSC deadline timer"), tsc_deadline_supported());
+    match estimated_base_frequency_mhz() {
+        Some(mhz) => println!("{}{} MHz", label("Base frequency (TSC)"), mhz),
+        None if tsc_is_invariant() => println!("{}N/A (timer does not read the TSC)", label("Base frequency (TSC)")),
+        None => println!("{}N/A (TSC not invariant)", label("Base frequency (TSC)")),
+    }
+    match nominal_base_frequency_mhz() {
+        Some(mhz) => println!("{}{:.0} MHz", label("Nominal frequency"), mhz),
+        None => println!("{}N/A (not advertised)", label("Nominal frequency")),
+    }
+    if let Some(percent) = frequency_discrepancy_percent() {
+        println!("{}{:+.2}%", label("TSC vs nominal"), percent);
+    }
+    match calibration_error_ppm() {
+        Some(ppm) => println!(
+            "{}{:.1} ppm ({} ms window)",
+            label("Calibration error"),
+            ppm,
+            calibration_window().as_secs_f64() * 1e3
+        ),
+        None if calibration_was_cached() => println!("{}N/A (cached frequency)", label("Calibration error")),
+        None => println!("{}N/A (hardware-reported frequency)", label("Calibration error")),
+    }
+    match rdtsc_read_cost_ns() {
+        Some(ns) if looks_emulated(ns) => {
+            println!("{}{:.1} ns/read (probably trapped/emulated)", label("rdtsc cost"), ns)
+        }
+        Some(ns) => println!("{}{:.1} ns/read", label("rdtsc cost"), ns),
+        None => println!("{}N/A (timer does not read the TSC)", label("rdtsc cost")),
+    }
+
+    println!("{}{}", label("Scheduling policy"), scheduling_policy());
+    match is_current_cpu_isolated() {
+        Some(true) => println!("{}yes (isolcpus/nohz_full)", label("Isolated CPU")),
+        Some(false) => println!("{}no", label("Isolated CPU")),
+        None => println!("{}N/A", label("Isolated CPU")),
+    }
+    match current_numa_node() {
+        Some(node) => println!("{}{}", label("NUMA node"), node),
+        None => println!("{}N/A (single node)", label("NUMA node")),
+    }
+
+    // a descheduled thread stays off-CPU for about one tick
+    match scheduler_hz() {
+        Some(hz) => println!("{}{} Hz", label("Scheduler tick"), hz),
+        None => println!("{}unknown", label("Scheduler tick")),
+    }
+
+    let env = environment_snapshot();
+    println!("{}{}", label("CPU governor"), env.governor.as_deref().unwrap_or("unknown"));
+    println!("{}{}", label("Turbo"), on_off(env.turbo));
+    println!("{}{}", label("SMT"), on_off(env.smt));
+    println!("{}{}", label("Instrumentation"), instrumentation().as_deref().unwrap_or("none detected"));
+    let counters = if perf_counters_available() { "available" } else { "unavailable" };
+    match perf_event_paranoid() {
+        Some(level) => println!("{}{} (perf_event_paranoid {})", label("Perf counters"), counters, level),
+        None => println!("{}{}", label("Perf counters"), counters),
+    }
+}
+
+fn on_off(state: Option<bool>) -> &'static str {
+    match state {
+        Some(true) => "on",
+        Some(false) => "off",
+        None => "unknown",
+    }
+}
+
+// ============================================================
+// Reproducibility settings
+// ============================================================
+
+/// The CPU settings that most affect run-to-run repeatability; each is
+/// None where it can't be read (non-Linux, missing sysfs file)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvironmentSnapshot {
+    /// cpufreq governor of CPU 0, e.g. "performance"
+    pub governor: Option<String>,
+    /// Whether turbo/boost is enabled
+    pub turbo: Option<bool>,
+    /// Whether SMT (hyper-threading) is active
+    pub smt: Option<bool>,
+}
+
+/// Read governor, turbo and SMT state from sysfs (Linux)
+pub fn environment_snapshot() -> EnvironmentSnapshot {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let read = |path: &str| std::fs::read_to_string(path).ok();
+        let turbo = read("/sys/devices/system/cpu/intel_pstate/no_turbo")
+            .and_then(|s| parse_no_turbo(&s))
+            // acpi-cpufreq / amd-pstate expose the inverse flag
+            .or_else(|| read("/sys/devices/system/cpu/cpufreq/boost").and_then(|s| parse_flag(&s)));
+        EnvironmentSnapshot {
+            governor: read("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor")
+                .and_then(|s| parse_governor(&s)),
+            turbo,
+            smt: read("/sys/devices/system/cpu/smt/active").and_then(|s| parse_flag(&s)),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        EnvironmentSnapshot::default()
+    }
+}
+
+/// Contents of a `scaling_governor` file
+pub fn parse_governor(contents: &str) -> Option<String> {
+    let governor = contents.trim();
+    (!governor.is_empty()).then(|| governor.to_string())
+}
+
+/// Turbo state from an intel_pstate `no_turbo` file ("1" = disabled)
+pub fn parse_no_turbo(contents: &str) -> Option<bool> {
+    parse_flag(contents).map(|no_turbo| !no_turbo)
+}
+
+/// A sysfs "0"/"1" flag such as `smt/active` or `cpufreq/boost`
+pub fn parse_flag(contents: &str) -> Option<bool> {
+    match contents.trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+// ============================================================
+// Version banner
+// ============================================================
+
+/// Target triple this binary was built for (set by build.rs)
+pub const TARGET_TRIPLE: &str = env!("TARGET_TRIPLE");
+
+/// `rustc --version` of the compiler that built this binary (set by
+/// build.rs; empty if it couldn't be run)
+pub const RUSTC_VERSION: &str = env!("RUSTC_VERSION");
+
+/// Cargo's `OPT_LEVEL` for this build: "0"-"3", "s" or "z" (set by
+/// build.rs)
+pub const BUILD_OPT_LEVEL: &str = env!("BUILD_OPT_LEVEL");
+
+/// `--version` output: crate version, target, compiled-in backends and
+/// the CPU features the timer relies on, for pasting into bug reports
+pub fn version_banner() -> String {
+    let backends: Vec<String> = available_backends()
+        .into_iter()
+        .map(|b| {
+            let selected = if b == active_backend() { " (selected)" } else { "" };
+            format!("{}{}", b.name(), selected)
+        })
+        .collect();
+    let yes_no = |available: bool| if available { "yes" } else { "no" };
+    format!(
+        "{} {}\n{}{} ({})\n{}{} (opt-level {})\n{}{}\n{}{}\n{}{}\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        label("Target"),
+        TARGET_TRIPLE,
+        std::env::consts::ARCH,
+        label("Compiler"),
+        RUSTC_VERSION,
+        BUILD_OPT_LEVEL,
+        label("Backends"),
+        backends.join(", "),
+        label("rdtscp"),
+        yes_no(rdtscp_supported()),
+        label("Invariant TSC"),
+        yes_no(tsc_is_invariant())
+    )
+}
+
+// ============================================================
+// Run identity
+// ============================================================
+
+/// Where and from what a run came, for grouping results from several
+/// machines and commits
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// "unknown" when it can't be read
+    pub hostname: String,
+    /// "unknown" when it can't be read
+    pub cpu_brand: String,
+    /// `GIT_COMMIT`, else `git rev-parse HEAD`, else "unknown"
+    pub commit: String,
+    /// ISO-8601 UTC time the identity was taken
+    pub timestamp: String,
+    /// `rustc --version` of the build, "unknown" if not captured
+    pub rustc: String,
+    /// Optimization level of the build (`BUILD_OPT_LEVEL`)
+    pub opt_level: String,
+    /// Built with debug assertions (a debug profile, usually)
+    pub debug_assertions: bool,
+}
+
+/// Identity of the current run
+pub fn identity() -> Identity {
+    identity_from(hostname(), cpu_brand(), git_commit(), current_timestamp())
+}
+
+/// Identity from already-read parts, "unknown" for a missing one
+fn identity_from(
+    hostname: Option<String>,
+    cpu_brand: Option<String>,
+    commit: Option<String>,
+    timestamp_ns: u64,
+) -> Identity {
+    let unknown = || "unknown".to_string();
+    Identity {
+        hostname: hostname.unwrap_or_else(unknown),
+        cpu_brand: cpu_brand.unwrap_or_else(unknown),
+        commit: commit.unwrap_or_else(unknown),
+        timestamp: iso8601_utc(timestamp_ns),
+        rustc: Some(RUSTC_VERSION.to_string()).filter(|v| !v.is_empty()).unwrap_or_else(unknown),
+        opt_level: Some(BUILD_OPT_LEVEL.to_string()).filter(|v| !v.is_empty()).unwrap_or_else(unknown),
+        debug_assertions: cfg!(debug_assertions),
+    }
+}
+
+/// Host name of this machine
+pub fn hostname() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+            return None;
+        }
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        Some(String::from_utf8_lossy(&buf[..len]).into_owned()).filter(|name| !name.is_empty())
+    }
+
+    #[cfg(windows)]
+    {
+        std::env::var("COMPUTERNAME").ok()
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        None
+    }
+}
+
+/// Commit being measured: the `GIT_COMMIT` environment variable, else
+/// `git rev-parse HEAD` in the working directory
+pub fn git_commit() -> Option<String> {
+    commit_from(std::env::var("GIT_COMMIT").ok(), || {
+        let output = std::process::Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()?;
+        let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        (output.status.success() && !commit.is_empty()).then_some(commit)
+    })
+}
+
+/// `git_commit` given the `GIT_COMMIT` value, if set, and the
+/// `git rev-parse HEAD` fallback
+fn commit_from(env: Option<String>, rev_parse: impl FnOnce() -> Option<String>) -> Option<String> {
+    env.filter(|commit| !commit.is_empty()).or_else(rev_parse)
 }
 
+// ============================================================
+// Debugger / tracer / valgrind
+// ============================================================
+
+/// What this process is running under, if detectable: a ptrace tracer
+/// (gdb, strace) on Linux, valgrind, or an attached Windows debugger.
+/// Each slows calls enough that timings stop meaning anything.
+pub fn instrumentation() -> Option<String> {
+    // valgrind preloads its core into the client
+    if std::env::var("LD_PRELOAD").is_ok_and(|preload| preload.contains("vgpreload")) {
+        return Some("valgrind".to_string());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let pid = parse_tracer_pid(&status)?;
+        let tracer = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok();
+        Some(match tracer {
+            Some(name) => format!("traced by {} (pid {})", name.trim(), pid),
+            None => format!("traced by pid {}", pid),
+        })
+    }
+
+    #[cfg(windows)]
+    {
+        let attached = unsafe { windows_sys::Win32::System::Diagnostics::Debug::IsDebuggerPresent() } != 0;
+        attached.then(|| "debugger attached".to_string())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    {
+        None
+    }
+}
+
+/// Whether `instrumentation()` found anything
+pub fn under_instrumentation() -> bool {
+    instrumentation().is_some()
+}
+
+/// Nonzero `TracerPid:` of a /proc/<pid>/status file
+pub fn parse_tracer_pid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("TracerPid:"))
+        .and_then(|pid| pid.trim().parse().ok())
+        .filter(|&pid| pid != 0)
+}
+
+// ============================================================
+// Scheduler tick rate
+// ============================================================
+
+/// Kernel scheduler tick rate (CONFIG_HZ), if it can be determined.
+///
+/// Linux only: reads the running kernel's config from /boot, else
+/// infers it from /proc/timer_list.
+pub fn scheduler_hz() -> Option<u64> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let release = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+        let config = format!("/boot/config-{}", release.trim());
+        if let Some(hz) = std::fs::read_to_string(config).ok().and_then(|c| parse_config_hz(&c)) {
+            return Some(hz);
+        }
+        std::fs::read_to_string("/proc/timer_list")
+            .ok()
+            .and_then(|c| parse_timer_list_hz(&c))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        None
+    }
+}
+
+/// Extract `CONFIG_HZ=<n>` from kernel config content
+pub fn parse_config_hz(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("CONFIG_HZ="))
+        .and_then(|value| value.parse().ok())
+}
+
+/// Infer HZ from the `now at <ns> nsecs` and `jiffies: <n>` lines of
+/// /proc/timer_list.
+///
+/// The kernel starts jiffies at 2^32 - 300*HZ (5 minutes before the
+/// 32-bit wrap), so `jiffies - 2^32 == HZ * (uptime_s - 300)`. The
+/// estimate is snapped to a standard HZ value.
+pub fn parse_timer_list_hz(content: &str) -> Option<u64> {
+    let now_ns: i128 = content.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("now at ")?
+            .strip_suffix(" nsecs")?
+            .parse()
+            .ok()
+    })?;
+    let jiffies: i128 = content.lines().find_map(|line| {
+        line.trim().strip_prefix("jiffies: ")?.parse().ok()
+    })?;
+
+    let elapsed_ns = now_ns - 300 * 1_000_000_000;
+    // too close to the 300s mark for a stable ratio
+    if elapsed_ns.abs() < 10 * 1_000_000_000 {
+        return None;
+    }
+    let estimate = (jiffies - (1i128 << 32)) as f64 * 1e9 / elapsed_ns as f64;
+
+    [100u64, 250, 300, 1000]
+        .into_iter()
+        .find(|&hz| (estimate - hz as f64).abs() <= hz as f64 * 0.1)
+}
+
+// ============================================================
+// Hardware performance counters
+// ============================================================
+
+/// `/proc/sys/kernel/perf_event_paranoid`: -1 allows everything, 2 and
+/// above only user-space counting of your own processes (distros and
+/// Android patch in levels that forbid even that)
+pub fn perf_event_paranoid() -> Option<i32> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        std::fs::read_to_string("/proc/sys/kernel/perf_event_paranoid").ok()?.trim().parse().ok()
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        None
+    }
+}
+
+/// Whether this process can open a hardware cycle counter with
+/// `perf_event_open` (user space only, so it works up to paranoid 2).
+///
+/// False off Linux/Android, and in containers or VMs without a PMU.
+pub fn perf_counters_available() -> bool {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        /// The first, 64-byte version of `struct perf_event_attr`
+        #[repr(C)]
+        struct PerfEventAttr {
+            kind: u32,
+            size: u32,
+            config: u64,
+            sample_period: u64,
+            sample_type: u64,
+            read_format: u64,
+            flags: u64,
+            wakeup_events: u32,
+            bp_type: u32,
+            bp_addr: u64,
+        }
+        const PERF_TYPE_HARDWARE: u32 = 0;
+        const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+        // disabled | exclude_kernel | exclude_hv
+        const FLAGS: u64 = 1 | 1 << 5 | 1 << 6;
+
+        let attr = PerfEventAttr {
+            kind: PERF_TYPE_HARDWARE,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config: PERF_COUNT_HW_CPU_CYCLES,
+            sample_period: 0,
+            sample_type: 0,
+            read_format: 0,
+            flags: FLAGS,
+            wakeup_events: 0,
+            bp_type: 0,
+            bp_addr: 0,
+        };
+        // this thread, any CPU, no group
+        let fd = unsafe { libc::syscall(libc::SYS_perf_event_open, &attr as *const PerfEventAttr, 0, -1, -1, 0) };
+        if fd < 0 {
+            return false;
+        }
+        unsafe { libc::close(fd as libc::c_int) };
+        true
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scheduler_hz() {
+        let config = "# CONFIG_HZ_100 is not set\nCONFIG_HZ_250=y\nCONFIG_HZ=250\n";
+        assert_eq!(parse_config_hz(config), Some(250));
+        assert_eq!(parse_config_hz("CONFIG_HZ_250=y\n"), None);
+
+        // 1000 s past the 300 s jiffies offset at HZ=1000
+        let timer_list = format!("now at 1300000000000 nsecs\n  jiffies: {}\n", (1u64 << 32) + 1_000_000);
+        assert_eq!(parse_timer_list_hz(&timer_list), Some(1000));
+        assert_eq!(parse_timer_list_hz("now at 1300000000000 nsecs\n"), None);
+    }
+
+    #[test]
+    fn identity_has_host_and_commit() {
+        let id = identity_from(Some("bench-01".into()), None, Some("0123abc".into()), 1_700_000_000_000_000_000);
+        assert_eq!(
+            (id.hostname.as_str(), id.cpu_brand.as_str(), id.commit.as_str()),
+            ("bench-01", "unknown", "0123abc")
+        );
+        assert_eq!(id.timestamp, "2023-11-14T22:13:20Z");
+        let json = crate::report::identity_json(&id);
+        assert!(json.contains("\"hostname\":\"bench-01\""), "{}", json);
+        assert!(json.contains("\"commit\":\"0123abc\""), "{}", json);
+    }
+
+    #[test]
+    fn git_commit_env_beats_rev_parse() {
+        let rev_parse = || Some("fedcba9".to_string());
+        assert_eq!(commit_from(Some("0123abc".into()), rev_parse).as_deref(), Some("0123abc"));
+        assert_eq!(commit_from(Some(String::new()), rev_parse).as_deref(), Some("fedcba9"));
+        assert_eq!(commit_from(None, rev_parse).as_deref(), Some("fedcba9"));
+        assert_eq!(commit_from(None, || None), None);
+    }
+
+    #[test]
+    fn parses_sysfs_samples() {
+        // scaling_governor
+        assert_eq!(parse_governor("performance\n").as_deref(), Some("performance"));
+        assert_eq!(parse_governor("\n"), None);
+        // intel_pstate/no_turbo
+        assert_eq!(parse_no_turbo("1\n"), Some(false));
+        assert_eq!(parse_no_turbo("0\n"), Some(true));
+        // smt/active, cpufreq/boost
+        assert_eq!(parse_flag("1\n"), Some(true));
+        assert_eq!(parse_flag("0"), Some(false));
+        assert_eq!(parse_flag("notsupported\n"), None);
+
+        let json = crate::report::environment_json(&EnvironmentSnapshot {
+            governor: Some("power\"save".into()),
+            turbo: Some(true),
+            smt: None,
+        });
+        assert_eq!(json, "{\"governor\":\"power\\\"save\",\"turbo\":true,\"smt\":null}");
+    }
+
+    #[test]
+    fn banner_names_arch_and_backends() {
+        let _lock = crate::high_resolution_timer::test_lock();
+        let banner = version_banner();
+        assert!(banner.starts_with(concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"), "\n")), "{}", banner);
+        assert!(banner.contains(&format!("{} ({})", TARGET_TRIPLE, std::env::consts::ARCH)), "{}", banner);
+        let backends = banner.lines().find(|l| l.starts_with("Backends")).expect("Backends line");
+        assert!(backends.contains(&format!("{} (selected)", active_backend().name())), "{}", backends);
+    }
+
+    #[test]
+    fn instrumentation_check_returns() {
+        // a test run may itself be traced, so only agreement is checked
+        assert_eq!(under_instrumentation(), instrumentation().is_some());
+
+        assert_eq!(parse_tracer_pid("Name:\ttiming-test\nTracerPid:\t4242\nUid:\t0\n"), Some(4242));
+        assert_eq!(parse_tracer_pid("Name:\ttiming-test\nTracerPid:\t0\n"), None);
+        assert_eq!(parse_tracer_pid("Name:\ttiming-test\n"), None);
+    }
+
+    #[test]
+    fn perf_counter_probe_is_stable() {
+        let available: bool = perf_counters_available();
+        // the probe closes its counter, so asking again gives the same answer
+        assert_eq!(perf_counters_available(), available);
+
+        match perf_event_paranoid() {
+            Some(level) => assert!((-1..=4).contains(&level), "{}", level),
+            None => {
+                let exposed = std::path::Path::new("/proc/sys/kernel/perf_event_paranoid").exists();
+                assert!(!cfg!(target_os = "linux") || !exposed);
+            }
+        }
+        if !cfg!(any(target_os = "linux", target_os = "android")) {
+            assert!(!available);
+        }
+    }
+
+    #[test]
+    fn identity_names_the_compiler() {
+        let identity = identity();
+        // build.rs could run the same rustc that compiles these tests
+        assert!(identity.rustc.starts_with("rustc "), "{}", identity.rustc);
+        assert!(["0", "1", "2", "3", "s", "z"].contains(&identity.opt_level.as_str()), "{}", identity.opt_level);
+        assert_eq!(identity.debug_assertions, cfg!(debug_assertions));
+    }
+}
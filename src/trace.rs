@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::high_resolution_timer::{tick_hz, ticks_to_ns, HighResolutionTimer};
+
+// ============================================================
+// Opt-in event log (--trace)
+// ============================================================
+
+/// What happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceKind {
+    CalibrationStart,
+    CalibrationEnd,
+    /// A calibration window failed its checks and is retried
+    CalibrationRejected,
+    Recalibrated,
+    WarmupDone,
+    RunStart,
+    RunEnd,
+}
+
+impl TraceKind {
+    /// Short lowercase name, as printed by `render_trace`
+    pub fn name(self) -> &'static str {
+        match self {
+            TraceKind::CalibrationStart => "calibration start",
+            TraceKind::CalibrationEnd => "calibration end",
+            TraceKind::CalibrationRejected => "calibration rejected",
+            TraceKind::Recalibrated => "recalibrated",
+            TraceKind::WarmupDone => "warmup done",
+            TraceKind::RunStart => "run start",
+            TraceKind::RunEnd => "run end",
+        }
+    }
+}
+
+/// One recorded event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Raw counter value when it was recorded
+    pub ticks: u64,
+    pub kind: TraceKind,
+    /// e.g. "freq=2099999000 Hz rse=1.2 ppm" or the benchmark name
+    pub detail: String,
+}
+
+static TRACE_ENABLED: AtomicBool = AtomicBool::new(false);
+static TRACE: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+
+/// Start recording events (off by default)
+pub fn enable_trace() {
+    TRACE_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stop recording events; what was recorded stays until `take_trace`
+pub fn disable_trace() {
+    TRACE_ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Record an event if tracing is enabled; one atomic load otherwise.
+///
+/// Stores raw ticks rather than ns, so it is safe to call from inside
+/// calibration.
+pub fn trace_event(kind: TraceKind, detail: impl FnOnce() -> String) {
+    if !TRACE_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let ticks = HighResolutionTimer::ticks();
+    if let Ok(mut events) = TRACE.lock() {
+        events.push(TraceEvent { ticks, kind, detail: detail() });
+    }
+}
+
+/// Remove and return the events recorded so far, oldest first
+pub fn take_trace() -> Vec<TraceEvent> {
+    TRACE.lock().map(|mut events| std::mem::take(&mut *events)).unwrap_or_default()
+}
+
+/// One line per event: ns since the first event, kind and detail
+pub fn render_trace(events: &[TraceEvent]) -> String {
+    let Some(first) = events.first() else {
+        return String::new();
+    };
+    let hz = tick_hz();
+    let mut out = String::new();
+    for event in events {
+        let ns = ticks_to_ns(event.ticks.wrapping_sub(first.ticks) as u128, hz);
+        out.push_str(&format!("{:>14} ns  {}", ns, event.kind.name()));
+        if !event.detail.is_empty() {
+            out.push_str(&format!(" ({})", event.detail));
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::benchmark::{clock_read_backend, BenchmarkResult};
+use crate::high_resolution_timer::{
+    calibration_error_ppm, is_below_resolution, is_suspicious_clock_read, rdtsc_looks_emulated, typical_clock_read_ns,
+};
+use crate::runner::RunConfig;
+use crate::system_info::instrumentation;
+
+// ============================================================
+// Structured warnings
+// ============================================================
+// Everything that makes a result less trustworthy, as data: carried in
+// each result's `warnings` for the JSON/CSV output as well as printed.
+
+/// What a `Warning` is about; `code()` is the stable id in JSON/CSV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    /// Built without optimizations
+    DebugBuild,
+    /// Under valgrind, a tracer or similar (see `instrumentation`)
+    Instrumentation,
+    /// rdtsc reads cost so much they are probably trapped
+    EmulatedRdtsc,
+    /// The calibration's segments disagreed by more than
+    /// `NOISY_CALIBRATION_PPM`
+    NoisyCalibration,
+    /// Many involuntary context switches or major faults during the run
+    SystemNoise,
+    /// Interrupts frequent enough to account for the tail
+    Interrupts,
+    /// The measurement crossed NUMA nodes
+    NumaCrossing,
+    /// Per-call cost below one timer tick
+    BelowResolution,
+    /// A clock read far outside the usual band for its backend
+    SuspiciousClockRead,
+    /// The thread moved between cores mid-measurement
+    CoreMigrations,
+    /// Too few samples for the percentiles (see `--min-samples`)
+    UnreliablePercentiles,
+    /// Timer and wall clock disagreed on a run's length (kept, and
+    /// flagged in the report)
+    ClockAnomaly,
+    /// `--target-precision` hit the loops cap first
+    PrecisionNotReached,
+}
+
+impl WarningKind {
+    pub fn code(self) -> &'static str {
+        match self {
+            WarningKind::DebugBuild => "debug_build",
+            WarningKind::Instrumentation => "instrumentation",
+            WarningKind::EmulatedRdtsc => "emulated_rdtsc",
+            WarningKind::NoisyCalibration => "noisy_calibration",
+            WarningKind::SystemNoise => "system_noise",
+            WarningKind::Interrupts => "interrupts",
+            WarningKind::NumaCrossing => "numa_crossing",
+            WarningKind::BelowResolution => "below_resolution",
+            WarningKind::SuspiciousClockRead => "suspicious_clock_read",
+            WarningKind::CoreMigrations => "core_migrations",
+            WarningKind::UnreliablePercentiles => "unreliable_percentiles",
+            WarningKind::ClockAnomaly => "clock_anomaly",
+            WarningKind::PrecisionNotReached => "precision_not_reached",
+        }
+    }
+}
+
+/// One warning: its kind plus a sentence for people
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Calibration error (ppm) above which `run_warnings` flags it; well
+/// below the `MAX_CALIBRATION_ERROR_PPM` that rejects a calibration
+pub const NOISY_CALIBRATION_PPM: f64 = 1_000.0;
+
+/// Warnings about the whole run's setup, for every result of it.
+/// Call after calibration, or the noisy-calibration check can't run.
+pub fn run_warnings() -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if cfg!(debug_assertions) {
+        warnings.push(Warning::new(WarningKind::DebugBuild, "debug build; build with --release for real costs"));
+    }
+    if let Some(under) = instrumentation() {
+        warnings.push(Warning::new(
+            WarningKind::Instrumentation,
+            format!("running under instrumentation ({}); timings will be inflated", under),
+        ));
+    }
+    if rdtsc_looks_emulated() {
+        warnings.push(Warning::new(WarningKind::EmulatedRdtsc, "rdtsc looks trapped/emulated (hypervisor or sandbox)"));
+    }
+    if let Some(ppm) = calibration_error_ppm().filter(|ppm| *ppm > NOISY_CALIBRATION_PPM) {
+        warnings.push(Warning::new(
+            WarningKind::NoisyCalibration,
+            format!("calibration error {:.0} ppm (> {} ppm); ns conversions are imprecise", ppm, NOISY_CALIBRATION_PPM),
+        ));
+    }
+    warnings
+}
+
+/// Warnings that follow from `result` itself
+pub fn result_warnings(result: &BenchmarkResult, config: &RunConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let ns = result.ns_per_call();
+    if is_below_resolution(ns) {
+        warnings.push(Warning::new(
+            WarningKind::BelowResolution,
+            format!("{}: {:.3} ns/call is below the timer resolution", result.name, ns),
+        ));
+    }
+    if let Some(backend) = clock_read_backend(&result.name).filter(|backend| is_suspicious_clock_read(*backend, ns)) {
+        let (low, high) = typical_clock_read_ns(backend);
+        warnings.push(Warning::new(
+            WarningKind::SuspiciousClockRead,
+            format!("{}: {:.1} ns, but {} reads usually take {}-{} ns", result.name, ns, backend.name(), low, high),
+        ));
+    }
+    if result.migrations > 0 {
+        warnings.push(Warning::new(
+            WarningKind::CoreMigrations,
+            format!("{}: {} core migrations during measurement; consider --cpu", result.name, result.migrations),
+        ));
+    }
+    if !result.percentiles_reliable(config.min_samples) {
+        warnings.push(Warning::new(
+            WarningKind::UnreliablePercentiles,
+            format!("{}: {} samples (< {}); p99 is unreliable", result.name, result.samples.len(), config.min_samples),
+        ));
+    }
+    warnings
+}